@@ -0,0 +1,33 @@
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+mod tests {
+    use criterion::Criterion;
+    use workflow_perf_monitor::mem::get_process_memory_info;
+
+    // `get_process_memory_info` on one thread, as a sampling loop does: the `task_info` call
+    // dominates, `mach_task_self()` is a load of a global.
+    pub fn bench_process_memory_info(c: &mut Criterion) {
+        c.bench_function("process memory info", |b| {
+            b.iter(|| get_process_memory_info().unwrap());
+        });
+    }
+
+    pub fn bench_mach_task_self(c: &mut Criterion) {
+        c.bench_function("mach_task_self", |b| {
+            b.iter(|| unsafe { mach::traps::mach_task_self() });
+        });
+    }
+}
+
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+criterion::criterion_group!(
+    benches,
+    tests::bench_process_memory_info,
+    tests::bench_mach_task_self
+);
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+criterion::criterion_main!(benches);
+
+#[cfg(not(any(target_os = "ios", target_os = "macos")))]
+fn main() {
+    println!("This benchmark can only be run on iOS or MacOS.");
+}
@@ -0,0 +1,19 @@
+//! The child of `mem::spawn_balloon`: it allocates and touches the number of bytes given as its
+//! argument, prints `ready`, and holds the memory until its stdin is closed.
+use std::io::{Read, Write};
+
+fn main() {
+    let size: usize = std::env::args()
+        .nth(1)
+        .and_then(|size| size.parse().ok())
+        .expect("usage: perf-balloon <size in bytes>");
+    // a non-zero value writes every byte, so the pages are resident.
+    let balloon = std::hint::black_box(vec![1u8; size]);
+
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "ready").expect("write to the parent");
+    stdout.flush().expect("write to the parent");
+    // EOF, or an error, once the parent closes the pipe or exits.
+    let _ = std::io::stdin().read_to_end(&mut Vec::new());
+    drop(balloon);
+}
@@ -0,0 +1,113 @@
+//! The source of time of the stateful helpers, e.g. `GrowthRate` and `BucketAggregator`, so
+//! that their windowing can be tested without sleeping.
+use std::time::Instant;
+
+/// A source of `Instant`s.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The real clock, `Instant::now`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for std::sync::Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockClock;
+
+#[cfg(any(test, feature = "test-util"))]
+mod mock {
+    use super::Clock;
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    /// A clock which only moves when advanced (`test-util` feature).
+    ///
+    /// The clones share the time: keep one to drive the clone given to the helper.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use workflow_perf_monitor::{clock::MockClock, mem::{GrowthRate, ProcessMemoryInfo}};
+    /// let clock = MockClock::new();
+    /// let mut rate = GrowthRate::with_clock(Duration::from_secs(60), clock.clone());
+    /// for rss in [1000, 2000] {
+    ///     rate.observe_now(&ProcessMemoryInfo {
+    ///         resident_set_size: rss,
+    ///         ..Default::default()
+    ///     });
+    ///     clock.advance(Duration::from_secs(1));
+    /// }
+    /// assert_eq!(rate.bytes_per_sec(), 1000.0);
+    /// ```
+    #[derive(Clone, Debug)]
+    pub struct MockClock {
+        now: Arc<Mutex<Instant>>,
+    }
+
+    impl Default for MockClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl MockClock {
+        /// A clock stopped at the current `Instant`.
+        pub fn new() -> Self {
+            MockClock {
+                now: Arc::new(Mutex::new(Instant::now())),
+            }
+        }
+
+        /// Move the time of this clock and of its clones forward by `by`.
+        pub fn advance(&self, by: Duration) {
+            *self.lock() += by;
+        }
+
+        fn lock(&self) -> std::sync::MutexGuard<'_, Instant> {
+            self.now.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.lock()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let shared = clock.clone();
+        assert_eq!(shared.now(), start);
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(shared.now(), start + Duration::from_secs(3));
+        assert_eq!((&shared).now(), start + Duration::from_secs(3));
+        assert!(SystemClock.now() >= start);
+    }
+}
@@ -0,0 +1,88 @@
+//! The API of the upstream [`perf_monitor`](https://crates.io/crates/perf_monitor) crate,
+//! to migrate with a single `use`:
+//!
+//! ```
+//! use workflow_perf_monitor::compat as perf_monitor;
+//!
+//! let info = perf_monitor::mem::get_process_memory_info().unwrap();
+//! let mut stat = perf_monitor::cpu::ProcessStat::cur().unwrap();
+//! let _ = stat.cpu().unwrap();
+//! ```
+//!
+//! Most items are the same ones as in this crate, a few behave differently:
+//!
+//! | item | |
+//! | -- | -- |
+//! | `cpu::{ProcessStat, ThreadStat, ThreadId, processor_numbers}` | faithful |
+//! | `cpu::cpu_time` | faithful, on Linux it falls back to `/proc/self/stat`, in clock ticks, where `clock_gettime` is denied |
+//! | `mem::get_process_memory_info` | faithful |
+//! | `mem::ProcessMemoryInfo` | has more fields: `shared` and `text` on Linux, `virtual_reserved` on Windows, struct literals need `..Default::default()` |
+//! | `mem::CountingAllocator` | faithful |
+//! | `mem::apple` | faithful, MacOS only |
+//! | `io::{get_process_io_stats, IOStatsError}` | faithful |
+//! | `io::IOStats` | has one more field: `cancelled_write_bytes` on Linux, which the upstream crate doesn't read, struct literals need `..Default::default()` |
+//! | `fd::fd_count_cur` | faithful |
+
+/// See [`crate::cpu`].
+pub mod cpu {
+    pub use crate::cpu::{cpu_time, processor_numbers, ProcessStat, ThreadId, ThreadStat};
+}
+
+/// See [`crate::mem`].
+pub mod mem {
+    #[cfg(target_os = "macos")]
+    pub use crate::mem::apple;
+    pub use crate::mem::{get_process_memory_info, CountingAllocator, ProcessMemoryInfo};
+}
+
+/// See [`crate::io`].
+pub mod io {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "windows"
+    ))]
+    pub use crate::io::get_process_io_stats;
+    pub use crate::io::{IOStats, IOStatsError};
+}
+
+/// See [`crate::fd`].
+pub mod fd {
+    pub use crate::fd::fd_count_cur;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compat as perf_monitor;
+
+    #[test]
+    fn test_aliases() {
+        let info = perf_monitor::mem::get_process_memory_info().unwrap();
+        let _: &perf_monitor::mem::ProcessMemoryInfo = &info;
+        assert!(info.resident_set_size > 0);
+        let _ = perf_monitor::mem::CountingAllocator::get_allocated();
+
+        let mut stat = perf_monitor::cpu::ProcessStat::cur().unwrap();
+        assert!(stat.cpu().unwrap() >= 0.0);
+        let mut thread =
+            perf_monitor::cpu::ThreadStat::build(perf_monitor::cpu::ThreadId::current()).unwrap();
+        assert!(thread.cpu_time().is_ok());
+        assert!(perf_monitor::cpu::cpu_time().is_ok());
+        assert!(perf_monitor::cpu::processor_numbers().unwrap() > 0);
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "windows"
+        ))]
+        {
+            let stats: perf_monitor::io::IOStats =
+                perf_monitor::io::get_process_io_stats().unwrap();
+            let _ = stats.read_bytes;
+        }
+
+        assert!(perf_monitor::fd::fd_count_cur().unwrap() > 0);
+    }
+}
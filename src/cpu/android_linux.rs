@@ -1,7 +1,8 @@
-use libc::{pthread_t, timespec};
+use libc::{clockid_t, pid_t, pthread_t, timespec};
 use std::{
     convert::TryInto,
     io::Error,
+    io::ErrorKind,
     io::Result,
     mem::MaybeUninit,
     time::{Duration, Instant},
@@ -27,13 +28,25 @@ fn timespec_to_duration(timespec { tv_sec, tv_nsec }: timespec) -> Duration {
     Duration::new(sec, nanos)
 }
 
-fn get_thread_cputime(ThreadId(thread): ThreadId) -> Result<timespec> {
+fn get_thread_cpuclock(ThreadId(thread): ThreadId) -> Result<clockid_t> {
     let mut clk_id = 0;
     let ret = unsafe { libc::pthread_getcpuclockid(thread, &mut clk_id) };
     if ret != 0 {
         return Err(Error::from_raw_os_error(ret));
     }
+    Ok(clk_id)
+}
+
+/// The cpu clock of a thread given its kernel tid,
+/// the same encoding glibc uses in `pthread_getcpuclockid`:
+/// `MAKE_THREAD_CPUCLOCK(tid, CPUCLOCK_SCHED)`.
+fn kernel_tid_cpuclock(tid: pid_t) -> clockid_t {
+    const CPUCLOCK_PERTHREAD_MASK: clockid_t = 4;
+    const CPUCLOCK_SCHED: clockid_t = 2;
+    (!tid << 3) | CPUCLOCK_PERTHREAD_MASK | CPUCLOCK_SCHED
+}
 
+fn get_thread_cputime(clk_id: clockid_t) -> Result<timespec> {
     let mut timespec = MaybeUninit::<timespec>::uninit();
     let ret = unsafe { libc::clock_gettime(clk_id, timespec.as_mut_ptr()) };
     if ret != 0 {
@@ -43,7 +56,7 @@ fn get_thread_cputime(ThreadId(thread): ThreadId) -> Result<timespec> {
 }
 
 pub struct ThreadStat {
-    tid: ThreadId,
+    clk_id: clockid_t,
     last_stat: (timespec, Instant),
 }
 
@@ -53,17 +66,47 @@ impl ThreadStat {
     }
 
     pub fn build(tid: ThreadId) -> Result<Self> {
-        let cputime = get_thread_cputime(tid)?;
+        Self::with_cpuclock(get_thread_cpuclock(tid)?)
+    }
+
+    fn with_cpuclock(clk_id: clockid_t) -> Result<Self> {
+        let cputime = get_thread_cputime(clk_id)?;
         let total_time = Instant::now();
         Ok(ThreadStat {
-            tid,
+            clk_id,
             last_stat: (cputime, total_time),
         })
     }
 
+    /// Scan `/proc/self/task/*/comm` for threads named `name`.
+    pub fn by_name(name: &str) -> Result<Vec<Self>> {
+        let mut stats = vec![];
+        for entry in std::fs::read_dir(crate::procfs::path("self/task"))? {
+            let entry = entry?;
+            let Some(tid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            let comm = match std::fs::read_to_string(entry.path().join("comm")) {
+                Ok(comm) => comm,
+                // the thread exited after the directory was listed.
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if comm.trim_end_matches('\n') != name {
+                continue;
+            }
+            match Self::with_cpuclock(kernel_tid_cpuclock(tid)) {
+                Ok(stat) => stats.push(stat),
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(stats)
+    }
+
     /// un-normalized
     pub fn cpu(&mut self) -> Result<f64> {
-        let cputime = get_thread_cputime(self.tid)?;
+        let cputime = get_thread_cputime(self.clk_id)?;
         let total_time = Instant::now();
         let (old_cputime, old_total_time) =
             std::mem::replace(&mut self.last_stat, (cputime, total_time));
@@ -77,7 +120,7 @@ impl ThreadStat {
     }
 
     pub fn cpu_time(&mut self) -> Result<Duration> {
-        let cputime = get_thread_cputime(self.tid)?;
+        let cputime = get_thread_cputime(self.clk_id)?;
         let total_time = Instant::now();
         let (old_cputime, _old_total_time) =
             std::mem::replace(&mut self.last_stat, (cputime, total_time));
@@ -85,7 +128,50 @@ impl ThreadStat {
     }
 }
 
+/// The cpu time of current process, from `clock_gettime(CLOCK_PROCESS_CPUTIME_ID)` with
+/// nanosecond resolution, or from `/proc/self/stat` in clock ticks when the clock is denied,
+/// e.g. by a seccomp sandbox. The error is the one of the clock if both fail.
 pub fn cpu_time() -> Result<Duration> {
+    cpu_time_with(clock_process_cputime, || {
+        crate::procfs::read_to_string("self/stat")
+    })
+}
+
+fn cpu_time_with(
+    clock: impl FnOnce() -> Result<Duration>,
+    read_stat: impl FnOnce() -> Result<String>,
+) -> Result<Duration> {
+    clock().or_else(|clock_err| {
+        read_stat()
+            .and_then(|stat| parse_stat_cpu_time(&stat))
+            .map_err(|_| clock_err)
+    })
+}
+
+/// `utime + stime` of a `/proc/[pid]/stat` line, see proc(5).
+fn parse_stat_cpu_time(stat: &str) -> Result<Duration> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "Invalid /proc/self/stat");
+    // the command name in parentheses may contain spaces and parentheses, skip it.
+    let (_, fields) = stat.rsplit_once(')').ok_or_else(invalid)?;
+    // the fields after the name start at `state`, the third one.
+    let mut fields = fields.split_ascii_whitespace().skip(14 - 3);
+    let mut ticks = || -> Result<u64> {
+        fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let ticks = ticks()? + ticks()?;
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return Err(Error::last_os_error());
+    }
+    let ticks_per_sec = ticks_per_sec as u64;
+    Ok(Duration::from_secs(ticks / ticks_per_sec)
+        + Duration::from_secs(ticks % ticks_per_sec) / ticks_per_sec as u32)
+}
+
+fn clock_process_cputime() -> Result<Duration> {
     let mut timespec = MaybeUninit::<timespec>::uninit();
     let ret = unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, timespec.as_mut_ptr()) };
     if ret != 0 {
@@ -93,3 +179,76 @@ pub fn cpu_time() -> Result<Duration> {
     }
     Ok(timespec_to_duration(unsafe { timespec.assume_init() }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_by_name() {
+        const NAME: &str = "perf-by-name";
+        let (named_tx, named_rx) = std::sync::mpsc::channel();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            let name = std::ffi::CString::new(NAME).unwrap();
+            unsafe { libc::prctl(libc::PR_SET_NAME, name.as_ptr()) };
+            named_tx.send(()).unwrap();
+            let _ = done_rx.recv();
+        });
+        named_rx.recv().unwrap();
+
+        let mut stats = ThreadStat::by_name(NAME).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].cpu().unwrap() >= 0f64);
+        assert!(ThreadStat::by_name("perf-no-such").unwrap().is_empty());
+
+        done_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_parse_stat_cpu_time() {
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+        let stat = format!(
+            "1234 (a (weird) name) S 1 1234 1234 0 -1 4194304 100 0 0 0 {} {} 0 0 20 0 1 0 100 0 0\n",
+            ticks_per_sec * 2,
+            ticks_per_sec / 2
+        );
+        assert_eq!(
+            parse_stat_cpu_time(&stat).unwrap(),
+            Duration::from_millis(2500)
+        );
+        assert!(parse_stat_cpu_time("1234 (truncated) S 1").is_err());
+    }
+
+    #[test]
+    fn test_cpu_time_fallback() {
+        let mut x = 1_000_000u64;
+        std::hint::black_box(&mut x);
+        std::hint::black_box((0..x).sum::<u64>());
+
+        let denied = || Err(Error::from(ErrorKind::PermissionDenied));
+        // the clock first, procfs is not read.
+        let clock = cpu_time_with(clock_process_cputime, || panic!("read procfs")).unwrap();
+        assert!(clock > Duration::ZERO);
+
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+        let stat = format!(
+            "1 (app) S 0 1 1 0 -1 0 0 0 0 0 {} 0 0 0 20 0 1 0 100 0 0\n",
+            ticks_per_sec
+        );
+        let fallback = cpu_time_with(denied, || Ok(stat)).unwrap();
+        assert_eq!(fallback, Duration::from_secs(1));
+
+        let err = cpu_time_with(denied, || Ok(String::new())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(cpu_time().is_ok());
+    }
+
+    #[test]
+    fn test_kernel_tid_cpuclock() {
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as pid_t;
+        let clk_id = get_thread_cpuclock(ThreadId::current()).unwrap();
+        assert_eq!(kernel_tid_cpuclock(tid), clk_id);
+    }
+}
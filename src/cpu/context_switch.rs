@@ -0,0 +1,43 @@
+use std::io::{Error, Result};
+
+/// The context switches of current process since it started, all threads included, exited
+/// ones too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextSwitches {
+    /// the thread gave the CPU up, to wait for I/O or a lock for example.
+    pub voluntary: u64,
+    /// the thread was preempted, a high rate is a sign of CPU contention.
+    pub involuntary: u64,
+}
+
+/// Get the context switches of current process, from `getrusage`.
+///
+/// `/proc/self/status` is not used on Linux, its counters are the ones of the main thread only.
+/// Windows has no such counter for a process.
+pub fn get_context_switches() -> Result<ContextSwitches> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(ContextSwitches {
+        voluntary: usage.ru_nvcsw as u64,
+        involuntary: usage.ru_nivcsw as u64,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_context_switches() {
+        let before = get_context_switches().unwrap();
+        // sleeping gives the CPU up.
+        for _ in 0..10 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let after = get_context_switches().unwrap();
+        assert!(after.voluntary > before.voluntary);
+        assert!(after.involuntary >= before.involuntary);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashSet, io::Result};
+
+/// The number of online logical processors of the system, hyperthreads included.
+///
+/// Unlike `processor_numbers`, it ignores the affinity mask and the cgroup quota of current
+/// process, so it is the count `physical_core_count` compares to.
+pub fn logical_core_count() -> Result<usize> {
+    platform_logical_core_count()
+}
+
+/// The number of physical cores of the system, each running one or more logical processors.
+///
+/// | platform | source |
+/// | -- | -- |
+/// | linux & android | the distinct `core_cpus_list` of `/sys/devices/system/cpu/cpu*/topology`, falling back to the `physical id` and `core id` pairs of `/proc/cpuinfo`, then to the logical count |
+/// | windows | the `RelationProcessorCore` records of `GetLogicalProcessorInformationEx` |
+/// | macos & ios | `sysctl hw.physicalcpu` |
+pub fn physical_core_count() -> Result<usize> {
+    platform_physical_core_count()
+}
+
+/// The number of distinct `(physical id, core id)` pairs in `/proc/cpuinfo`, `None` if it has
+/// no `core id`, as on most ARM kernels.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+pub(crate) fn parse_cpuinfo_physical_cores(cpuinfo: &str) -> Option<usize> {
+    let mut cores = HashSet::new();
+    // the processors are separated by blank lines, `physical id` comes before `core id`.
+    let mut package = None;
+    for line in cpuinfo.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            package = None;
+            continue;
+        };
+        match key.trim() {
+            "physical id" => package = Some(value.trim().to_string()),
+            "core id" => {
+                cores.insert((package.clone(), value.trim().to_string()));
+            }
+            _ => {}
+        }
+    }
+    if cores.is_empty() {
+        None
+    } else {
+        Some(cores.len())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn platform_logical_core_count() -> Result<usize> {
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if count < 1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn platform_physical_core_count() -> Result<usize> {
+    const CPUS: &str = "/sys/devices/system/cpu";
+
+    let mut cores = HashSet::new();
+    if let Ok(entries) = std::fs::read_dir(CPUS) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(cpu) = name.to_str().and_then(|name| name.strip_prefix("cpu")) else {
+                continue;
+            };
+            if cpu.is_empty() || !cpu.bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+            // offline processors have no topology, older kernels only `thread_siblings_list`.
+            let topology = entry.path().join("topology");
+            let siblings = std::fs::read_to_string(topology.join("core_cpus_list"))
+                .or_else(|_| std::fs::read_to_string(topology.join("thread_siblings_list")));
+            if let Ok(siblings) = siblings {
+                cores.insert(siblings.trim().to_string());
+            }
+        }
+    }
+    if !cores.is_empty() {
+        return Ok(cores.len());
+    }
+    if let Ok(cpuinfo) = crate::procfs::read_to_string("cpuinfo") {
+        if let Some(cores) = parse_cpuinfo_physical_cores(&cpuinfo) {
+            return Ok(cores);
+        }
+    }
+    platform_logical_core_count()
+}
+
+#[cfg(target_os = "windows")]
+fn platform_logical_core_count() -> Result<usize> {
+    use windows_sys::Win32::System::Threading::GetActiveProcessorCount;
+
+    // ALL_PROCESSOR_GROUPS, counts the processors beyond the 64 of a group.
+    let count = unsafe { GetActiveProcessorCount(0xffff) };
+    if count == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
+#[cfg(target_os = "windows")]
+fn platform_physical_core_count() -> Result<usize> {
+    use windows_sys::Win32::{
+        Foundation::{GetLastError, ERROR_INSUFFICIENT_BUFFER},
+        System::SystemInformation::{
+            GetLogicalProcessorInformationEx, RelationProcessorCore,
+            SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX,
+        },
+    };
+
+    let mut len = 0u32;
+    let ok = unsafe {
+        GetLogicalProcessorInformationEx(RelationProcessorCore, std::ptr::null_mut(), &mut len)
+    };
+    if ok != 0 || unsafe { GetLastError() } != ERROR_INSUFFICIENT_BUFFER {
+        return Err(std::io::Error::last_os_error());
+    }
+    // u64 for the alignment of the records.
+    let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+    let ok = unsafe {
+        GetLogicalProcessorInformationEx(
+            RelationProcessorCore,
+            buf.as_mut_ptr() as *mut SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX,
+            &mut len,
+        )
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // the records have variable sizes, one per physical core.
+    let (mut offset, mut cores) = (0usize, 0usize);
+    let base = buf.as_ptr() as *const u8;
+    while offset < len as usize {
+        let record =
+            unsafe { &*(base.add(offset) as *const SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX) };
+        if record.Size == 0 {
+            break;
+        }
+        if record.Relationship == RelationProcessorCore {
+            cores += 1;
+        }
+        offset += record.Size as usize;
+    }
+    Ok(cores.max(1))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn sysctl_count(name: &[u8]) -> Result<usize> {
+    let mut count: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    let ret = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr() as *const libc::c_char,
+            &mut count as *mut libc::c_int as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(count.max(1) as usize)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn platform_logical_core_count() -> Result<usize> {
+    sysctl_count(b"hw.logicalcpu\0")
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn platform_physical_core_count() -> Result<usize> {
+    sysctl_count(b"hw.physicalcpu\0")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpuinfo_physical_cores() {
+        // 2 sockets of 2 cores of 2 threads.
+        let mut cpuinfo = String::new();
+        for processor in 0..8 {
+            cpuinfo.push_str(&format!(
+                "processor\t: {}\nmodel name\t: Xeon\nphysical id\t: {}\nsiblings\t: 4\n\
+                 core id\t\t: {}\ncpu cores\t: 2\n\n",
+                processor,
+                processor / 4,
+                processor % 2
+            ));
+        }
+        assert_eq!(parse_cpuinfo_physical_cores(&cpuinfo), Some(4));
+
+        let arm = "processor\t: 0\nBogoMIPS\t: 50.00\nCPU part\t: 0xd0c\n\n\
+                   processor\t: 1\nBogoMIPS\t: 50.00\nCPU part\t: 0xd0c\n";
+        assert_eq!(parse_cpuinfo_physical_cores(arm), None);
+    }
+
+    #[test]
+    fn test_core_counts() {
+        let logical = logical_core_count().unwrap();
+        let physical = physical_core_count().unwrap();
+        assert!(physical >= 1);
+        assert!(
+            physical <= logical,
+            "{} physical, {} logical",
+            physical,
+            logical
+        );
+        assert!(crate::cpu::processor_numbers().unwrap() <= logical);
+    }
+}
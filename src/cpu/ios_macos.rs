@@ -32,7 +32,10 @@ pub fn get_thread_basic_info(ThreadId(tid): ThreadId) -> Result<thread_basic_inf
         )
     };
     if ret != KERN_SUCCESS as i32 {
-        return Err(Error::from_raw_os_error(ret));
+        return Err(crate::utils::kern_return::kern_return_error(
+            "thread_info",
+            ret,
+        ));
     }
     Ok(unsafe { thread_basic_info.assume_init() })
 }
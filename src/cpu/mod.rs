@@ -10,18 +10,24 @@
 //! ## Example
 //!
 //! ```
-//! # use perf_monitor::cpu::ThreadStat;
+//! # use workflow_perf_monitor::cpu::ThreadStat;
 //! let mut stat = ThreadStat::cur().unwrap();
 //! let _ = (0..1_000_000).into_iter().sum::<u64>();
 //! let usage = stat.cpu().unwrap();
 //! println!("current thread cpu usage is {:.2}%", usage * 100f64);
 //! ```
 //!
+//! `logical_core_count` and `physical_core_count` count the processors of the system with and
+//! without the hyperthreads.
+//!
+//! `get_context_switches` counts the context switches of current process, not on Windows
+//! which has no such counter for a process.
+//!
 //! ## Bottom Layer Interface
 //! | platform | thread | process |
 //! | -- | -- | -- |
 //! | windows |[GetThreadTimes] | [GetProcessTimes] |
-//! | linux & android | [/proc/{pid}/task/{tid}/stat][man5] | [clockgettime] |
+//! | linux & android | [/proc/{pid}/task/{tid}/stat][man5] | [clockgettime], falling back to [/proc/self/stat][man5] |
 //! | macos & ios | [thread_info] | [getrusage] |
 //!
 //! [GetThreadTimes]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getthreadtimes
@@ -31,6 +37,14 @@
 //! [clockgettime]: https://man7.org/linux/man-pages/man2/clock_gettime.2.html
 //! [getrusage]: https://www.man7.org/linux/man-pages/man2/getrusage.2.html
 
+mod cores;
+pub use cores::{logical_core_count, physical_core_count};
+
+#[cfg(not(target_os = "windows"))]
+mod context_switch;
+#[cfg(not(target_os = "windows"))]
+pub use context_switch::{get_context_switches, ContextSwitches};
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 mod android_linux;
 #[cfg(any(target_os = "ios", target_os = "macos"))]
@@ -55,11 +69,20 @@ use std::{
     time::{Duration, Instant},
 };
 
-/// logical processor number
+/// logical processor number, the ones current process may run on: it is lower than
+/// `logical_core_count` with an affinity mask or a cgroup CPU quota.
 pub fn processor_numbers() -> std::io::Result<usize> {
     std::thread::available_parallelism().map(|x| x.get())
 }
 
+/// return the user and system cpu time consumed by current process since it started.
+///
+/// Divide it by the uptime of the process for its average usage, or use `ProcessStat` for
+/// the usage over an interval.
+pub fn total_cpu_time() -> Result<Duration> {
+    platform::cpu_time()
+}
+
 /// A struct to monitor process cpu usage
 pub struct ProcessStat {
     now: Instant,
@@ -109,6 +132,18 @@ impl ThreadStat {
         })
     }
 
+    /// return monitors of all threads in current process named `name`.
+    ///
+    /// Names are matched against `/proc/self/task/*/comm`, which the kernel truncates to 15 bytes.
+    /// Several threads may share a name, so all of them are returned.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn by_name(name: &str) -> Result<Vec<Self>> {
+        Ok(platform::ThreadStat::by_name(name)?
+            .into_iter()
+            .map(|stat| ThreadStat { stat })
+            .collect())
+    }
+
     /// return the cpu usage from last invoke,
     /// or when this struct created if it is the first invoke.
     pub fn cpu(&mut self) -> Result<f64> {
@@ -154,6 +189,18 @@ mod test {
         assert!(usage > 0.9 * num as f64)
     }
 
+    #[test]
+    fn test_total_cpu_time() {
+        let mut x = 1_000_000u64;
+        std::hint::black_box(&mut x);
+        for i in 0..100 {
+            std::hint::black_box((0..x + i).into_iter().sum::<u64>());
+        }
+        let first = total_cpu_time().unwrap();
+        assert!(first > Duration::ZERO);
+        assert!(total_cpu_time().unwrap() >= first);
+    }
+
     #[test]
     fn test_thread_usage() {
         let mut stat = ThreadStat::cur().unwrap();
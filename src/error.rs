@@ -0,0 +1,161 @@
+//! The error type for APIs reporting failures of several kinds at once.
+use std::ffi::{c_char, CStr};
+use thiserror::Error;
+
+pub const PERF_OK: i32 = 0;
+pub const PERF_ERR_IO: i32 = 1;
+pub const PERF_ERR_PARSE: i32 = 2;
+pub const PERF_ERR_UNSUPPORTED: i32 = 3;
+pub const PERF_ERR_PERMISSION: i32 = 4;
+pub const PERF_ERR_MACH: i32 = 5;
+
+/// An error classified by what went wrong, most functions of this crate return a
+/// `std::io::Error` which converts into it.
+#[derive(Error, Debug)]
+pub enum PerfError {
+    /// an OS call or a read failed, e.g. with `ErrorKind::NotFound` for a process which exited.
+    #[error("io error")]
+    Io(#[source] std::io::Error),
+    /// the data read doesn't have the expected format.
+    #[error("parse error: {0}")]
+    Parse(String),
+    /// the platform or the build doesn't support the operation.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    /// the OS refused the operation to current process.
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    /// a Mach call of MacOS or iOS failed, for another reason than a refusal.
+    #[error("mach error: {0}")]
+    Mach(String),
+}
+
+impl PerfError {
+    /// The stable code of the kind of this error for C callers, `perf_strerror` describes it.
+    ///
+    /// | code | |
+    /// | -- | -- |
+    /// | `PERF_OK`, 0 | success, never returned here |
+    /// | `PERF_ERR_IO`, 1 | `PerfError::Io` |
+    /// | `PERF_ERR_PARSE`, 2 | `PerfError::Parse` |
+    /// | `PERF_ERR_UNSUPPORTED`, 3 | `PerfError::Unsupported` |
+    /// | `PERF_ERR_PERMISSION`, 4 | `PerfError::PermissionDenied` |
+    /// | `PERF_ERR_MACH`, 5 | `PerfError::Mach` |
+    ///
+    /// The codes are never reused, a new kind of error gets the next one. A failed Mach call
+    /// is `PERF_ERR_PERMISSION` when its `kern_return_t` is a refusal, `KERN_NO_ACCESS` for
+    /// instance, `PERF_ERR_MACH` otherwise.
+    pub fn code(&self) -> i32 {
+        match self {
+            PerfError::Io(_) => PERF_ERR_IO,
+            PerfError::Parse(_) => PERF_ERR_PARSE,
+            PerfError::Unsupported(_) => PERF_ERR_UNSUPPORTED,
+            PerfError::PermissionDenied(_) => PERF_ERR_PERMISSION,
+            PerfError::Mach(_) => PERF_ERR_MACH,
+        }
+    }
+}
+
+fn strerror(code: i32) -> &'static CStr {
+    let message: &'static [u8] = match code {
+        PERF_OK => b"success\0",
+        PERF_ERR_IO => b"io error\0",
+        PERF_ERR_PARSE => b"parse error\0",
+        PERF_ERR_UNSUPPORTED => b"unsupported\0",
+        PERF_ERR_PERMISSION => b"permission denied\0",
+        PERF_ERR_MACH => b"mach error\0",
+        _ => b"unknown error\0",
+    };
+    // every message above ends with its only nul.
+    CStr::from_bytes_with_nul(message).unwrap()
+}
+
+/// A static, nul terminated description of a code of `PerfError::code`, never null. The
+/// string must not be freed.
+#[no_mangle]
+pub extern "C" fn perf_strerror(code: i32) -> *const c_char {
+    strerror(code).as_ptr()
+}
+
+impl From<std::io::Error> for PerfError {
+    fn from(e: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match e.kind() {
+            ErrorKind::InvalidData => PerfError::Parse(e.to_string()),
+            ErrorKind::Unsupported => PerfError::Unsupported(e.to_string()),
+            ErrorKind::PermissionDenied => PerfError::PermissionDenied(e.to_string()),
+            _ if e
+                .get_ref()
+                .is_some_and(|inner| inner.is::<crate::utils::kern_return::KernReturnError>()) =>
+            {
+                PerfError::Mach(e.to_string())
+            }
+            _ => PerfError::Io(e),
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for PerfError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        PerfError::Parse(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn test_from_io_error() {
+        let e: PerfError = Error::new(ErrorKind::PermissionDenied, "task_for_pid").into();
+        assert!(matches!(e, PerfError::PermissionDenied(_)));
+        let e: PerfError = Error::new(ErrorKind::Unsupported, "not on iOS").into();
+        assert!(matches!(e, PerfError::Unsupported(_)));
+        let e: PerfError = Error::new(ErrorKind::InvalidData, "Invalid VmRSS").into();
+        assert_eq!(e.to_string(), "parse error: Invalid VmRSS");
+        let e: PerfError = Error::from(ErrorKind::NotFound).into();
+        // the io error is the source, it isn't repeated in the message.
+        assert_eq!(e.to_string(), "io error");
+        let source = std::error::Error::source(&e).unwrap().to_string();
+        assert_eq!(source, Error::from(ErrorKind::NotFound).to_string());
+        assert!(matches!(e, PerfError::Io(e) if e.kind() == ErrorKind::NotFound));
+        use crate::utils::kern_return::{kern_return_error, KERN_INVALID_TASK, KERN_NO_ACCESS};
+        let e: PerfError = kern_return_error("task_info", KERN_INVALID_TASK).into();
+        assert_eq!(
+            e.to_string(),
+            "mach error: task_info failed: invalid task (DARWIN_KERN_RET_CODE:16)"
+        );
+        let e: PerfError = kern_return_error("task_info", KERN_NO_ACCESS).into();
+        assert!(matches!(e, PerfError::PermissionDenied(_)));
+        let e: PerfError = "x".parse::<u32>().unwrap_err().into();
+        assert!(matches!(e, PerfError::Parse(_)));
+    }
+
+    #[test]
+    fn test_codes() {
+        let errors = [
+            PerfError::Io(Error::from(ErrorKind::NotFound)),
+            PerfError::Parse(String::new()),
+            PerfError::Unsupported(String::new()),
+            PerfError::PermissionDenied(String::new()),
+            PerfError::Mach(String::new()),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(PerfError::code).collect();
+        assert!(codes.iter().all(|&code| code != PERF_OK));
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+
+        for code in codes {
+            let message = perf_strerror(code);
+            assert!(!message.is_null());
+            let message = unsafe { CStr::from_ptr(message) }.to_str().unwrap();
+            assert_ne!(message, "unknown error");
+            // the Display of the errors starts with their description.
+            assert!(errors[code as usize - 1].to_string().starts_with(message));
+        }
+        assert_eq!(strerror(PERF_OK).to_str().unwrap(), "success");
+        assert_eq!(strerror(-1).to_str().unwrap(), "unknown error");
+    }
+}
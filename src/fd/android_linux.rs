@@ -1,11 +1,14 @@
+#[allow(dead_code)]
 pub fn fd_count_pid(pid: u32) -> std::io::Result<usize> {
     // Subtract 2 to exclude `.`, `..` entries
-    std::fs::read_dir(format!("/proc/{}/fd", pid)).map(|entries| entries.count().saturating_sub(2))
+    std::fs::read_dir(crate::procfs::path(&format!("{}/fd", pid)))
+        .map(|entries| entries.count().saturating_sub(2))
 }
 
 pub fn fd_count_cur() -> std::io::Result<usize> {
     // Subtract 3 to exclude `.`, `..` entries and fd created by `read_dir`
-    std::fs::read_dir("/proc/self/fd").map(|entries| entries.count().saturating_sub(3))
+    std::fs::read_dir(crate::procfs::path("self/fd"))
+        .map(|entries| entries.count().saturating_sub(3))
 }
 
 #[cfg(test)]
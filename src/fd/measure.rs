@@ -0,0 +1,46 @@
+use super::fd_count_cur;
+
+/// Run `f` and return its result along with the change of the fd count it caused, a leak gate
+/// for tests.
+///
+/// ```
+/// # use workflow_perf_monitor::fd::measure_fd;
+/// let (_, delta) = measure_fd(|| drop(std::fs::File::open("Cargo.toml")));
+/// assert!(delta.unwrap() <= 0);
+/// ```
+///
+/// The count is the one of the whole process, the descriptors other threads open or close
+/// meanwhile, like the ones of tests running in parallel, count too. The delta is `None` if the
+/// count can't be read before or after `f`, e.g. on iOS, `f` runs anyway.
+pub fn measure_fd<T>(f: impl FnOnce() -> T) -> (T, Option<i64>) {
+    let before = fd_count_cur().ok();
+    let ret = f();
+    let after = fd_count_cur().ok();
+    let delta = match (before, after) {
+        (Some(before), Some(after)) => Some(after as i64 - before as i64),
+        _ => None,
+    };
+    (ret, delta)
+}
+
+#[cfg(all(test, not(target_os = "ios")))]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    // retry, as the other tests open and close files in parallel.
+    fn settles_at(expected: i64, f: impl Fn() -> Option<File>) -> bool {
+        (0..20).any(|_| measure_fd(&f).1 == Some(expected))
+    }
+
+    #[test]
+    fn test_measure_fd() {
+        let path = std::env::current_exe().unwrap();
+        // the file is still open after the closure: a leak.
+        assert!(settles_at(1, || Some(File::open(&path).unwrap())));
+        assert!(settles_at(0, || {
+            drop(File::open(&path).unwrap());
+            None
+        }));
+    }
+}
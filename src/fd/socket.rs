@@ -0,0 +1,172 @@
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub fn socket_count_cur() -> std::io::Result<usize> {
+    Ok(socket_fds()?.len())
+}
+
+/// The fds of current process which are sockets.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn socket_fds() -> std::io::Result<Vec<i32>> {
+    let mut fds = vec![];
+    for entry in std::fs::read_dir(crate::procfs::path("self/fd"))? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        // The fd may have been closed since the directory was listed, and the one held by
+        // `read_dir` itself links to the directory, neither is a socket.
+        if let Ok(target) = std::fs::read_link(entry.path()) {
+            if target
+                .as_os_str()
+                .as_encoded_bytes()
+                .starts_with(b"socket:")
+            {
+                fds.push(fd);
+            }
+        }
+    }
+    Ok(fds)
+}
+
+/// The fds of current process which are sockets.
+#[cfg(target_os = "macos")]
+fn socket_fds() -> std::io::Result<Vec<i32>> {
+    use std::mem::MaybeUninit;
+
+    let mut fds = vec![];
+    for entry in std::fs::read_dir("/dev/fd")? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        // fails for the fd closed in between, that one is not counted.
+        if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } != 0 {
+            continue;
+        }
+        let stat = unsafe { stat.assume_init() };
+        if stat.st_mode & libc::S_IFMT == libc::S_IFSOCK {
+            fds.push(fd);
+        }
+    }
+    Ok(fds)
+}
+
+#[cfg(target_os = "ios")]
+pub fn socket_count_cur() -> std::io::Result<usize> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "socket count is not supported on iOS",
+    ))
+}
+
+#[cfg(target_os = "windows")]
+pub fn socket_count_cur() -> std::io::Result<usize> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetExtendedTcpTable, GetExtendedUdpTable, MIB_TCP6ROW_OWNER_PID, MIB_TCPROW_OWNER_PID,
+        MIB_UDP6ROW_OWNER_PID, MIB_UDPROW_OWNER_PID, TCP_TABLE_OWNER_PID_ALL, UDP_TABLE_OWNER_PID,
+    };
+
+    // ws2def.h
+    const AF_INET: u32 = 2;
+    const AF_INET6: u32 = 23;
+
+    let pid = std::process::id();
+    let count = count_rows::<MIB_TCPROW_OWNER_PID>(
+        pid,
+        |ptr, size| unsafe {
+            GetExtendedTcpTable(ptr, size, 0, AF_INET, TCP_TABLE_OWNER_PID_ALL, 0)
+        },
+        |row| row.dwOwningPid,
+    )? + count_rows::<MIB_TCP6ROW_OWNER_PID>(
+        pid,
+        |ptr, size| unsafe {
+            GetExtendedTcpTable(ptr, size, 0, AF_INET6, TCP_TABLE_OWNER_PID_ALL, 0)
+        },
+        |row| row.dwOwningPid,
+    )? + count_rows::<MIB_UDPROW_OWNER_PID>(
+        pid,
+        |ptr, size| unsafe { GetExtendedUdpTable(ptr, size, 0, AF_INET, UDP_TABLE_OWNER_PID, 0) },
+        |row| row.dwOwningPid,
+    )? + count_rows::<MIB_UDP6ROW_OWNER_PID>(
+        pid,
+        |ptr, size| unsafe { GetExtendedUdpTable(ptr, size, 0, AF_INET6, UDP_TABLE_OWNER_PID, 0) },
+        |row| row.dwOwningPid,
+    )?;
+    Ok(count)
+}
+
+/// Count the rows of a `MIB_*TABLE_OWNER_PID` owned by `pid`.
+///
+/// Every such table is a `dwNumEntries: u32` followed by an array of `Row`.
+#[cfg(target_os = "windows")]
+fn count_rows<Row>(
+    pid: u32,
+    get_table: impl Fn(*mut core::ffi::c_void, *mut u32) -> u32,
+    owning_pid: impl Fn(&Row) -> u32,
+) -> std::io::Result<usize> {
+    use windows_sys::Win32::Foundation::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR};
+
+    let mut size = 0;
+    let mut buf: Vec<u64> = vec![];
+    loop {
+        let ret = get_table(buf.as_mut_ptr() as *mut _, &mut size);
+        match ret {
+            NO_ERROR => break,
+            // the table may grow between two calls, retry until the buffer is large enough.
+            ERROR_INSUFFICIENT_BUFFER => {
+                buf.resize((size as usize).div_ceil(std::mem::size_of::<u64>()), 0)
+            }
+            err => return Err(std::io::Error::from_raw_os_error(err as i32)),
+        }
+    }
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
+    let entries = unsafe { *(buf.as_ptr() as *const u32) } as usize;
+    // rows are aligned after the count.
+    let offset = std::mem::align_of::<Row>().max(std::mem::size_of::<u32>());
+    let rows = unsafe {
+        std::slice::from_raw_parts(
+            (buf.as_ptr() as *const u8).add(offset) as *const Row,
+            entries,
+        )
+    };
+    Ok(rows.iter().filter(|row| owning_pid(row) == pid).count())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn test_socket_count() {
+        let listeners: Vec<_> = (0..3)
+            .map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        // the other tests may close their sockets meanwhile, but not ours.
+        assert!(socket_count_cur().unwrap() >= listeners.len());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    #[test]
+    fn test_socket_fds() {
+        use std::os::unix::io::AsRawFd;
+
+        let listeners: Vec<_> = (0..3)
+            .map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let files: Vec<_> = (0..10)
+            .map(|_| std::fs::File::open(std::env::current_exe().unwrap()).unwrap())
+            .collect();
+        let sockets = socket_fds().unwrap();
+        for listener in &listeners {
+            assert!(sockets.contains(&listener.as_raw_fd()));
+        }
+        // regular files are not sockets.
+        for file in &files {
+            assert!(!sockets.contains(&file.as_raw_fd()));
+        }
+    }
+}
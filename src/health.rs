@@ -0,0 +1,170 @@
+//! A single health number for current process, to rank workers.
+//!
+//! ```
+//! # use workflow_perf_monitor::health::{process_health_score, HealthWeights};
+//! let score = process_health_score(HealthWeights::default()).unwrap();
+//! assert!((0.0..=100.0).contains(&score));
+//! ```
+use std::{io::Result, time::Duration};
+
+use crate::{cpu::ProcessStat, mem::get_process_memory_info};
+
+/// How much each resource counts in the score, only the ratios between the weights matter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthWeights {
+    pub memory: f64,
+    pub cpu: f64,
+    pub fd: f64,
+}
+
+impl Default for HealthWeights {
+    /// Memory first, as running out of it kills the process, then CPU, then file descriptors.
+    fn default() -> Self {
+        HealthWeights {
+            memory: 0.5,
+            cpu: 0.3,
+            fd: 0.2,
+        }
+    }
+}
+
+/// How long the CPU usage is measured for.
+const CPU_WINDOW: Duration = Duration::from_millis(100);
+
+/// The weighted score of the usage ratios, `None` for a resource without a known limit.
+///
+/// `100 * (1 - sum(weight * min(ratio, 1)) / sum(weight))` over the known ratios.
+fn score(weights: HealthWeights, memory: Option<f64>, cpu: Option<f64>, fd: Option<f64>) -> f64 {
+    let (mut used, mut total) = (0.0, 0.0);
+    for (weight, ratio) in [
+        (weights.memory, memory),
+        (weights.cpu, cpu),
+        (weights.fd, fd),
+    ] {
+        let (Some(ratio), true) = (ratio, weight > 0.0) else {
+            continue;
+        };
+        used += weight * ratio.clamp(0.0, 1.0);
+        total += weight;
+    }
+    if total == 0.0 {
+        return 100.0;
+    }
+    100.0 * (1.0 - used / total)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn memory_limit() -> Result<Option<u64>> {
+    if let Some(limit) = crate::mem::get_cgroup_memory()?.and_then(|cgroup| cgroup.limit) {
+        return Ok(Some(limit));
+    }
+    Ok(Some(crate::mem::get_system_memory_info()?.total))
+}
+
+#[cfg(target_os = "windows")]
+fn memory_limit() -> Result<Option<u64>> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(Some(status.ullTotalPhys))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn memory_limit() -> Result<Option<u64>> {
+    let mut memsize: u64 = 0;
+    let mut size = std::mem::size_of::<u64>();
+    let ret = unsafe {
+        libc::sysctlbyname(
+            b"hw.memsize\0".as_ptr() as *const libc::c_char,
+            &mut memsize as *mut u64 as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(Some(memsize))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn fd_limit() -> Result<Option<u64>> {
+    use crate::rlimit::{get_rlimit, Resource};
+    Ok(get_rlimit(Resource::NoFile)?.soft)
+}
+
+/// Windows has no per-process limit of handles short of the 16M kernel one.
+#[cfg(target_os = "windows")]
+fn fd_limit() -> Result<Option<u64>> {
+    Ok(None)
+}
+
+fn ratio(used: u64, limit: Option<u64>) -> Option<f64> {
+    limit
+        .filter(|limit| *limit > 0)
+        .map(|limit| used as f64 / limit as f64)
+}
+
+/// A health score of current process from 0, every resource exhausted, to 100, idle.
+///
+/// Each resource gets a usage ratio, capped at 1:
+///
+/// - memory: the RSS against the limit of the memory cgroup on Linux, or else the physical
+///   memory of the system.
+/// - CPU: the usage over the next 100ms against the number of cores. This call blocks for
+///   that long.
+/// - fd: the open file descriptors against the soft `RLIMIT_NOFILE`. Windows has no such
+///   limit, like an unlimited rlimit the fd is then left out.
+///
+/// The score is `100 * (1 - sum(weight * ratio) / sum(weight))` over the resources with
+/// a known limit, 100 if there is none.
+pub fn process_health_score(weights: HealthWeights) -> Result<f64> {
+    let mut stat = ProcessStat::cur()?;
+    std::thread::sleep(CPU_WINDOW);
+    let cpu = stat.cpu()? / crate::cpu::processor_numbers()? as f64;
+
+    let rss = get_process_memory_info()?.resident_set_size;
+    let memory = ratio(rss, memory_limit()?);
+    let fd = ratio(crate::fd::fd_count_cur()? as u64, fd_limit()?);
+    Ok(score(weights, memory, Some(cpu), fd))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        let weights = HealthWeights::default();
+        assert_eq!(score(weights, Some(0.0), Some(0.0), Some(0.0)), 100.0);
+        assert_eq!(score(weights, Some(1.0), Some(1.0), Some(1.0)), 0.0);
+        // 100 * (1 - (0.5 * 0.4 + 0.3 * 0.5 + 0.2 * 0.25))
+        let mixed = score(weights, Some(0.4), Some(0.5), Some(0.25));
+        assert!((mixed - 60.0).abs() < 1e-9);
+        // ratios are capped.
+        assert_eq!(score(weights, Some(3.0), Some(1.0), Some(1.0)), 0.0);
+
+        // without an fd limit, memory and CPU are weighted 0.5 and 0.3 out of 0.8.
+        let no_fd = score(weights, Some(0.4), Some(0.8), None);
+        assert!((no_fd - 100.0 * (1.0 - (0.2 + 0.24) / 0.8)).abs() < 1e-9);
+
+        let memory_only = HealthWeights {
+            memory: 1.0,
+            cpu: 0.0,
+            fd: 0.0,
+        };
+        assert_eq!(score(memory_only, Some(0.25), Some(1.0), Some(1.0)), 75.0);
+        assert_eq!(score(memory_only, None, Some(1.0), Some(1.0)), 100.0);
+    }
+
+    #[test]
+    fn test_process_health_score() {
+        let score = process_health_score(HealthWeights::default()).unwrap();
+        assert!((0.0..=100.0).contains(&score));
+    }
+}
@@ -0,0 +1,108 @@
+//! A minimal blocking HTTP server for the Prometheus exposition, without any dependency.
+//!
+//! ```no_run
+//! # use workflow_perf_monitor::http::serve_metrics;
+//! serve_metrics("0.0.0.0:9100".parse().unwrap()).unwrap();
+//! ```
+use std::{
+    io::{BufRead, BufReader, Read, Result, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::prometheus::PrometheusExporter;
+
+/// A slow or stuck client must not hold the only serving thread for long.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest request head read, the rest is ignored.
+const MAX_HEAD: u64 = 8192;
+
+/// Serve `GET /metrics` on `addr` from a background thread, with the memory, CPU and I/O
+/// metrics of [`PrometheusExporter`] sampled every 15 seconds.
+///
+/// Connections are served one at a time and closed after the response, which is all a
+/// Prometheus scraper needs. Any other path is a 404, any other method a 405. The server runs
+/// for the lifetime of the process, the error is the one of binding `addr`.
+pub fn serve_metrics(addr: SocketAddr) -> Result<JoinHandle<()>> {
+    Ok(serve(TcpListener::bind(addr)?))
+}
+
+fn serve(listener: TcpListener) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let exporter = PrometheusExporter::builder()
+            .with_cpu(true)
+            .with_io(true)
+            .build();
+        // a failed connection only concerns its client.
+        for stream in listener.incoming().flatten() {
+            let _ = handle(stream, &exporter);
+        }
+    })
+}
+
+fn handle(mut stream: TcpStream, exporter: &PrometheusExporter) -> Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut reader = BufReader::new((&stream).take(MAX_HEAD));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // drain the headers, some clients wait for the request to be read before reading.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_ascii_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) if path.split('?').next() == Some("/metrics") => {
+            ("200 OK", exporter.render())
+        }
+        (Some("GET"), Some(_)) => ("404 Not Found", "Not Found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _server = serve(listener);
+
+        let response = get(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(body.contains("\nprocess_resident_memory_bytes "));
+        assert!(body.contains("\nprocess_cpu_seconds_total "));
+
+        let response = get(addr, "GET / HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = get(addr, "POST /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+
+        assert!(serve_metrics(addr).is_err());
+    }
+}
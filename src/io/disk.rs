@@ -0,0 +1,163 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+/// The I/O counters of a block device since boot, a line of `/proc/diskstats`.
+///
+/// See <https://www.kernel.org/doc/Documentation/ABI/testing/procfs-diskstats>. Sectors are
+/// always 512 bytes there, whatever the sector size of the device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceIoStats {
+    pub major: u32,
+    pub minor: u32,
+    pub name: String,
+    pub reads_completed: u64,
+    pub reads_merged: u64,
+    pub sectors_read: u64,
+    pub time_reading: Duration,
+    pub writes_completed: u64,
+    pub writes_merged: u64,
+    pub sectors_written: u64,
+    pub time_writing: Duration,
+    /// the requests issued to the device and not completed yet, not a counter.
+    pub io_in_progress: u64,
+    /// the time the device had at least one request in flight.
+    pub time_doing_io: Duration,
+    /// the time of each request from issue to completion summed, the time in queue.
+    pub weighted_time_doing_io: Duration,
+}
+
+impl DeviceIoStats {
+    /// The share of `elapsed` the device was busy between `previous` and this sample, from 0
+    /// to 1, the `%util` of `iostat` divided by 100.
+    ///
+    /// Devices serving requests in parallel, like SSDs, may be far from saturated at 1.
+    pub fn utilization(&self, previous: &DeviceIoStats, elapsed: Duration) -> f64 {
+        if elapsed.is_zero() {
+            return 0.0;
+        }
+        let busy = self.time_doing_io.saturating_sub(previous.time_doing_io);
+        (busy.as_secs_f64() / elapsed.as_secs_f64()).min(1.0)
+    }
+}
+
+fn parse_line(line: &str) -> Option<DeviceIoStats> {
+    let mut fields = line.split_ascii_whitespace();
+    let major = fields.next()?.parse().ok()?;
+    let minor = fields.next()?.parse().ok()?;
+    let name = fields.next()?.to_string();
+    // the kernel appended discard and flush counters over time, they are ignored.
+    let mut values = [0u64; 11];
+    for value in values.iter_mut() {
+        *value = fields.next()?.parse().ok()?;
+    }
+    let ms = Duration::from_millis;
+    Some(DeviceIoStats {
+        major,
+        minor,
+        name,
+        reads_completed: values[0],
+        reads_merged: values[1],
+        sectors_read: values[2],
+        time_reading: ms(values[3]),
+        writes_completed: values[4],
+        writes_merged: values[5],
+        sectors_written: values[6],
+        time_writing: ms(values[7]),
+        io_in_progress: values[8],
+        time_doing_io: ms(values[9]),
+        weighted_time_doing_io: ms(values[10]),
+    })
+}
+
+/// Parse the content of `/proc/diskstats`, one entry per device line.
+pub fn parse_diskstats(diskstats: &str) -> Result<Vec<DeviceIoStats>> {
+    diskstats
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            parse_line(line).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid diskstats line: {}", line.trim()),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Get the I/O counters of every block device of the system, partitions included.
+///
+/// Take two samples to compute rates, or [`DeviceIoStats::utilization`].
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_disk_io_stats() -> Result<Vec<DeviceIoStats>> {
+    parse_diskstats(&crate::procfs::read_to_string("diskstats")?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_diskstats() {
+        let diskstats = "\
+ 259       0 nvme0n1 120573 25888 9831360 41124 315784 178222 19354416 230699 0 170340 289735 0 0 0 0 10223 17911
+ 259       1 nvme0n1p1 303 1000 12482 89 2 0 2 0 0 108 89 0 0 0 0 0 0
+   8      16 sdb 2951 38 84782 1679 12 4 128 20 1 1044 1699
+";
+        let stats = parse_diskstats(diskstats).unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            stats[0],
+            DeviceIoStats {
+                major: 259,
+                minor: 0,
+                name: "nvme0n1".to_string(),
+                reads_completed: 120573,
+                reads_merged: 25888,
+                sectors_read: 9831360,
+                time_reading: Duration::from_millis(41124),
+                writes_completed: 315784,
+                writes_merged: 178222,
+                sectors_written: 19354416,
+                time_writing: Duration::from_millis(230699),
+                io_in_progress: 0,
+                time_doing_io: Duration::from_millis(170340),
+                weighted_time_doing_io: Duration::from_millis(289735),
+            }
+        );
+        assert_eq!(stats[1].name, "nvme0n1p1");
+        // a kernel older than 4.18, without the discard fields.
+        assert_eq!(stats[2].io_in_progress, 1);
+        assert_eq!(stats[2].weighted_time_doing_io, Duration::from_millis(1699));
+
+        assert!(parse_diskstats("8 0 sda 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_utilization() {
+        let before = DeviceIoStats {
+            time_doing_io: Duration::from_millis(10_000),
+            ..Default::default()
+        };
+        let after = DeviceIoStats {
+            time_doing_io: Duration::from_millis(10_250),
+            ..Default::default()
+        };
+        let elapsed = Duration::from_secs(1);
+        assert!((after.utilization(&before, elapsed) - 0.25).abs() < 1e-9);
+        // the clock of the sampler and the kernel drift apart a little.
+        assert_eq!(after.utilization(&before, Duration::from_millis(200)), 1.0);
+        assert_eq!(before.utilization(&after, elapsed), 0.0);
+        assert_eq!(after.utilization(&before, Duration::ZERO), 0.0);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_get_disk_io_stats() {
+        // containers may have no block device at all.
+        let stats = get_disk_io_stats().unwrap();
+        assert!(stats.iter().all(|device| !device.name.is_empty()));
+    }
+}
@@ -1,6 +1,14 @@
 //! Get io usage for current process.
+//!
+//! `get_disk_io_stats` reads the counters of the block devices of the system on Linux, to tell
+//! whether the disk is the bottleneck.
 use thiserror::Error;
 
+mod disk;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use disk::get_disk_io_stats;
+pub use disk::{parse_diskstats, DeviceIoStats};
+
 #[derive(Error, Debug)]
 #[error("IOStatsError({code}):{msg}")]
 pub struct IOStatsError {
@@ -29,9 +37,13 @@ impl From<std::num::ParseIntError> for IOStatsError {
 #[derive(Debug, Clone, Default)]
 pub struct IOStats {
     /// (linux & windows)  the number of read operations performed (cumulative)
+    ///
+    /// On linux these are the read syscalls, `syscr` of `/proc/self/io`.
     pub read_count: u64,
 
     /// (linux & windows) the number of write operations performed (cumulative)
+    ///
+    /// On linux these are the write syscalls, `syscw` of `/proc/self/io`.
     pub write_count: u64,
 
     /// the number of bytes read (cumulative).
@@ -39,6 +51,11 @@ pub struct IOStats {
 
     /// the number of bytes written (cumulative)
     pub write_bytes: u64,
+
+    /// (linux) the bytes counted in `write_bytes` which were never written, because the
+    /// dirty page cache was dropped first, e.g. by truncating the file (cumulative)
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub cancelled_write_bytes: u64,
 }
 /// Get the io stats of current process. Most platforms are supported.
 #[cfg(any(
@@ -53,15 +70,15 @@ pub fn get_process_io_stats() -> Result<IOStats, IOStatsError> {
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 fn get_process_io_stats_impl() -> Result<IOStats, IOStatsError> {
-    use std::{
-        io::{BufRead, BufReader},
-        str::FromStr,
-    };
+    parse_proc_io(&crate::procfs::read_to_string("self/io")?)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn parse_proc_io(io: &str) -> Result<IOStats, IOStatsError> {
+    use std::str::FromStr;
     let mut io_stats = IOStats::default();
-    let reader = BufReader::new(std::fs::File::open("/proc/self/io")?);
 
-    for line in reader.lines() {
-        let line = line?;
+    for line in io.lines() {
         let mut s = line.split_whitespace();
         if let (Some(field), Some(value)) = (s.next(), s.next()) {
             match field {
@@ -69,6 +86,7 @@ fn get_process_io_stats_impl() -> Result<IOStats, IOStatsError> {
                 "syscw:" => io_stats.write_count = u64::from_str(value)?,
                 "read_bytes:" => io_stats.read_bytes = u64::from_str(value)?,
                 "write_bytes:" => io_stats.write_bytes = u64::from_str(value)?,
+                "cancelled_write_bytes:" => io_stats.cancelled_write_bytes = u64::from_str(value)?,
                 _ => continue,
             }
         }
@@ -125,3 +143,29 @@ fn get_process_io_stats_impl() -> Result<IOStats, IOStatsError> {
         ..Default::default()
     })
 }
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_io() {
+        let io = "\
+rchar: 323934931
+wchar: 323929600
+syscr: 632687
+syscw: 632675
+read_bytes: 4096
+write_bytes: 323932160
+cancelled_write_bytes: 8192
+";
+        let stats = parse_proc_io(io).unwrap();
+        assert_eq!(stats.read_count, 632687);
+        assert_eq!(stats.write_count, 632675);
+        assert_eq!(stats.read_bytes, 4096);
+        assert_eq!(stats.write_bytes, 323932160);
+        assert_eq!(stats.cancelled_write_bytes, 8192);
+
+        assert!(parse_proc_io("syscr: many\n").is_err());
+    }
+}
@@ -10,12 +10,50 @@ pub(crate) mod bindings {
     include!(concat!(env!("OUT_DIR"), "/monitor_rs_ios_macos_binding.rs"));
 }
 
+pub mod clock;
+
 pub mod cpu;
 
+pub mod compat;
+
+mod error;
+pub use error::{
+    perf_strerror, PerfError, PERF_ERR_IO, PERF_ERR_MACH, PERF_ERR_PARSE, PERF_ERR_PERMISSION,
+    PERF_ERR_UNSUPPORTED, PERF_OK,
+};
+
 pub mod mem;
 
 pub mod io;
 
 pub mod fd;
 
+pub mod health;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+pub mod monitor;
+
+mod platform;
+pub use platform::{is_supported, CURRENT_SUPPORTED, SUPPORTED};
+
+pub mod process;
+
+pub mod psi;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod procfs;
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+#[cfg(not(target_os = "windows"))]
+pub mod rlimit;
+
+pub mod runner;
+
+mod summary;
+pub use summary::{summary, Summary};
+
 mod utils;
@@ -0,0 +1,127 @@
+//! A last memory snapshot when an allocation fails.
+use super::{published_snapshot, ProcessMemoryInfo};
+use std::{
+    cell::Cell,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+type Sink = Box<dyn Fn(ProcessMemoryInfo) + Send + Sync>;
+
+static HOOK: AtomicPtr<Sink> = AtomicPtr::new(ptr::null_mut());
+thread_local! {
+    // whether the sink runs on this thread, a const initializer doesn't allocate.
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Call `sink` with the last published memory snapshot whenever an allocation fails, replacing
+/// the previous sink, to get the memory usage in the crash log before the process aborts.
+///
+/// `std::alloc::set_alloc_error_hook` is unstable, so the sink is called by
+/// `CountingAllocator`, which must be the `global_allocator`: nothing is reported otherwise.
+/// It is called as the allocator returns null, before `handle_alloc_error` aborts, and also for
+/// the failures `try_reserve` and the like recover from.
+///
+/// The allocator is exhausted at that point:
+/// - the snapshot is the one of `published_snapshot`, only RSS and VSZ are filled, and they
+///   are 0 if neither a `MemoryMonitor` nor `publish_snapshot` published anything yet. Reading
+///   the memory info afresh could allocate.
+/// - `sink` should not allocate either, e.g. write a formatted line to stderr with a stack
+///   buffer. An allocation failing within `sink` doesn't call it again, while the failures of
+///   the other threads do, concurrently.
+/// - `sink` must not panic, a panic unwinding out of the allocator is undefined behavior.
+///
+/// The replaced sinks are leaked, a failing allocation on another thread may still use them.
+pub fn set_alloc_error_hook_snapshot(sink: impl Fn(ProcessMemoryInfo) + Send + Sync + 'static) {
+    let sink: Sink = Box::new(sink);
+    HOOK.store(Box::into_raw(Box::new(sink)), Ordering::SeqCst);
+}
+
+/// Called by `CountingAllocator` when the system allocator returns null.
+#[cold]
+pub(crate) fn alloc_failed() {
+    let hook = HOOK.load(Ordering::SeqCst);
+    // the thread local is gone while the thread exits, skip the sink then.
+    if hook.is_null()
+        || RUNNING
+            .try_with(|running| running.replace(true))
+            .unwrap_or(true)
+    {
+        return;
+    }
+    let snapshot = published_snapshot().unwrap_or_default();
+    let info = ProcessMemoryInfo {
+        resident_set_size: snapshot.resident_set_size,
+        virtual_memory_size: snapshot.virtual_memory_size,
+        ..Default::default()
+    };
+    // the sinks are never freed.
+    unsafe { (*hook)(info) };
+    let _ = RUNNING.try_with(|running| running.set(false));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mem::{publish_snapshot, CountingAllocator};
+    use std::{
+        alloc::{GlobalAlloc, Layout},
+        sync::atomic::AtomicU64,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    fn fail_alloc() {
+        // far more than any address space, the system allocator can only refuse it.
+        let layout = Layout::from_size_align(isize::MAX as usize / 2, 16).unwrap();
+        let ptr = unsafe { CountingAllocator.alloc(layout) };
+        assert!(ptr.is_null());
+    }
+
+    #[test]
+    fn test_hook_fires() {
+        static REPORTED_RSS: AtomicU64 = AtomicU64::new(0);
+        publish_snapshot(&ProcessMemoryInfo {
+            resident_set_size: 12345,
+            ..Default::default()
+        });
+        set_alloc_error_hook_snapshot(|info| {
+            REPORTED_RSS.store(info.resident_set_size, Ordering::SeqCst);
+        });
+
+        fail_alloc();
+        // another test may publish meanwhile, but not 0.
+        assert_ne!(REPORTED_RSS.load(Ordering::SeqCst), 0);
+
+        // a small allocation succeeds and doesn't call the sink.
+        REPORTED_RSS.store(0, Ordering::SeqCst);
+        let layout = Layout::from_size_align(64, 16).unwrap();
+        unsafe {
+            let ptr = CountingAllocator.alloc(layout);
+            assert!(!ptr.is_null());
+            CountingAllocator.dealloc(ptr, layout);
+        }
+        assert_eq!(REPORTED_RSS.load(Ordering::SeqCst), 0);
+
+        // a failure on another thread is reported while the sink runs, in the same test as the
+        // hook is global.
+        static CALLS: AtomicU64 = AtomicU64::new(0);
+        fn wait_calls(calls: u64) {
+            let start = Instant::now();
+            while CALLS.load(Ordering::SeqCst) < calls && start.elapsed() < Duration::from_secs(5) {
+                thread::yield_now();
+            }
+        }
+        set_alloc_error_hook_snapshot(|_| {
+            if CALLS.fetch_add(1, Ordering::SeqCst) == 0 {
+                // hold the first failure until the one of the other thread is reported.
+                wait_calls(2);
+            }
+        });
+        let first = thread::spawn(fail_alloc);
+        wait_calls(1);
+        fail_alloc();
+        first.join().unwrap();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+}
@@ -11,6 +11,8 @@ static ENABLE: AtomicBool = AtomicBool::new(false);
 /// An allocator tracks inuse allocated bytes.
 ///
 /// The counter is disable by default. Please enable it by `CountingAllocator::enable()` then call `CountingAllocator::get_allocated()` will return the bytes inused.
+///
+/// It also calls the sink of `set_alloc_error_hook_snapshot` when an allocation fails.
 pub struct CountingAllocator;
 
 impl CountingAllocator {
@@ -44,7 +46,9 @@ unsafe impl GlobalAlloc for CountingAllocator {
     #[inline]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let ret = System.alloc(layout);
-        if !ret.is_null() && Self::is_enable() {
+        if ret.is_null() {
+            super::alloc_error::alloc_failed();
+        } else if Self::is_enable() {
             ALLOCATED.fetch_add(layout.size() as isize, Ordering::SeqCst);
         }
         ret
@@ -61,11 +65,9 @@ unsafe impl GlobalAlloc for CountingAllocator {
     #[inline]
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
         let ret: *mut u8 = System.realloc(ptr, layout, new_size);
-        if !ret.is_null()
-            && Self::is_enable()
-            && layout.align() <= MIN_ALIGN
-            && layout.align() <= new_size
-        {
+        if ret.is_null() {
+            super::alloc_error::alloc_failed();
+        } else if Self::is_enable() && layout.align() <= MIN_ALIGN && layout.align() <= new_size {
             ALLOCATED.fetch_add(new_size as isize - layout.size() as isize, Ordering::SeqCst);
         }
         ret
@@ -74,7 +76,9 @@ unsafe impl GlobalAlloc for CountingAllocator {
     #[inline]
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
         let ret = System.alloc_zeroed(layout);
-        if !ret.is_null() && Self::is_enable() {
+        if ret.is_null() {
+            super::alloc_error::alloc_failed();
+        } else if Self::is_enable() {
             ALLOCATED.fetch_add(layout.size() as isize, Ordering::SeqCst);
         }
         ret
@@ -82,4 +86,4 @@ unsafe impl GlobalAlloc for CountingAllocator {
 }
 #[cfg(feature = "allocation_counter")]
 #[global_allocator]
-static _COUNTER: perf_monitor::mem::CountingAllocator = perf_monitor::mem::CountingAllocator;
+static _COUNTER: crate::mem::CountingAllocator = crate::mem::CountingAllocator;
@@ -0,0 +1,115 @@
+//! Best-effort detection of the `malloc` implementation of the process.
+
+/// The `malloc` implementation serving the allocations, see `detected_allocator`.
+///
+/// They return freed memory to the OS very differently, an RSS which doesn't go down after a
+/// free is not necessarily a leak. Each variant tells how to make the RSS follow the live memory
+/// more closely when looking for leaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Allocator {
+    /// glibc's ptmalloc. Freed chunks stay in the per-thread arenas, only the top of the heap
+    /// is trimmed above `M_TRIM_THRESHOLD`: call `release_free_memory` (`malloc_trim`) before
+    /// reading the RSS, and cap the arenas with `MALLOC_ARENA_MAX=2` in many-threaded processes.
+    Glibc,
+    /// musl's mallocng, it unmaps freed memory eagerly, the RSS follows the live memory closely.
+    Musl,
+    /// jemalloc. Dirty pages are purged after `dirty_decay_ms` (10s by default) and only while
+    /// allocating, unless `background_thread:true` is set, e.g. in `MALLOC_CONF`: set
+    /// `dirty_decay_ms:0,muzzy_decay_ms:0` to have the RSS drop on free.
+    Jemalloc,
+    /// mimalloc. Freed pages are reset after `MIMALLOC_PURGE_DELAY` (10ms by default), and
+    /// segments are cached per thread: set `MIMALLOC_PURGE_DELAY=0` for an eager release.
+    Mimalloc,
+    /// tcmalloc. Freed memory stays in the thread and central caches, and is released at
+    /// `TCMALLOC_RELEASE_RATE`: `MallocExtension::ReleaseFreeMemory` returns it at once.
+    Tcmalloc,
+    /// The platform allocator on other targets: the magazine malloc of MacOS and iOS, the
+    /// Windows heap, bionic's scudo on android. They cache freed blocks for reuse, sample the
+    /// RSS after the workload settled.
+    System,
+}
+
+/// Guess the `malloc` implementation of the process.
+///
+/// jemalloc, mimalloc and tcmalloc are recognized by their exported symbols, then by
+/// `LD_PRELOAD` (`DYLD_INSERT_LIBRARIES` on MacOS) naming them, and the libc allocator of the
+/// target is assumed otherwise. On Windows this is always `Allocator::System`.
+///
+/// A Rust `#[global_allocator]` linked statically, e.g. `tikv-jemallocator` or `mimalloc`, only
+/// serves the Rust allocations and doesn't export its symbols: it is not detected, the libc
+/// allocator still serves C code and is the one reported.
+pub fn detected_allocator() -> Allocator {
+    #[cfg(unix)]
+    if let Some(allocator) = from_symbols().or_else(from_preload) {
+        return allocator;
+    }
+    if cfg!(all(target_os = "linux", target_env = "gnu")) {
+        Allocator::Glibc
+    } else if cfg!(target_env = "musl") {
+        Allocator::Musl
+    } else {
+        Allocator::System
+    }
+}
+
+#[cfg(unix)]
+fn from_symbols() -> Option<Allocator> {
+    use std::ffi::CStr;
+
+    let exported = |symbol: &[u8]| {
+        let symbol = CStr::from_bytes_with_nul(symbol).expect("nul terminated symbol");
+        !unsafe { libc::dlsym(libc::RTLD_DEFAULT, symbol.as_ptr()) }.is_null()
+    };
+    if exported(b"mi_version\0") {
+        Some(Allocator::Mimalloc)
+    } else if exported(b"mallctl\0") || exported(b"je_mallctl\0") {
+        Some(Allocator::Jemalloc)
+    } else if exported(b"tc_version\0") {
+        Some(Allocator::Tcmalloc)
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn from_preload() -> Option<Allocator> {
+    let var = if cfg!(any(target_os = "macos", target_os = "ios")) {
+        "DYLD_INSERT_LIBRARIES"
+    } else {
+        "LD_PRELOAD"
+    };
+    let preload = std::env::var(var).ok()?;
+    [
+        ("mimalloc", Allocator::Mimalloc),
+        ("jemalloc", Allocator::Jemalloc),
+        ("tcmalloc", Allocator::Tcmalloc),
+    ]
+    .iter()
+    .find(|(name, _)| preload.contains(name))
+    .map(|(_, allocator)| *allocator)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detected_allocator() {
+        let allocator = detected_allocator();
+        assert_eq!(detected_allocator(), allocator);
+        // no allocator is linked in the tests, only a preloaded one may replace the libc's.
+        if std::env::var_os("LD_PRELOAD").is_none()
+            && std::env::var_os("DYLD_INSERT_LIBRARIES").is_none()
+        {
+            let expected = if cfg!(all(target_os = "linux", target_env = "gnu")) {
+                Allocator::Glibc
+            } else if cfg!(target_env = "musl") {
+                Allocator::Musl
+            } else {
+                Allocator::System
+            };
+            assert_eq!(allocator, expected);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! The memory of the default Metal device, with the `metal` feature.
+
+use std::io;
+
+/// The memory allocated through a Metal device, in bytes.
+///
+/// On Apple silicon and other unified-memory Macs the GPU allocates from the system memory,
+/// this comes on top of what the RSS of the process shows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MetalMemoryInfo {
+    /// `MTLDevice.currentAllocatedSize`, the memory allocated by current process on the device.
+    pub current_allocated_size: u64,
+    /// `MTLDevice.recommendedMaxWorkingSetSize`, how much the device can use without hurting
+    /// performance.
+    pub recommended_max_working_set_size: u64,
+    /// `MTLDevice.hasUnifiedMemory`, whether the device shares the system memory.
+    pub has_unified_memory: bool,
+}
+
+/// Get the memory info of the system default Metal device, `MTLCreateSystemDefaultDevice`.
+///
+/// Requires MacOS 10.15 or later for `hasUnifiedMemory`, the sizes are available since 10.13.
+/// `NotFound` when the system has no Metal device, e.g. in some virtual machines.
+pub fn get_metal_memory_info() -> io::Result<MetalMemoryInfo> {
+    let device = ::metal::Device::system_default()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No Metal device"))?;
+    Ok(MetalMemoryInfo {
+        current_allocated_size: device.current_allocated_size() as u64,
+        recommended_max_working_set_size: device.recommended_max_working_set_size(),
+        has_unified_memory: device.has_unified_memory(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metal_memory_info() {
+        let info = match get_metal_memory_info() {
+            Ok(info) => info,
+            // CI runners may have no GPU.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+            Err(err) => panic!("{}", err),
+        };
+        assert!(info.recommended_max_working_set_size > 0);
+        let device = ::metal::Device::system_default().unwrap();
+        let _buffer = device.new_buffer(1 << 20, ::metal::MTLResourceOptions::StorageModeShared);
+        let after = get_metal_memory_info().unwrap();
+        assert!(after.current_allocated_size >= info.current_allocated_size + (1 << 20));
+    }
+}
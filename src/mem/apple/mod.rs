@@ -1,2 +1,7 @@
 pub mod heap;
 pub mod vm;
+
+#[cfg(feature = "metal")]
+mod gpu;
+#[cfg(feature = "metal")]
+pub use gpu::{get_metal_memory_info, MetalMemoryInfo};
@@ -0,0 +1,184 @@
+//! Memory regression gates against a baseline committed with the code.
+use super::{format_bytes, get_process_memory_info, ProcessMemoryInfo};
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+/// A field which grew past the tolerance since the baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regression {
+    pub field: &'static str,
+    pub baseline: u64,
+    pub current: u64,
+    /// the growth in percent of the baseline, infinite if the baseline was 0.
+    pub change_pct: f64,
+}
+
+/// The outcome of `check_against_baseline`, it displays as one line per regression for the CI
+/// logs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegressionReport {
+    pub tolerance_pct: f64,
+    pub regressions: Vec<Regression>,
+}
+
+impl RegressionReport {
+    /// `true` when no field grew past the tolerance, fail the CI job otherwise.
+    pub fn is_ok(&self) -> bool {
+        self.regressions.is_empty()
+    }
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "no memory regression over {}%", self.tolerance_pct);
+        }
+        for regression in &self.regressions {
+            writeln!(
+                f,
+                "{}: {} -> {} (+{:.1}%, tolerance {}%)",
+                regression.field,
+                format_bytes(regression.baseline),
+                format_bytes(regression.current),
+                regression.change_pct,
+                self.tolerance_pct
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields of `current` more than `tolerance_pct` percent above `baseline`.
+///
+/// Shrinking fields are not regressions. A negative or NaN `tolerance_pct` is taken as 0, any
+/// growth is then a regression.
+pub fn compare_to_baseline(
+    baseline: &ProcessMemoryInfo,
+    current: &ProcessMemoryInfo,
+    tolerance_pct: f64,
+) -> RegressionReport {
+    // also rejects NaN, which max would let through.
+    let tolerance_pct = if tolerance_pct > 0.0 {
+        tolerance_pct
+    } else {
+        0.0
+    };
+    let regressions = baseline
+        .fields()
+        .into_iter()
+        .zip(current.fields())
+        .filter_map(|((field, baseline), (_, current))| {
+            if current <= baseline
+                || current as f64 <= baseline as f64 * (1.0 + tolerance_pct / 100.0)
+            {
+                return None;
+            }
+            let change_pct = if baseline == 0 {
+                f64::INFINITY
+            } else {
+                (current - baseline) as f64 / baseline as f64 * 100.0
+            };
+            Some(Regression {
+                field,
+                baseline,
+                current,
+                change_pct,
+            })
+        })
+        .collect();
+    RegressionReport {
+        tolerance_pct,
+        regressions,
+    }
+}
+
+/// Write the memory info of current process as the JSON baseline at `path` (`serde` feature).
+pub fn write_baseline(path: &Path) -> Result<()> {
+    let info = get_process_memory_info()?;
+    let json = serde_json::to_string_pretty(&info).map_err(Error::other)?;
+    std::fs::write(path, json)
+}
+
+/// Compare the memory info of current process with the baseline `write_baseline` stored at
+/// `path` (`serde` feature), see `compare_to_baseline`.
+///
+/// The fields missing from the baseline, e.g. one written on another platform, are taken as 0.
+/// A baseline which isn't valid JSON fails with `ErrorKind::InvalidData`.
+///
+/// ```no_run
+/// # use workflow_perf_monitor::mem::check_against_baseline;
+/// let report = check_against_baseline("memory-baseline.json".as_ref(), 10.0).unwrap();
+/// assert!(report.is_ok(), "{}", report);
+/// ```
+pub fn check_against_baseline(path: &Path, tolerance_pct: f64) -> Result<RegressionReport> {
+    let json = std::fs::read_to_string(path)?;
+    let baseline: ProcessMemoryInfo =
+        serde_json::from_str(&json).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let current = get_process_memory_info()?;
+    Ok(compare_to_baseline(&baseline, &current, tolerance_pct))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(rss: u64, vsz: u64) -> ProcessMemoryInfo {
+        ProcessMemoryInfo {
+            resident_set_size: rss,
+            virtual_memory_size: vsz,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_to_baseline() {
+        let baseline = info(1000, 4000);
+        assert!(compare_to_baseline(&baseline, &info(1100, 3000), 10.0).is_ok());
+
+        let report = compare_to_baseline(&baseline, &info(1500, 4000), 10.0);
+        assert_eq!(
+            report.regressions,
+            [Regression {
+                field: "resident_set_size",
+                baseline: 1000,
+                current: 1500,
+                change_pct: 50.0,
+            }]
+        );
+        assert!(report.to_string().starts_with("resident_set_size: "));
+
+        let report = compare_to_baseline(&info(0, 4000), &info(1, 4000), 10.0);
+        assert_eq!(report.regressions[0].change_pct, f64::INFINITY);
+
+        // shrinking fields pass whatever the tolerance, any growth fails without one.
+        for tolerance in [-10.0, f64::NAN] {
+            let report = compare_to_baseline(&baseline, &info(1001, 3000), tolerance);
+            assert_eq!(report.tolerance_pct, 0.0);
+            assert_eq!(report.regressions.len(), 1);
+            assert_eq!(report.regressions[0].field, "resident_set_size");
+        }
+    }
+
+    #[test]
+    fn test_check_against_baseline() {
+        let path = std::env::temp_dir().join(format!("perf-baseline-{}.json", std::process::id()));
+        write_baseline(&path).unwrap();
+        let baseline = get_process_memory_info().unwrap().resident_set_size;
+
+        // grow the RSS by more than the baseline.
+        let size = (baseline as usize).max(64 * 1024 * 1024) * 2;
+        let grown = vec![1u8; size];
+        let report = check_against_baseline(&path, 10.0).unwrap();
+        drop(std::hint::black_box(grown));
+        assert!(!report.is_ok());
+        assert_eq!(report.regressions[0].field, "resident_set_size");
+
+        std::fs::write(&path, "not json").unwrap();
+        let err = check_against_baseline(&path, 10.0).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
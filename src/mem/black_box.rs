@@ -0,0 +1,336 @@
+//! A flight recorder of memory samples in a fixed-size file, to read the timeline before a
+//! crash after the restart.
+//!
+//! The file starts with a 24 bytes header: the magic `PERFBBX1`, the record size as a `u32`,
+//! 4 reserved bytes and the capacity as a `u64`. `capacity` records follow, each of
+//! `BLACK_BOX_RECORD_SIZE` bytes: the sequence number, the time in nanoseconds since the Unix epoch, the
+//! RSS, the virtual memory size and an FNV-1a checksum of the 32 bytes before it, as `u64`s.
+//! All the numbers are little-endian. The record `seq` is in the slot `(seq - 1) % capacity`.
+#[cfg(unix)]
+use super::ProcessMemoryInfo;
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8; 8] = b"PERFBBX1";
+const HEADER_SIZE: usize = 24;
+/// The size of a record in the file, in bytes.
+pub const BLACK_BOX_RECORD_SIZE: usize = 40;
+
+/// A sample recovered from the file of a `BlackBox`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlackBoxRecord {
+    /// the number of the record, from 1, across the restarts of the writer.
+    pub seq: u64,
+    pub at: SystemTime,
+    pub resident_set_size: u64,
+    pub virtual_memory_size: u64,
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(word)
+}
+
+#[cfg(any(unix, test))]
+fn encode(record: &BlackBoxRecord) -> [u8; BLACK_BOX_RECORD_SIZE] {
+    let nanos = record
+        .at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .min(u64::MAX as u128) as u64;
+    let mut bytes = [0; BLACK_BOX_RECORD_SIZE];
+    for (i, word) in [
+        record.seq,
+        nanos,
+        record.resident_set_size,
+        record.virtual_memory_size,
+    ]
+    .iter()
+    .enumerate()
+    {
+        bytes[i * 8..i * 8 + 8].copy_from_slice(&word.to_le_bytes());
+    }
+    let sum = checksum(&bytes[..32]);
+    bytes[32..].copy_from_slice(&sum.to_le_bytes());
+    bytes
+}
+
+/// `None` for an empty slot or a torn record, one the writer died writing.
+fn decode(bytes: &[u8]) -> Option<BlackBoxRecord> {
+    let seq = u64_at(bytes, 0);
+    if seq == 0 || checksum(&bytes[..32]) != u64_at(bytes, 32) {
+        return None;
+    }
+    Some(BlackBoxRecord {
+        seq,
+        at: UNIX_EPOCH + Duration::from_nanos(u64_at(bytes, 8)),
+        resident_set_size: u64_at(bytes, 16),
+        virtual_memory_size: u64_at(bytes, 24),
+    })
+}
+
+/// The capacity in the header of `file`, `InvalidData` unless it is a black box file of the
+/// size the header tells.
+fn parse_header(file: &[u8]) -> Result<usize> {
+    let invalid = |what: &str| Error::new(ErrorKind::InvalidData, format!("black box {}", what));
+    if file.len() < HEADER_SIZE || &file[..8] != MAGIC {
+        return Err(invalid("magic mismatch"));
+    }
+    let mut record_size = [0; 4];
+    record_size.copy_from_slice(&file[8..12]);
+    if u32::from_le_bytes(record_size) as usize != BLACK_BOX_RECORD_SIZE {
+        return Err(invalid("record size mismatch"));
+    }
+    let capacity = usize::try_from(u64_at(file, 16)).map_err(|_| invalid("capacity overflow"))?;
+    if capacity
+        .checked_mul(BLACK_BOX_RECORD_SIZE)
+        .map(|size| size + HEADER_SIZE)
+        != Some(file.len())
+    {
+        return Err(invalid("size mismatch"));
+    }
+    Ok(capacity)
+}
+
+/// The valid records of the content of a black box file, oldest first.
+pub fn parse_black_box(file: &[u8]) -> Result<Vec<BlackBoxRecord>> {
+    parse_header(file)?;
+    let mut records: Vec<_> = file[HEADER_SIZE..]
+        .chunks_exact(BLACK_BOX_RECORD_SIZE)
+        .filter_map(decode)
+        .collect();
+    records.sort_unstable_by_key(|record| record.seq);
+    Ok(records)
+}
+
+/// Read the records a `BlackBox` left at `path`, oldest first, e.g. at startup after a crash.
+///
+/// A record torn by a crash in the middle of its write fails its checksum and is skipped, the
+/// ones before it are intact. No locking is needed, the file can be read while written.
+pub fn read_black_box(path: &Path) -> Result<Vec<BlackBoxRecord>> {
+    parse_black_box(&std::fs::read(path)?)
+}
+
+/// Writes memory samples to a memory-mapped ring buffer file, overwriting the oldest, see
+/// `read_black_box`. Unix only.
+///
+/// A write is a copy into the mapping, no syscall: the kernel writes the dirty pages back on
+/// its own, and they survive a crash of the process. `flush` is only needed to survive
+/// a crash of the system. With a sample every second, a capacity of 600 keeps the last
+/// ten minutes in 24KB.
+///
+/// ```no_run
+/// # use workflow_perf_monitor::mem::{get_process_memory_info, read_black_box, BlackBox};
+/// let path = std::path::Path::new("/var/tmp/app.blackbox");
+/// if let Ok(records) = read_black_box(path) {
+///     println!("{} samples before the restart", records.len());
+/// }
+/// let mut black_box = BlackBox::open(path, 600).unwrap();
+/// black_box.record(&get_process_memory_info().unwrap());
+/// ```
+#[cfg(unix)]
+pub struct BlackBox {
+    map: std::ptr::NonNull<u8>,
+    len: usize,
+    capacity: usize,
+    next_seq: u64,
+}
+
+// The mapping is owned and only written through `&mut self`.
+#[cfg(unix)]
+unsafe impl Send for BlackBox {}
+
+#[cfg(unix)]
+impl BlackBox {
+    /// Map the file at `path` holding `capacity` records, at least 1, creating it if needed.
+    ///
+    /// The records of an existing file are kept, the writes continue after the newest one.
+    /// An existing file of another capacity, or not a black box file, is `InvalidData`.
+    pub fn open(path: &Path, capacity: usize) -> Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let capacity = capacity.max(1);
+        let len = capacity
+            .checked_mul(BLACK_BOX_RECORD_SIZE)
+            .and_then(|size| size.checked_add(HEADER_SIZE))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "black box capacity overflow"))?;
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let new = file.metadata()?.len() == 0;
+        if new {
+            file.set_len(len as u64)?;
+        }
+        let existing = file.metadata()?.len();
+        if existing != len as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "black box size mismatch, {} bytes for {} records is not {}",
+                    existing, capacity, len
+                ),
+            ));
+        }
+        let map = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        let mut black_box = BlackBox {
+            map: std::ptr::NonNull::new(map as *mut u8).expect("mmap returned NULL"),
+            len,
+            capacity,
+            next_seq: 1,
+        };
+        if new {
+            let bytes = black_box.bytes();
+            bytes[..8].copy_from_slice(MAGIC);
+            bytes[8..12].copy_from_slice(&(BLACK_BOX_RECORD_SIZE as u32).to_le_bytes());
+            bytes[16..24].copy_from_slice(&(capacity as u64).to_le_bytes());
+        }
+        let records = parse_black_box(black_box.bytes())?;
+        black_box.next_seq = records.last().map_or(1, |newest| newest.seq + 1);
+        Ok(black_box)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Write a sample taken now, see `record_at`.
+    pub fn record(&mut self, info: &ProcessMemoryInfo) {
+        self.record_at(SystemTime::now(), info);
+    }
+
+    /// Write a sample taken at `at` over the oldest record.
+    pub fn record_at(&mut self, at: SystemTime, info: &ProcessMemoryInfo) {
+        let record = encode(&BlackBoxRecord {
+            seq: self.next_seq,
+            at,
+            resident_set_size: info.resident_set_size,
+            virtual_memory_size: info.virtual_memory_size,
+        });
+        let offset = HEADER_SIZE
+            + ((self.next_seq - 1) % self.capacity as u64) as usize * BLACK_BOX_RECORD_SIZE;
+        self.bytes()[offset..offset + BLACK_BOX_RECORD_SIZE].copy_from_slice(&record);
+        self.next_seq += 1;
+    }
+
+    /// Write the records back to the file synchronously, `msync`.
+    pub fn flush(&self) -> Result<()> {
+        if unsafe { libc::msync(self.map.as_ptr() as *mut _, self.len, libc::MS_SYNC) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn bytes(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for BlackBox {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map.as_ptr() as *mut _, self.len) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_black_box() {
+        let mut file = vec![0; HEADER_SIZE + 2 * BLACK_BOX_RECORD_SIZE];
+        file[..8].copy_from_slice(MAGIC);
+        file[8..12].copy_from_slice(&(BLACK_BOX_RECORD_SIZE as u32).to_le_bytes());
+        file[16..24].copy_from_slice(&2u64.to_le_bytes());
+        assert_eq!(parse_black_box(&file).unwrap(), []);
+
+        let record = BlackBoxRecord {
+            seq: 3,
+            at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            resident_set_size: 1 << 20,
+            virtual_memory_size: 1 << 30,
+        };
+        file[HEADER_SIZE..HEADER_SIZE + BLACK_BOX_RECORD_SIZE].copy_from_slice(&encode(&record));
+        assert_eq!(parse_black_box(&file).unwrap(), [record]);
+
+        // a torn record.
+        file[HEADER_SIZE + 20] ^= 1;
+        assert_eq!(parse_black_box(&file).unwrap(), []);
+
+        for invalid in [
+            &file[..HEADER_SIZE + BLACK_BOX_RECORD_SIZE],
+            &[0; HEADER_SIZE][..],
+        ] {
+            let err = parse_black_box(invalid).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_black_box() {
+        let info = |rss: u64| ProcessMemoryInfo {
+            resident_set_size: rss,
+            virtual_memory_size: rss * 4,
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join(format!("perf-black-box-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut black_box = BlackBox::open(&path, 4).unwrap();
+        for i in 1..=10 {
+            black_box.record_at(start + Duration::from_secs(i), &info(i));
+        }
+        drop(black_box);
+
+        let records = read_black_box(&path).unwrap();
+        let seqs: Vec<u64> = records.iter().map(|record| record.seq).collect();
+        assert_eq!(seqs, [7, 8, 9, 10]);
+        assert_eq!(records[3].at, start + Duration::from_secs(10));
+        assert_eq!(records[3].resident_set_size, 10);
+        assert_eq!(records[3].virtual_memory_size, 40);
+
+        // a restart continues after the newest record, over the oldest.
+        let mut black_box = BlackBox::open(&path, 4).unwrap();
+        black_box.record(&info(11));
+        black_box.flush().unwrap();
+        let seqs: Vec<u64> = read_black_box(&path)
+            .unwrap()
+            .iter()
+            .map(|record| record.seq)
+            .collect();
+        assert_eq!(seqs, [8, 9, 10, 11]);
+        drop(black_box);
+
+        let err = BlackBox::open(&path, 8).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
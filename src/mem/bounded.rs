@@ -0,0 +1,236 @@
+use super::ProcessMemoryInfo;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{RecvError, RecvTimeoutError, TryRecvError},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+/// What the sampling thread does with a sample when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait for the consumer, the samples are not taken on schedule meanwhile.
+    Block,
+    /// Drop the oldest queued sample, the queue always ends with the freshest one.
+    DropOldest,
+    /// Drop the new sample, the queue keeps the oldest ones.
+    DropNewest,
+}
+
+struct State {
+    samples: VecDeque<ProcessMemoryInfo>,
+    sender_alive: bool,
+    receiver_alive: bool,
+    stopping: bool,
+}
+
+/// The queue between the sampling thread and a `SampleReceiver`.
+pub(crate) struct SampleQueue {
+    state: Mutex<State>,
+    ready: Condvar,
+    space: Condvar,
+    capacity: usize,
+    policy: Backpressure,
+    dropped: AtomicU64,
+}
+
+impl SampleQueue {
+    pub(crate) fn new(capacity: usize, policy: Backpressure) -> Arc<Self> {
+        let capacity = capacity.max(1);
+        Arc::new(SampleQueue {
+            state: Mutex::new(State {
+                samples: VecDeque::with_capacity(capacity),
+                sender_alive: true,
+                receiver_alive: true,
+                stopping: false,
+            }),
+            ready: Condvar::new(),
+            space: Condvar::new(),
+            capacity,
+            policy,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // the critical sections don't panic, but don't propagate a poison anyway.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue `info` according to the policy, return false once the receiver is gone.
+    pub(crate) fn push(&self, info: ProcessMemoryInfo) -> bool {
+        let mut state = self.lock();
+        if state.samples.len() >= self.capacity {
+            match self.policy {
+                Backpressure::Block => {
+                    while state.samples.len() >= self.capacity
+                        && state.receiver_alive
+                        && !state.stopping
+                    {
+                        state = self.space.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    if state.stopping {
+                        // the sampling thread is about to exit, the sample is dropped.
+                        return state.receiver_alive;
+                    }
+                }
+                Backpressure::DropOldest => {
+                    state.samples.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Backpressure::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return state.receiver_alive;
+                }
+            }
+        }
+        if !state.receiver_alive {
+            return false;
+        }
+        state.samples.push_back(info);
+        self.ready.notify_one();
+        true
+    }
+
+    /// Wake up a sampling thread blocked on a full queue, for it to see the stop request.
+    pub(crate) fn stop(&self) {
+        self.lock().stopping = true;
+        self.space.notify_all();
+    }
+
+    /// Called when the sampling thread exits.
+    pub(crate) fn close(&self) {
+        self.lock().sender_alive = false;
+        self.ready.notify_all();
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// The receiving side of `MemoryMonitor::spawn_bounded`, like a `std::sync::mpsc::Receiver`.
+///
+/// The sampling thread exits when it is dropped.
+pub struct SampleReceiver {
+    queue: Arc<SampleQueue>,
+}
+
+impl SampleReceiver {
+    pub(crate) fn new(queue: Arc<SampleQueue>) -> Self {
+        SampleReceiver { queue }
+    }
+
+    fn pop(&self, state: &mut State) -> Option<ProcessMemoryInfo> {
+        let info = state.samples.pop_front()?;
+        self.queue.space.notify_one();
+        Some(info)
+    }
+
+    /// Wait for a sample, `Err` once the sampling thread has exited and the queue is empty.
+    pub fn recv(&self) -> Result<ProcessMemoryInfo, RecvError> {
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(info) = self.pop(&mut state) {
+                return Ok(info);
+            }
+            if !state.sender_alive {
+                return Err(RecvError);
+            }
+            state = self
+                .queue
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<ProcessMemoryInfo, TryRecvError> {
+        let mut state = self.queue.lock();
+        match self.pop(&mut state) {
+            Some(info) => Ok(info),
+            None if state.sender_alive => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<ProcessMemoryInfo, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(info) = self.pop(&mut state) {
+                return Ok(info);
+            }
+            if !state.sender_alive {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .queue
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+impl Drop for SampleReceiver {
+    fn drop(&mut self) {
+        self.queue.lock().receiver_alive = false;
+        self.queue.space.notify_all();
+    }
+}
+
+/// The sampling thread side of the queue, the receiver sees the disconnection however the
+/// thread exits, panics included.
+pub(crate) struct SampleSender(pub(crate) Arc<SampleQueue>);
+
+impl SampleSender {
+    pub(crate) fn send(&self, info: ProcessMemoryInfo) -> bool {
+        self.0.push(info)
+    }
+}
+
+impl Drop for SampleSender {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(rss: u64) -> ProcessMemoryInfo {
+        ProcessMemoryInfo {
+            resident_set_size: rss,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let queue = SampleQueue::new(3, Backpressure::DropOldest);
+        let sender = SampleSender(queue.clone());
+        let receiver = SampleReceiver::new(queue.clone());
+        for rss in 1..=5 {
+            assert!(sender.send(info(rss)));
+        }
+        assert_eq!(queue.dropped(), 2);
+        drop(sender);
+
+        // the 3 freshest samples survive, in order.
+        let kept: Vec<u64> = std::iter::from_fn(|| receiver.recv().ok())
+            .map(|info| info.resident_set_size)
+            .collect();
+        assert_eq!(kept, [3, 4, 5]);
+        assert_eq!(receiver.try_recv().err(), Some(TryRecvError::Disconnected));
+    }
+}
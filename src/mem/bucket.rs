@@ -0,0 +1,212 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::ProcessMemoryInfo;
+use crate::clock::{Clock, SystemClock};
+
+/// Statistics over the RSS samples of one closed window, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemorySummary {
+    /// start of the window, the window ends `window` later.
+    pub window_start: Instant,
+    pub min: u64,
+    pub avg: f64,
+    pub max: u64,
+    /// nearest-rank 95th percentile: the smallest sample at least 95% of the samples don't exceed.
+    pub p95: u64,
+    pub samples: usize,
+}
+
+/// Pre-aggregates RSS samples into one `MemorySummary` per fixed time window, for backends
+/// that store one row per window.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::{get_process_memory_info, BucketAggregator};
+/// # use std::time::Duration;
+/// let mut aggregator = BucketAggregator::new(Duration::from_secs(10));
+/// aggregator.push(&get_process_memory_info().unwrap());
+/// for summary in aggregator.drain() {
+///     println!("p95 {}", summary.p95);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BucketAggregator<C = SystemClock> {
+    window: Duration,
+    current: Option<(Instant, Vec<u64>)>,
+    closed: VecDeque<MemorySummary>,
+    clock: C,
+}
+
+impl BucketAggregator {
+    /// `window` is at least 1ns.
+    pub fn new(window: Duration) -> Self {
+        Self::with_clock(window, SystemClock)
+    }
+}
+
+impl<C: Clock> BucketAggregator<C> {
+    /// As `new`, with `push` reading the time from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(window: Duration, clock: C) -> Self {
+        BucketAggregator {
+            window: window.max(Duration::from_nanos(1)),
+            current: None,
+            closed: VecDeque::new(),
+            clock,
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Add a sample taken now, according to the clock.
+    pub fn push(&mut self, info: &ProcessMemoryInfo) {
+        let now = self.clock.now();
+        self.push_at(now, info);
+    }
+
+    /// Count the RSS of `info` in the window of `now`, which should not go back in time.
+    ///
+    /// The first sample starts the first window, the next ones are aligned on it. A sample past
+    /// the current window closes it, windows without samples are not summarized. A sample older
+    /// than the current window is counted in it.
+    pub fn push_at(&mut self, now: Instant, info: &ProcessMemoryInfo) {
+        let rss = info.resident_set_size;
+        let Some((start, samples)) = &mut self.current else {
+            self.current = Some((now, vec![rss]));
+            return;
+        };
+        let elapsed = now.saturating_duration_since(*start);
+        if elapsed < self.window {
+            samples.push(rss);
+            return;
+        }
+        // in nanoseconds, the number of windows skipped may not fit a u32.
+        let window = self.window.as_nanos();
+        let skipped = elapsed.as_nanos() / window * window;
+        // `skipped` is at most `elapsed`, its seconds fit a u64.
+        let next = *start
+            + Duration::new(
+                (skipped / 1_000_000_000) as u64,
+                (skipped % 1_000_000_000) as u32,
+            );
+        self.close();
+        self.current = Some((next, vec![rss]));
+    }
+
+    /// Close the current window now, even if it isn't over, e.g. at shutdown.
+    pub fn flush(&mut self) {
+        self.close();
+    }
+
+    /// Take the summaries of the windows closed so far, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = MemorySummary> + '_ {
+        self.closed.drain(..)
+    }
+
+    fn close(&mut self) {
+        let Some((window_start, mut samples)) = self.current.take() else {
+            return;
+        };
+        samples.sort_unstable();
+        let count = samples.len();
+        let rank = (count * 95).div_ceil(100).max(1);
+        self.closed.push_back(MemorySummary {
+            window_start,
+            min: samples[0],
+            avg: samples.iter().map(|rss| *rss as u128).sum::<u128>() as f64 / count as f64,
+            max: samples[count - 1],
+            p95: samples[rank - 1],
+            samples: count,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(rss: u64) -> ProcessMemoryInfo {
+        ProcessMemoryInfo {
+            resident_set_size: rss,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_two_windows() {
+        let start = Instant::now();
+        let mut aggregator = BucketAggregator::new(Duration::from_secs(10));
+        // 1..=100 over the first window, one sample every 50ms.
+        for i in 1..=100u64 {
+            aggregator.push_at(start + Duration::from_millis(i * 50), &info(i));
+        }
+        assert_eq!(aggregator.drain().count(), 0);
+        // the first sample of the second window closes the first one.
+        for (secs, rss) in [(12, 1000), (15, 3000), (19, 2000)] {
+            aggregator.push_at(start + Duration::from_secs(secs), &info(rss));
+        }
+        aggregator.push_at(start + Duration::from_secs(35), &info(7));
+        aggregator.flush();
+
+        let summaries: Vec<_> = aggregator.drain().collect();
+        assert_eq!(summaries.len(), 3);
+        let first = summaries[0];
+        assert_eq!(first.window_start, start + Duration::from_millis(50));
+        assert_eq!(
+            (first.min, first.max, first.p95, first.samples),
+            (1, 100, 95, 100)
+        );
+        assert_eq!(first.avg, 50.5);
+
+        let second = summaries[1];
+        assert_eq!(second.window_start, start + Duration::from_millis(10_050));
+        assert_eq!(
+            (second.min, second.max, second.p95, second.samples),
+            (1000, 3000, 3000, 3)
+        );
+        assert_eq!(second.avg, 2000.0);
+
+        // the empty window in between is skipped, the last one stays aligned.
+        assert_eq!(
+            summaries[2].window_start,
+            start + Duration::from_millis(30_050)
+        );
+        assert_eq!(summaries[2].samples, 1);
+        assert_eq!(aggregator.drain().count(), 0);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = crate::clock::MockClock::new();
+        let start = clock.now();
+        let mut aggregator = BucketAggregator::with_clock(Duration::from_secs(10), clock.clone());
+        for rss in [1, 2, 3] {
+            aggregator.push(&info(rss));
+            clock.advance(Duration::from_secs(4));
+        }
+        // the third sample, 8s in, still is in the first window.
+        assert_eq!(aggregator.drain().count(), 0);
+        aggregator.push(&info(4));
+        let summaries: Vec<_> = aggregator.drain().collect();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].window_start, start);
+        assert_eq!((summaries[0].max, summaries[0].samples), (3, 3));
+    }
+
+    #[test]
+    fn test_many_windows_skipped() {
+        let start = Instant::now();
+        let mut aggregator = BucketAggregator::new(Duration::from_nanos(1));
+        aggregator.push_at(start, &info(1));
+        // more than u32::MAX windows later.
+        let later = start + Duration::from_secs(10) + Duration::from_nanos(7);
+        aggregator.push_at(later, &info(2));
+        aggregator.flush();
+        let summaries: Vec<_> = aggregator.drain().collect();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[1].window_start, later);
+    }
+}
@@ -0,0 +1,161 @@
+use super::{get_process_memory_info, ProcessMemoryInfo};
+use crate::clock::{Clock, SystemClock};
+use std::{
+    io::Result,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+/// A latest-value cache in front of `get_process_memory_info`.
+///
+/// `get` returns the cached value while it is younger than `max_staleness`,
+/// otherwise it refreshes it. Refreshes are single-flight: when many threads
+/// find the value stale at the same time only one of them reads from the OS,
+/// the others wait for it and share the result. So N readers per second cost
+/// at most one syscall per `max_staleness`.
+///
+/// Errors are returned to the caller that performed the refresh and are not cached.
+pub struct CachedMemory<C = SystemClock> {
+    max_staleness: Duration,
+    reader: fn() -> Result<ProcessMemoryInfo>,
+    latest: RwLock<Option<(Instant, ProcessMemoryInfo)>>,
+    refresh: Mutex<()>,
+    clock: C,
+}
+
+impl CachedMemory {
+    /// Create a cache whose values are considered fresh for `max_staleness`.
+    pub fn new(max_staleness: Duration) -> Self {
+        Self::with_clock(max_staleness, SystemClock)
+    }
+}
+
+impl<C: Clock> CachedMemory<C> {
+    /// As `new`, with the age of the cached value read from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(max_staleness: Duration, clock: C) -> Self {
+        Self::with_reader(max_staleness, get_process_memory_info, clock)
+    }
+
+    fn with_reader(
+        max_staleness: Duration,
+        reader: fn() -> Result<ProcessMemoryInfo>,
+        clock: C,
+    ) -> Self {
+        CachedMemory {
+            max_staleness,
+            reader,
+            latest: RwLock::new(None),
+            refresh: Mutex::new(()),
+            clock,
+        }
+    }
+
+    /// The configured max-staleness.
+    pub fn max_staleness(&self) -> Duration {
+        self.max_staleness
+    }
+
+    /// return the cached value if it is fresh enough, or refresh it.
+    pub fn get(&self) -> Result<ProcessMemoryInfo> {
+        if let Some(info) = self.fresh() {
+            return Ok(info);
+        }
+
+        // Only one refresher at a time, the others block here and pick up its result.
+        let _guard = self.refresh.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(info) = self.fresh() {
+            return Ok(info);
+        }
+
+        let info = (self.reader)()?;
+        *self.latest.write().unwrap_or_else(|e| e.into_inner()) =
+            Some((self.clock.now(), info.clone()));
+        Ok(info)
+    }
+
+    /// Drop the cached value, the next `get` reads from the OS.
+    pub fn invalidate(&self) {
+        *self.latest.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    fn fresh(&self) -> Option<ProcessMemoryInfo> {
+        let latest = self.latest.read().unwrap_or_else(|e| e.into_inner());
+        match &*latest {
+            Some((at, info))
+                if self.clock.now().saturating_duration_since(*at) < self.max_staleness =>
+            {
+                Some(info.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Barrier,
+    };
+
+    static READS: AtomicUsize = AtomicUsize::new(0);
+
+    fn counting_reader() -> Result<ProcessMemoryInfo> {
+        READS.fetch_add(1, Ordering::SeqCst);
+        // make the read slow enough for the callers to pile up.
+        std::thread::sleep(Duration::from_millis(50));
+        get_process_memory_info()
+    }
+
+    #[test]
+    fn test_single_read_within_ttl() {
+        let cache = Arc::new(CachedMemory::with_reader(
+            Duration::from_secs(60),
+            counting_reader,
+            SystemClock,
+        ));
+        const CALLERS: usize = 32;
+        let barrier = Arc::new(Barrier::new(CALLERS));
+
+        let handles: Vec<_> = (0..CALLERS)
+            .map(|_| {
+                let cache = cache.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    cache.get().unwrap().resident_set_size
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap() > 0);
+        }
+        assert_eq!(READS.load(Ordering::SeqCst), 1);
+
+        cache.invalidate();
+        cache.get().unwrap();
+        assert_eq!(READS.load(Ordering::SeqCst), 2);
+    }
+
+    static MOCK_READS: AtomicUsize = AtomicUsize::new(0);
+
+    fn mock_reader() -> Result<ProcessMemoryInfo> {
+        MOCK_READS.fetch_add(1, Ordering::SeqCst);
+        Ok(ProcessMemoryInfo::default())
+    }
+
+    #[test]
+    fn test_mock_clock_staleness() {
+        let clock = crate::clock::MockClock::new();
+        let cache = CachedMemory::with_reader(Duration::from_secs(10), mock_reader, clock.clone());
+        cache.get().unwrap();
+        clock.advance(Duration::from_secs(9));
+        cache.get().unwrap();
+        assert_eq!(MOCK_READS.load(Ordering::SeqCst), 1);
+
+        clock.advance(Duration::from_secs(1));
+        cache.get().unwrap();
+        assert_eq!(MOCK_READS.load(Ordering::SeqCst), 2);
+    }
+}
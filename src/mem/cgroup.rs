@@ -0,0 +1,352 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+/// Memory usage and limit of the cgroup of current process.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CgroupMemory {
+    /// `memory.current` on cgroup v2, `memory.usage_in_bytes` on v1.
+    pub usage: u64,
+    /// `memory.max` on cgroup v2, `memory.limit_in_bytes` on v1, `None` when unlimited.
+    pub limit: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// cgroup v1 reports no limit as the largest page-aligned `i64`, anything that large is unlimited.
+const UNLIMITED_THRESHOLD: u64 = 1 << 62;
+
+/// Parse the content of `memory.max` (v2) or `memory.limit_in_bytes` (v1), `None` means unlimited.
+pub fn parse_cgroup_limit(limit: &str) -> Result<Option<u64>> {
+    let limit = limit.trim();
+    if limit == "max" {
+        return Ok(None);
+    }
+    let limit: u64 = limit.parse().map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid cgroup memory limit {:?}: {}", limit, e),
+        )
+    })?;
+    Ok(if limit >= UNLIMITED_THRESHOLD {
+        None
+    } else {
+        Some(limit)
+    })
+}
+
+/// Find the memory cgroup in the content of `/proc/self/cgroup`, a v1 memory hierarchy wins
+/// over the v2 one as the memory controller can't be enabled on both.
+fn parse_proc_cgroup(proc_cgroup: &str) -> Option<(CgroupVersion, &str)> {
+    let mut v2 = None;
+    for line in proc_cgroup.lines() {
+        let mut parts = line.splitn(3, ':');
+        let (Some(id), Some(controllers), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if controllers.split(',').any(|c| c == "memory") {
+            return Some((CgroupVersion::V1, path));
+        }
+        if id == "0" && controllers.is_empty() {
+            v2 = Some((CgroupVersion::V2, path));
+        }
+    }
+    v2
+}
+
+impl CgroupVersion {
+    fn usage_file(self) -> &'static str {
+        match self {
+            CgroupVersion::V1 => "memory.usage_in_bytes",
+            CgroupVersion::V2 => "memory.current",
+        }
+    }
+
+    fn limit_file(self) -> &'static str {
+        match self {
+            CgroupVersion::V1 => "memory.limit_in_bytes",
+            CgroupVersion::V2 => "memory.max",
+        }
+    }
+}
+
+/// The directory of the memory cgroup of current process, `None` if there is none.
+pub(crate) fn cgroup_memory_dir() -> Result<Option<(CgroupVersion, PathBuf)>> {
+    let proc_cgroup = match crate::procfs::read_to_string("self/cgroup") {
+        Ok(s) => s,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let Some((version, path)) = parse_proc_cgroup(&proc_cgroup) else {
+        return Ok(None);
+    };
+    let mount = Path::new(match version {
+        CgroupVersion::V1 => "/sys/fs/cgroup/memory",
+        CgroupVersion::V2 => "/sys/fs/cgroup",
+    });
+    // Within a cgroup namespace the mount point is the cgroup itself, but the path may still
+    // be the one seen from the host.
+    let candidates = [
+        mount.join(path.trim_start_matches('/')),
+        mount.to_path_buf(),
+    ];
+    Ok(candidates
+        .iter()
+        .find(|dir| dir.join(version.usage_file()).exists())
+        .map(|dir| (version, dir.clone())))
+}
+
+/// Get the memory usage and limit of the cgroup of current process, v1 and v2 are supported.
+///
+/// `None` is returned when the process is not in a memory cgroup, as on a host without
+/// the memory controller.
+pub fn get_cgroup_memory() -> Result<Option<CgroupMemory>> {
+    let Some((version, dir)) = cgroup_memory_dir()? else {
+        return Ok(None);
+    };
+    let usage = parse_usage(&std::fs::read_to_string(dir.join(version.usage_file()))?)?;
+    let limit = limit_from_file(std::fs::read_to_string(dir.join(version.limit_file())))?;
+    Ok(Some(CgroupMemory { usage, limit }))
+}
+
+fn parse_usage(usage: &str) -> Result<u64> {
+    usage.trim().parse().map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid cgroup memory usage {:?}: {}", usage.trim(), e),
+        )
+    })
+}
+
+/// The limit out of the result of reading the limit file, which the root cgroup of v2 lacks.
+fn limit_from_file(content: Result<String>) -> Result<Option<u64>> {
+    match content {
+        Ok(limit) => parse_cgroup_limit(&limit),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether the memory cgroup of current process has a finite limit.
+///
+/// It is false on hosts without the memory controller, at the root cgroup, with a limit of
+/// `max` or the cgroup v1 "unlimited" sentinel, and when the cgroup can't be read.
+pub fn is_memory_constrained() -> bool {
+    matches!(
+        get_cgroup_memory(),
+        Ok(Some(CgroupMemory { limit: Some(_), .. }))
+    )
+}
+
+/// Sum the kernel memory in the content of the `memory.stat` of a cgroup v2, in bytes.
+///
+/// It is `kernel + sock`: `kernel` covers the slab, kernel stacks, page tables, percpu and
+/// vmalloc memory since Linux 5.18. Older kernels lack it, `slab + sock` is returned then.
+pub fn parse_cgroup_kernel_memory(stat: &str) -> u64 {
+    let (mut kernel, mut slab, mut sock) = (None, 0, 0);
+    for line in stat.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse::<u64>() else {
+            continue;
+        };
+        match key {
+            "kernel" => kernel = Some(value),
+            "slab" => slab = value,
+            "sock" => sock = value,
+            _ => {}
+        }
+    }
+    kernel.unwrap_or(slab) + sock
+}
+
+/// Get the kernel memory charged to the memory cgroup of current process: slab, socket
+/// buffers and the like, in bytes. `ErrorKind::NotFound` is returned out of a memory cgroup.
+///
+/// On cgroup v2 it comes from `memory.stat`, see `parse_cgroup_kernel_memory`. On cgroup v1
+/// the kernel memory is accounted separately, it is `memory.kmem.usage_in_bytes`, slab
+/// included, plus `memory.kmem.tcp.usage_in_bytes`; files which recent kernels dropped
+/// along with the v1 kmem accounting, 0 is counted then.
+pub fn get_cgroup_kernel_memory() -> Result<u64> {
+    let Some((version, dir)) = cgroup_memory_dir()? else {
+        return Err(Error::new(ErrorKind::NotFound, "not in a memory cgroup"));
+    };
+    match version {
+        CgroupVersion::V2 => Ok(parse_cgroup_kernel_memory(&std::fs::read_to_string(
+            dir.join("memory.stat"),
+        )?)),
+        CgroupVersion::V1 => {
+            let mut total = 0;
+            for file in [
+                "memory.kmem.usage_in_bytes",
+                "memory.kmem.tcp.usage_in_bytes",
+            ] {
+                total += match std::fs::read_to_string(dir.join(file)) {
+                    Ok(usage) => usage.trim().parse().unwrap_or(0),
+                    Err(e) if e.kind() == ErrorKind::NotFound => 0,
+                    Err(e) => return Err(e),
+                };
+            }
+            Ok(total)
+        }
+    }
+}
+
+/// The usage minus the inactive file cache, out of the content of the usage file and
+/// `memory.stat`. v1 reports the inactive file cache of the hierarchy as `total_inactive_file`.
+fn parse_working_set(version: CgroupVersion, usage: &str, stat: &str) -> Result<u64> {
+    let key = match version {
+        CgroupVersion::V1 => "total_inactive_file",
+        CgroupVersion::V2 => "inactive_file",
+    };
+    let inactive_file = stat
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(k, _)| *k == key)
+        .and_then(|(_, value)| value.trim().parse::<u64>().ok())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Missing {} in memory.stat", key),
+            )
+        })?;
+    Ok(parse_usage(usage)?.saturating_sub(inactive_file))
+}
+
+/// Get the working set of the memory cgroup of current process, the usage minus the inactive
+/// file cache, in bytes. `ErrorKind::NotFound` is returned out of a memory cgroup.
+///
+/// This is the formula of the kubelet, the working set is the memory Kubernetes compares to
+/// the limit to evict pods: unlike the usage it excludes the page cache the kernel can
+/// reclaim easily.
+pub fn get_cgroup_working_set() -> Result<u64> {
+    let Some((version, dir)) = cgroup_memory_dir()? else {
+        return Err(Error::new(ErrorKind::NotFound, "not in a memory cgroup"));
+    };
+    parse_working_set(
+        version,
+        &std::fs::read_to_string(dir.join(version.usage_file()))?,
+        &std::fs::read_to_string(dir.join("memory.stat"))?,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_cgroup_limit() {
+        assert_eq!(parse_cgroup_limit("max\n").unwrap(), None);
+        assert_eq!(parse_cgroup_limit("536870912\n").unwrap(), Some(512 << 20));
+        assert_eq!(parse_cgroup_limit("9223372036854771712").unwrap(), None);
+        assert!(parse_cgroup_limit("lots").is_err());
+    }
+
+    #[test]
+    fn test_limit_from_file() {
+        assert_eq!(limit_from_file(Ok("max\n".to_string())).unwrap(), None);
+        assert_eq!(
+            limit_from_file(Ok("1073741824\n".to_string())).unwrap(),
+            Some(1 << 30)
+        );
+        assert_eq!(
+            limit_from_file(Err(Error::from(ErrorKind::NotFound))).unwrap(),
+            None
+        );
+        assert!(limit_from_file(Err(Error::from(ErrorKind::PermissionDenied))).is_err());
+    }
+
+    #[test]
+    fn test_parse_proc_cgroup() {
+        assert_eq!(
+            parse_proc_cgroup("0::/user.slice/session-2.scope\n"),
+            Some((CgroupVersion::V2, "/user.slice/session-2.scope"))
+        );
+        let hybrid = "5:devices:/\n4:memory:/docker/0123abcd\n1:name=systemd:/\n0::/\n";
+        assert_eq!(
+            parse_proc_cgroup(hybrid),
+            Some((CgroupVersion::V1, "/docker/0123abcd"))
+        );
+        assert_eq!(
+            parse_proc_cgroup("3:cpu,memory:/a\n"),
+            Some((CgroupVersion::V1, "/a"))
+        );
+        assert_eq!(parse_proc_cgroup("2:cpu:/\n"), None);
+    }
+
+    #[test]
+    fn test_parse_working_set() {
+        let stat = "anon 104857600\nfile 52428800\nactive_file 41943040\ninactive_file 10485760\n";
+        assert_eq!(
+            parse_working_set(CgroupVersion::V2, "157286400\n", stat).unwrap(),
+            157286400 - 10485760
+        );
+        let stat = "cache 52428800\ninactive_file 1048576\ntotal_inactive_file 10485760\n";
+        assert_eq!(
+            parse_working_set(CgroupVersion::V1, "157286400\n", stat).unwrap(),
+            157286400 - 10485760
+        );
+        // the usage is sampled before the stat, the cache may have grown meanwhile.
+        assert_eq!(
+            parse_working_set(CgroupVersion::V2, "4096", "inactive_file 8192\n").unwrap(),
+            0
+        );
+        assert!(parse_working_set(CgroupVersion::V2, "4096", "anon 4096\n").is_err());
+        assert!(parse_working_set(CgroupVersion::V2, "lots", "inactive_file 0\n").is_err());
+    }
+
+    #[test]
+    fn test_get_cgroup_working_set() {
+        match get_cgroup_working_set() {
+            Ok(working_set) => {
+                let usage = get_cgroup_memory().unwrap().unwrap().usage;
+                // both grow and shrink between the reads.
+                assert!(working_set <= usage + (64 << 20));
+            }
+            Err(e) => assert_eq!(e.kind(), ErrorKind::NotFound),
+        }
+    }
+
+    #[test]
+    fn test_parse_cgroup_kernel_memory() {
+        let stat = "\
+anon 104857600
+file 52428800
+kernel 8388608
+kernel_stack 1048576
+pagetables 2097152
+sock 4096
+shmem 0
+slab 3145728
+inactive_file 1048576
+";
+        assert_eq!(parse_cgroup_kernel_memory(stat), 8388608 + 4096);
+        // before Linux 5.18.
+        let stat = "anon 104857600\nsock 4096\nslab 3145728\n";
+        assert_eq!(parse_cgroup_kernel_memory(stat), 3145728 + 4096);
+        assert_eq!(parse_cgroup_kernel_memory(""), 0);
+    }
+
+    #[test]
+    fn test_get_cgroup_memory() {
+        let cgroup = get_cgroup_memory().unwrap();
+        if let Some(cgroup) = &cgroup {
+            assert!(cgroup.usage > 0);
+        }
+        assert_eq!(
+            is_memory_constrained(),
+            cgroup.as_ref().map_or(false, |c| c.limit.is_some())
+        );
+        match get_cgroup_kernel_memory() {
+            Ok(_) => assert!(cgroup.is_some()),
+            Err(e) => assert_eq!(e.kind(), ErrorKind::NotFound),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Human readable memory sizes.
+use super::ProcessMemoryInfo;
+
+const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const SI_UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+/// The unit system of the formatted sizes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteUnits {
+    /// powers of 1024: KiB, MiB, GiB ...
+    #[default]
+    Binary,
+    /// powers of 1000: kB, MB, GB ...
+    Si,
+}
+
+/// A byte count, displayed by [`format_bytes`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        ByteSize(bytes)
+    }
+}
+
+impl std::fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format_bytes(self.0))
+    }
+}
+
+/// Format a byte count with binary units and one decimal, e.g. `124.3 MiB`.
+///
+/// Counts below 1KiB are printed as is, e.g. `512 B`.
+pub fn format_bytes(n: u64) -> String {
+    format_bytes_prec(n, 1)
+}
+
+/// Same as [`format_bytes`] with `decimals` decimals, e.g. `124.34 MiB` with 2.
+pub fn format_bytes_prec(n: u64, decimals: usize) -> String {
+    format_bytes_with(n, decimals, ByteUnits::Binary)
+}
+
+/// Format a byte count in `units` with `decimals` decimals, e.g. `130.4 MB` in `ByteUnits::Si`.
+///
+/// Counts below the first multiple are printed as is, e.g. `512 B`.
+pub fn format_bytes_with(n: u64, decimals: usize, units: ByteUnits) -> String {
+    let (base, names) = match units {
+        ByteUnits::Binary => (1024f64, &BINARY_UNITS),
+        ByteUnits::Si => (1000f64, &SI_UNITS),
+    };
+    if (n as f64) < base {
+        return format!("{} B", n);
+    }
+    let mut value = n as f64;
+    let mut unit = 0;
+    while value >= base && unit < names.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    format!("{:.*} {}", decimals, value, names[unit])
+}
+
+/// Serializes the wrapped `ProcessMemoryInfo` with every field formatted by [`format_bytes`],
+/// for logs read by people. `ProcessMemoryInfo` itself serializes raw byte counts.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::{get_process_memory_info, Human};
+/// let info = get_process_memory_info().unwrap();
+/// let for_machines = serde_json::to_string(&info).unwrap();
+/// let for_humans = serde_json::to_string(&Human(info)).unwrap();
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Clone, Default)]
+pub struct Human(pub ProcessMemoryInfo);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Human {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let fields = self.0.fields();
+        let mut state = serializer.serialize_struct("ProcessMemoryInfo", fields.len())?;
+        for (name, value) in fields {
+            state.serialize_field(name, &format_bytes(value))?;
+        }
+        state.end()
+    }
+}
+
+fn format_delta(before: u64, after: u64) -> (String, String) {
+    let delta = if after >= before {
+        format!("+{}", format_bytes(after - before))
+    } else {
+        format!("-{}", format_bytes(before - after))
+    };
+    let percent = if before == 0 {
+        "n/a".to_string()
+    } else {
+        format!(
+            "{:+.1}%",
+            (after as f64 - before as f64) / before as f64 * 100f64
+        )
+    };
+    (delta, percent)
+}
+
+/// Format a table of the changes between two memory infos, for reports read by people.
+///
+/// There is a row per field present on the platform, with the sizes formatted by
+/// [`format_bytes`] and signed deltas:
+/// ```text
+/// field                 before     after      delta  percent
+/// resident_set_size   10.0 MiB  12.5 MiB  +2.5 MiB   +25.0%
+/// virtual_memory_size  1.0 GiB   1.0 GiB      +0 B    +0.0%
+/// ```
+/// The percent is `n/a` when the field was 0 before.
+pub fn format_diff(before: &ProcessMemoryInfo, after: &ProcessMemoryInfo) -> String {
+    let mut rows = vec![[
+        "field".to_string(),
+        "before".to_string(),
+        "after".to_string(),
+        "delta".to_string(),
+        "percent".to_string(),
+    ]];
+    for ((name, before), (_, after)) in before.fields().into_iter().zip(after.fields()) {
+        let (delta, percent) = format_delta(before, after);
+        rows.push([
+            name.to_string(),
+            format_bytes(before),
+            format_bytes(after),
+            delta,
+            percent,
+        ]);
+    }
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let mut line = format!("{:<width$}", row[0], width = widths[0]);
+        for (cell, width) in row.iter().zip(widths).skip(1) {
+            line += &format!("  {:>width$}", cell, width = width);
+        }
+        table += line.trim_end();
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(124 * 1024 * 1024), "124.0 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(format_bytes(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn test_byte_size() {
+        assert_eq!(ByteSize(1536).to_string(), "1.5 KiB");
+        assert_eq!(ByteSize::from(42).bytes(), 42);
+    }
+
+    #[test]
+    fn test_format_bytes_with() {
+        let n = 124 * 1024 * 1024 + 350 * 1024;
+        assert_eq!(format_bytes_prec(n, 2), "124.34 MiB");
+        assert_eq!(format_bytes_prec(n, 0), "124 MiB");
+        assert_eq!(format_bytes_prec(n, 1), format_bytes(n));
+        assert_eq!(format_bytes_prec(1023, 3), "1023 B");
+
+        assert_eq!(format_bytes_with(999, 1, ByteUnits::Si), "999 B");
+        assert_eq!(format_bytes_with(1000, 1, ByteUnits::Si), "1.0 kB");
+        assert_eq!(format_bytes_with(n, 2, ByteUnits::Si), "130.38 MB");
+        assert_eq!(format_bytes_with(3_000_000_000, 0, ByteUnits::Si), "3 GB");
+        assert_eq!(format_bytes_with(u64::MAX, 1, ByteUnits::Si), "18.4 EB");
+        assert_eq!(format_bytes_with(n, 2, ByteUnits::Binary), "124.34 MiB");
+    }
+
+    #[test]
+    fn test_format_diff() {
+        let before = ProcessMemoryInfo {
+            resident_set_size: 10 * 1024 * 1024,
+            virtual_memory_size: 1024 * 1024 * 1024,
+            ..Default::default()
+        };
+        let after = ProcessMemoryInfo {
+            resident_set_size: 12 * 1024 * 1024 + 512 * 1024,
+            virtual_memory_size: 1024 * 1024 * 1024 - 1024 * 1024,
+            ..Default::default()
+        };
+        let diff = format_diff(&before, &after);
+        let lines: Vec<&str> = diff.lines().collect();
+        assert_eq!(lines.len(), before.fields().len() + 1);
+        assert!(lines[0].starts_with("field "));
+        assert!(lines[0].ends_with("percent"));
+
+        let rss = lines
+            .iter()
+            .find(|line| line.starts_with("resident_set_size "))
+            .unwrap();
+        assert_eq!(
+            rss.split_whitespace().collect::<Vec<_>>(),
+            [
+                "resident_set_size",
+                "10.0",
+                "MiB",
+                "12.5",
+                "MiB",
+                "+2.5",
+                "MiB",
+                "+25.0%"
+            ]
+        );
+        let vsz = lines
+            .iter()
+            .find(|line| line.starts_with("virtual_memory_size "))
+            .unwrap();
+        assert!(vsz.contains("-1.0 MiB"));
+        assert!(vsz.ends_with("-0.1%"));
+
+        // columns are aligned.
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert!(format_diff(&ProcessMemoryInfo::default(), &after).contains("n/a"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let info = ProcessMemoryInfo {
+            resident_set_size: 124 * 1024 * 1024,
+            virtual_memory_size: 2048,
+            ..Default::default()
+        };
+
+        let machine = serde_json::to_value(&info).unwrap();
+        assert_eq!(machine["resident_set_size"], 124 * 1024 * 1024);
+        assert_eq!(machine["virtual_memory_size"], 2048);
+
+        let human = serde_json::to_value(Human(info)).unwrap();
+        assert_eq!(human["resident_set_size"], "124.0 MiB");
+        assert_eq!(human["virtual_memory_size"], "2.0 KiB");
+        assert_eq!(
+            human.as_object().unwrap().len(),
+            machine.as_object().unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use super::ProcessMemoryInfo;
+
+/// The ratio of the RSS of `info` to `allocated_bytes`, the bytes in use by the program as
+/// counted by `CountingAllocator::get_allocated` or the allocator's own statistics.
+///
+/// - above 1, the allocator keeps more resident memory than what is allocated: freed memory
+///   retained in its caches and arenas, or fragmentation. The RSS also includes the stacks,
+///   the code and the memory allocated outside of the allocator: a process with few
+///   allocations sits well above 1.
+/// - below 1, allocated memory is not resident: swapped out, or allocated but never written
+///   so never faulted in.
+///
+/// It is `f64::INFINITY` when `allocated_bytes` is 0, NaN when both are 0.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::{fragmentation_ratio, get_process_memory_info, CountingAllocator};
+/// let info = get_process_memory_info().unwrap();
+/// let ratio = fragmentation_ratio(CountingAllocator::get_allocated().max(0) as u64, &info);
+/// ```
+pub fn fragmentation_ratio(allocated_bytes: u64, info: &ProcessMemoryInfo) -> f64 {
+    info.resident_set_size as f64 / allocated_bytes as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fragmentation_ratio() {
+        let info = ProcessMemoryInfo {
+            resident_set_size: 300 << 20,
+            ..Default::default()
+        };
+        assert_eq!(fragmentation_ratio(200 << 20, &info), 1.5);
+        assert_eq!(fragmentation_ratio(600 << 20, &info), 0.5);
+        assert_eq!(fragmentation_ratio(0, &info), f64::INFINITY);
+        assert!(fragmentation_ratio(0, &ProcessMemoryInfo::default()).is_nan());
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::ProcessMemoryInfo;
+use crate::clock::{Clock, SystemClock};
+
+/// The RSS growth rate over a sliding time window, a sustained positive slope hints at a leak.
+///
+/// ```
+/// # use std::time::{Duration, Instant};
+/// # use workflow_perf_monitor::mem::{get_process_memory_info, GrowthRate};
+/// let mut rate = GrowthRate::new(Duration::from_secs(60));
+/// rate.observe(Instant::now(), &get_process_memory_info().unwrap());
+/// println!("{:.0} bytes/sec", rate.bytes_per_sec());
+/// ```
+pub struct GrowthRate<C = SystemClock> {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+    clock: C,
+}
+
+impl GrowthRate {
+    /// Samples older than `window` relative to the latest one are dropped.
+    pub fn new(window: Duration) -> Self {
+        Self::with_clock(window, SystemClock)
+    }
+}
+
+impl<C: Clock> GrowthRate<C> {
+    /// As `new`, with `observe_now` reading the time from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(window: Duration, clock: C) -> Self {
+        GrowthRate {
+            window,
+            samples: VecDeque::new(),
+            clock,
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Add a sample taken now, according to the clock.
+    pub fn observe_now(&mut self, info: &ProcessMemoryInfo) {
+        let now = self.clock.now();
+        self.observe(now, info);
+    }
+
+    /// Add the RSS of `info` at `now` to the window, dropping the samples which fell out of it.
+    ///
+    /// `now` is expected to grow from a call to the next, the window ends at the latest one.
+    pub fn observe(&mut self, now: Instant, info: &ProcessMemoryInfo) {
+        self.samples.push_back((now, info.resident_set_size));
+        while let Some(&(oldest, _)) = self.samples.front() {
+            if now.saturating_duration_since(oldest) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// The least squares slope of RSS over time in the window.
+    ///
+    /// It is 0 until there are two samples at different times.
+    pub fn bytes_per_sec(&self) -> f64 {
+        let Some(&(origin, _)) = self.samples.front() else {
+            return 0.0;
+        };
+        let n = self.samples.len() as f64;
+        let points = || {
+            self.samples
+                .iter()
+                .map(move |&(t, rss)| (t.duration_since(origin).as_secs_f64(), rss as f64))
+        };
+        let mean_t = points().map(|(t, _)| t).sum::<f64>() / n;
+        let mean_rss = points().map(|(_, rss)| rss).sum::<f64>() / n;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (t, rss) in points() {
+            cov += (t - mean_t) * (rss - mean_rss);
+            var += (t - mean_t) * (t - mean_t);
+        }
+        if var == 0.0 {
+            return 0.0;
+        }
+        cov / var
+    }
+}
+
+/// The shape of a series of memory samples, see `classify_trend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trend {
+    /// no significant change.
+    Stable,
+    /// a sustained growth, the troughs included when it saws: a likely leak.
+    Growing,
+    /// repeated growths and sharp drops around a steady level, as a garbage collector or
+    /// a cache does: not a leak.
+    Sawtooth,
+    /// a sustained decrease.
+    Shrinking,
+}
+
+/// Below this peak-to-trough range, relative to the mean RSS, the series is `Stable`.
+const STABLE_RANGE: f64 = 0.05;
+/// A drift over the series beyond this, relative to the mean RSS, is a trend.
+const TREND: f64 = 0.1;
+/// A decrease between consecutive samples of at least this, relative to the peak-to-trough
+/// range, is a drop of a sawtooth.
+const DROP: f64 = 0.5;
+
+/// Classify the RSS of `samples`, taken at a regular interval in chronological order.
+///
+/// The heuristics, with the thresholds relative to the mean RSS of the series:
+/// 1. a peak-to-trough range under 5% is `Stable`, as are fewer than 3 samples.
+/// 2. at least two drops between consecutive samples, each of at least half of the range, is a
+///    `Sawtooth`. Unless the sample after the last drop is 10% above the one after the first
+///    drop: the memory the drops leave is growing, that's `Growing`.
+/// 3. otherwise the drift of the least squares line over the series, above 10% is `Growing`,
+///    below -10% is `Shrinking`, in between is `Stable`.
+pub fn classify_trend(samples: &[ProcessMemoryInfo]) -> Trend {
+    let rss: Vec<f64> = samples
+        .iter()
+        .map(|info| info.resident_set_size as f64)
+        .collect();
+    if rss.len() < 3 {
+        return Trend::Stable;
+    }
+    let n = rss.len() as f64;
+    let mean = rss.iter().sum::<f64>() / n;
+    let (min, max) = rss.iter().fold((f64::MAX, f64::MIN), |(min, max), &rss| {
+        (min.min(rss), max.max(rss))
+    });
+    let range = max - min;
+    if mean == 0.0 || range < mean * STABLE_RANGE {
+        return Trend::Stable;
+    }
+
+    let troughs: Vec<f64> = rss
+        .windows(2)
+        .filter(|pair| pair[0] - pair[1] >= range * DROP)
+        .map(|pair| pair[1])
+        .collect();
+    if troughs.len() >= 2 {
+        return if troughs[troughs.len() - 1] - troughs[0] > mean * TREND {
+            Trend::Growing
+        } else {
+            Trend::Sawtooth
+        };
+    }
+
+    let mean_i = (n - 1.0) / 2.0;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (i, rss) in rss.iter().enumerate() {
+        cov += (i as f64 - mean_i) * (rss - mean);
+        var += (i as f64 - mean_i) * (i as f64 - mean_i);
+    }
+    let drift = cov / var * (n - 1.0);
+    if drift > mean * TREND {
+        Trend::Growing
+    } else if drift < -mean * TREND {
+        Trend::Shrinking
+    } else {
+        Trend::Stable
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(rss: u64) -> ProcessMemoryInfo {
+        ProcessMemoryInfo {
+            resident_set_size: rss,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_linear_growth() {
+        let start = Instant::now();
+        let mut rate = GrowthRate::new(Duration::from_secs(10));
+        assert_eq!(rate.bytes_per_sec(), 0.0);
+        rate.observe(start, &info(1 << 20));
+        assert_eq!(rate.bytes_per_sec(), 0.0);
+
+        // 4KiB/s with some noise.
+        for i in 1..100u64 {
+            let noise = if i % 2 == 0 { 100 } else { 0 };
+            let now = start + Duration::from_millis(i * 250);
+            rate.observe(now, &info((1 << 20) + i * 1024 + noise));
+        }
+        assert!((rate.bytes_per_sec() - 4096.0).abs() < 50.0);
+        // only the last 10s are kept.
+        assert_eq!(rate.samples.len(), 41);
+
+        // memory freed afterward shows up as a negative slope once the growth left the window.
+        let now = start + Duration::from_secs(30);
+        for i in 0..40u64 {
+            rate.observe(
+                now + Duration::from_millis(i * 250),
+                &info((1 << 20) - i * 512),
+            );
+        }
+        assert!((rate.bytes_per_sec() + 2048.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_mock_clock_window() {
+        let clock = crate::clock::MockClock::new();
+        let mut rate = GrowthRate::with_clock(Duration::from_secs(10), clock.clone());
+        // 1MiB/s for 10s.
+        for i in 0..=10u64 {
+            rate.observe_now(&info(i << 20));
+            clock.advance(Duration::from_secs(1));
+        }
+        assert!((rate.bytes_per_sec() - (1 << 20) as f64).abs() < 1.0);
+
+        // flat for 9s more: the last sample of the growth is still in the window.
+        for _ in 0..9 {
+            rate.observe_now(&info(10 << 20));
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(rate.samples.len(), 11);
+        assert!(rate.bytes_per_sec() > 0.0);
+        // one more second and it left.
+        rate.observe_now(&info(10 << 20));
+        assert_eq!(rate.samples.len(), 11);
+        assert_eq!(rate.bytes_per_sec(), 0.0);
+    }
+
+    fn series(rss: impl IntoIterator<Item = u64>) -> Vec<ProcessMemoryInfo> {
+        rss.into_iter().map(info).collect()
+    }
+
+    #[test]
+    fn test_classify_trend() {
+        const MIB: u64 = 1 << 20;
+        // a few KiB of noise around 100MiB.
+        let stable = series((0..100).map(|i| 100 * MIB + (i % 3) * 4096));
+        assert_eq!(classify_trend(&stable), Trend::Stable);
+        assert_eq!(classify_trend(&stable[..2]), Trend::Stable);
+        assert_eq!(classify_trend(&[]), Trend::Stable);
+
+        let growing = series((0..100).map(|i| 100 * MIB + i * MIB / 2 + (i % 2) * MIB));
+        assert_eq!(classify_trend(&growing), Trend::Growing);
+
+        let shrinking = series((0..100).map(|i| 200 * MIB - i * MIB / 2));
+        assert_eq!(classify_trend(&shrinking), Trend::Shrinking);
+
+        // 10 samples growing by 5MiB then collected back to 100MiB, 5 times.
+        let sawtooth = series((0..50).map(|i| 100 * MIB + (i % 10) * 5 * MIB));
+        assert_eq!(classify_trend(&sawtooth), Trend::Sawtooth);
+
+        // the same, but each collection leaves 5MiB more behind.
+        let leaking = series((0..50).map(|i| 100 * MIB + (i / 10) * 5 * MIB + (i % 10) * 5 * MIB));
+        assert_eq!(classify_trend(&leaking), Trend::Growing);
+    }
+}
@@ -0,0 +1,151 @@
+//! How much more current process can allocate, under every limit at once.
+use std::io::Result;
+
+/// The limits `available_headroom` folds in, `None` when absent on the platform or unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Sources {
+    /// the memory available on the system.
+    system_available: Option<u64>,
+    /// the soft `RLIMIT_AS` and the virtual memory size of the process.
+    address_space: Option<(u64, u64)>,
+    /// the limit and the usage of the memory cgroup.
+    cgroup: Option<(u64, u64)>,
+}
+
+/// The most restrictive of the present constraints, `None` without any.
+fn headroom(sources: Sources) -> Option<u64> {
+    let address_space = sources
+        .address_space
+        .map(|(limit, vsz)| limit.saturating_sub(vsz));
+    let cgroup = sources
+        .cgroup
+        .map(|(limit, usage)| limit.saturating_sub(usage));
+    [sources.system_available, address_space, cgroup]
+        .iter()
+        .flatten()
+        .copied()
+        .min()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn sources() -> Result<Sources> {
+    let cgroup = super::get_cgroup_memory()?
+        .and_then(|cgroup| cgroup.limit.map(|limit| (limit, cgroup.usage)));
+    Ok(Sources {
+        system_available: Some(super::get_system_memory_info()?.available),
+        address_space: address_space()?,
+        cgroup,
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn sources() -> Result<Sources> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(Sources {
+        system_available: Some(status.ullAvailPhys),
+        ..Default::default()
+    })
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn sources() -> Result<Sources> {
+    fn sysctl<T: Default>(name: &[u8]) -> Result<T> {
+        let mut value = T::default();
+        let mut size = std::mem::size_of::<T>();
+        let ret = unsafe {
+            libc::sysctlbyname(
+                name.as_ptr() as *const libc::c_char,
+                &mut value as *mut T as *mut libc::c_void,
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(value)
+    }
+
+    // the percentage of the memory available, the level the memorystatus of the kernel acts on.
+    let level: libc::c_int = sysctl(b"kern.memorystatus_level\0")?;
+    let total: u64 = sysctl(b"hw.memsize\0")?;
+    Ok(Sources {
+        system_available: Some(total / 100 * level.clamp(0, 100) as u64),
+        address_space: address_space()?,
+        cgroup: None,
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn address_space() -> Result<Option<(u64, u64)>> {
+    use crate::rlimit::{get_rlimit, Resource};
+
+    let Some(limit) = get_rlimit(Resource::AddressSpace)?.soft else {
+        return Ok(None);
+    };
+    let vsz = super::get_process_memory_info()?.virtual_memory_size;
+    Ok(Some((limit, vsz)))
+}
+
+/// How many more bytes current process can allocate before hitting a limit, the most
+/// restrictive of:
+///
+/// - the memory available on the system: `MemAvailable` of `/proc/meminfo` on Linux, the
+///   available physical memory of `GlobalMemoryStatusEx` on Windows, `kern.memorystatus_level`
+///   percent of `hw.memsize` on MacOS and iOS.
+/// - the soft `RLIMIT_AS` minus the virtual memory size, when the limit is set, not on
+///   Windows. Reserving address space counts against it, touched or not.
+/// - the limit of the memory cgroup minus its usage on Linux, when it is limited. The usage
+///   includes the page cache of the cgroup, which the kernel reclaims before failing an
+///   allocation: this one tends to underestimate.
+///
+/// Swap is not counted, nor the limits of a Windows job object. An allocation within the
+/// headroom can still fail, e.g. on `vm.max_map_count`, or be served at the cost of reclaim.
+/// It is `u64::MAX` when no constraint applies.
+pub fn available_headroom() -> Result<u64> {
+    Ok(headroom(sources()?).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    #[test]
+    fn test_headroom() {
+        let sources = Sources {
+            system_available: Some(8 * GIB),
+            address_space: Some((16 * GIB, GIB)),
+            cgroup: Some((GIB, 900 << 20)),
+        };
+        // the cgroup binds.
+        assert_eq!(headroom(sources), Some(124 << 20));
+
+        let unlimited_cgroup = Sources {
+            cgroup: None,
+            ..sources
+        };
+        assert_eq!(headroom(unlimited_cgroup), Some(8 * GIB));
+
+        let tight_address_space = Sources {
+            address_space: Some((2 * GIB, 3 * GIB)),
+            ..unlimited_cgroup
+        };
+        assert_eq!(headroom(tight_address_space), Some(0));
+
+        assert_eq!(headroom(Sources::default()), None);
+    }
+
+    #[test]
+    fn test_available_headroom() {
+        assert!(available_headroom().unwrap() > 0);
+    }
+}
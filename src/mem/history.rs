@@ -0,0 +1,234 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::ProcessMemoryInfo;
+use crate::clock::{Clock, SystemClock};
+
+// Values below 2^SUB_BITS get a bucket each, larger ones are split in 2^SUB_BITS buckets per
+// power of two, so a bucket is at most 1/16 of its lower bound wide.
+const SUB_BITS: u32 = 4;
+const SUBS: usize = 1 << SUB_BITS;
+const BUCKETS: usize = (64 - SUB_BITS as usize + 1) * SUBS;
+
+fn bucket(value: u64) -> usize {
+    if value < SUBS as u64 {
+        return value as usize;
+    }
+    let exp = 63 - value.leading_zeros();
+    let sub = (value >> (exp - SUB_BITS)) as usize & (SUBS - 1);
+    (exp - SUB_BITS + 1) as usize * SUBS + sub
+}
+
+/// The middle of the values falling in `bucket`.
+fn bucket_value(bucket: usize) -> u64 {
+    if bucket < SUBS {
+        return bucket as u64;
+    }
+    let shift = (bucket / SUBS) as u32 - 1;
+    let lower = ((SUBS + bucket % SUBS) as u64) << shift;
+    lower + ((1u64 << shift) >> 1)
+}
+
+/// Timestamped RSS samples of the recent past, bounded both in age and in count.
+///
+/// Percentiles are read from a log-scaled histogram kept along the samples, in a time
+/// independent of the number of samples. They are approximate: the value returned is within
+/// about 3% of a sample of the requested rank, exact below 32 bytes.
+/// The memory used is the samples, at most `max_samples` of them, plus a fixed 4KiB histogram.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::{get_process_memory_info, BoundedHistory};
+/// # use std::time::Duration;
+/// let mut history = BoundedHistory::new(Duration::from_secs(600), 10_000);
+/// history.push(&get_process_memory_info().unwrap());
+/// println!("p99 {:?}", history.percentile(99.0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BoundedHistory<C = SystemClock> {
+    max_age: Duration,
+    max_samples: usize,
+    samples: VecDeque<(Instant, u64)>,
+    histogram: Box<[u32]>,
+    clock: C,
+}
+
+impl BoundedHistory {
+    /// `max_samples` is at least 1.
+    pub fn new(max_age: Duration, max_samples: usize) -> Self {
+        Self::with_clock(max_age, max_samples, SystemClock)
+    }
+}
+
+impl<C: Clock> BoundedHistory<C> {
+    /// As `new`, with `push` reading the time from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(max_age: Duration, max_samples: usize, clock: C) -> Self {
+        let max_samples = max_samples.max(1);
+        BoundedHistory {
+            max_age,
+            max_samples,
+            samples: VecDeque::new(),
+            histogram: vec![0; BUCKETS].into_boxed_slice(),
+            clock,
+        }
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    pub fn max_samples(&self) -> usize {
+        self.max_samples
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The samples kept, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = (Instant, u64)> + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// Add a sample taken now, according to the clock.
+    pub fn push(&mut self, info: &ProcessMemoryInfo) {
+        let now = self.clock.now();
+        self.push_at(now, info);
+    }
+
+    /// Keep the RSS of `info` taken at `now`, no earlier than the samples kept already.
+    ///
+    /// The samples older than `max_age` at `now` are evicted, then the oldest ones past
+    /// `max_samples`.
+    pub fn push_at(&mut self, now: Instant, info: &ProcessMemoryInfo) {
+        self.expire(now);
+        if self.samples.len() == self.max_samples {
+            self.pop_oldest();
+        }
+        let rss = info.resident_set_size;
+        self.samples.push_back((now, rss));
+        self.histogram[bucket(rss)] += 1;
+    }
+
+    /// Evict the samples older than `max_age` at `now`, for when no sample was pushed for a while.
+    pub fn expire(&mut self, now: Instant) {
+        while let Some((at, _)) = self.samples.front() {
+            if now.saturating_duration_since(*at) <= self.max_age {
+                break;
+            }
+            self.pop_oldest();
+        }
+    }
+
+    /// The approximate `p`th percentile of the RSS kept, `p` in `0.0..=100.0`, in bytes.
+    ///
+    /// It is the nearest-rank percentile, the smallest sample at least `p`% of the samples
+    /// don't exceed, up to the precision of the histogram. `None` without samples.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let count = self.samples.len();
+        let rank = ((p.clamp(0.0, 100.0) / 100.0 * count as f64).ceil() as usize).clamp(1, count);
+        let mut seen = 0;
+        for (bucket, n) in self.histogram.iter().enumerate() {
+            seen += *n as usize;
+            if seen >= rank {
+                return Some(bucket_value(bucket));
+            }
+        }
+        // the histogram counts every sample.
+        None
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some((_, rss)) = self.samples.pop_front() {
+            self.histogram[bucket(rss)] -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(rss: u64) -> ProcessMemoryInfo {
+        ProcessMemoryInfo {
+            resident_set_size: rss,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_buckets() {
+        for value in (0..100_000).chain([u64::MAX / 3, u64::MAX]) {
+            let approx = bucket_value(bucket(value));
+            assert_eq!(bucket(approx), bucket(value));
+            assert!(
+                approx.abs_diff(value) as f64 <= value as f64 / 32.0,
+                "{}",
+                value
+            );
+        }
+        assert!(bucket(u64::MAX) < BUCKETS);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let start = Instant::now();
+        let mut history = BoundedHistory::new(Duration::from_secs(60), 1000);
+        for secs in 0..100 {
+            history.push_at(start + Duration::from_secs(secs), &info(secs));
+        }
+        // samples of 39s to 99s are at most 60s old.
+        assert_eq!(history.len(), 61);
+        assert_eq!(history.samples().next().unwrap().1, 39);
+        assert_eq!(history.percentile(0.0), Some(39));
+
+        history.expire(start + Duration::from_secs(200));
+        assert!(history.is_empty());
+        assert_eq!(history.percentile(50.0), None);
+
+        let mut history = BoundedHistory::new(Duration::from_secs(60), 10);
+        for i in 0..30 {
+            history.push_at(start, &info(i));
+        }
+        assert_eq!(history.len(), 10);
+        assert_eq!(history.percentile(0.0), Some(20));
+        assert_eq!(history.percentile(100.0), Some(29));
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = crate::clock::MockClock::new();
+        let mut history = BoundedHistory::with_clock(Duration::from_secs(10), 1000, clock.clone());
+        for rss in 0..20 {
+            history.push(&info(rss));
+            clock.advance(Duration::from_secs(1));
+        }
+        // pushed at 0s to 19s, the last one at 19s keeps 9s to 19s.
+        assert_eq!(history.len(), 11);
+        assert_eq!(history.samples().next().unwrap().1, 9);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let start = Instant::now();
+        let mut history = BoundedHistory::new(Duration::from_secs(3600), 10_000);
+        // 1MiB to 1000MiB, shuffled.
+        for i in 0..1000u64 {
+            let mib = (i * 7919) % 1000 + 1;
+            history.push_at(start, &info(mib << 20));
+        }
+        for (p, expected) in [(50.0, 500u64), (95.0, 950), (99.0, 990), (100.0, 1000)] {
+            let value = history.percentile(p).unwrap() as f64;
+            let expected = (expected << 20) as f64;
+            assert!((value - expected).abs() <= expected / 32.0, "p{}", p);
+        }
+    }
+}
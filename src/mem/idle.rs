@@ -0,0 +1,176 @@
+//! Working set estimation with the idle page tracking of Linux, see
+//! <https://docs.kernel.org/admin-guide/mm/idle_page_tracking.html>.
+use super::{maps::parse_mapping, process_memory_info::page_size};
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{Error, ErrorKind, Result},
+    os::unix::fs::FileExt,
+    time::Duration,
+};
+
+const PAGE_IDLE_BITMAP: &str = "/sys/kernel/mm/page_idle/bitmap";
+/// The pagemap entries read at once.
+const CHUNK: usize = 512;
+const PM_PRESENT: u64 = 1 << 63;
+const PM_PFN_MASK: u64 = (1 << 55) - 1;
+
+/// Estimate the working set of current process: the bytes of its resident pages referenced
+/// within `idle_window`.
+///
+/// It marks every resident page of the process idle in `/sys/kernel/mm/page_idle/bitmap`,
+/// sleeps `idle_window`, then counts the pages which lost the idle flag, meaning they were
+/// accessed meanwhile. Unlike RSS it leaves out the pages resident but not used lately.
+///
+/// This is for occasional measurements only:
+/// - it needs `CAP_SYS_ADMIN`, to read the page frame numbers in `/proc/self/pagemap` and to
+///   access the bitmap, `ErrorKind::PermissionDenied` otherwise, and a kernel built with
+///   `CONFIG_IDLE_PAGE_TRACKING`, `ErrorKind::NotFound` otherwise.
+/// - it reads 8 bytes of pagemap for every page of the address space, reserved or not, and
+///   two syscalls per 64 resident pages: seconds for a large process.
+/// - the bitmap is written 64 pages at a time, with only the bits of our own pages set: the
+///   kernel ignores the bits written as 0, the pages of other processes are left alone. The
+///   pages shared with other processes are marked idle for them too.
+///
+/// The pages mapped, or moved by the kernel, during the window are not counted.
+pub fn estimate_working_set(idle_window: Duration) -> Result<u64> {
+    let bitmap = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(PAGE_IDLE_BITMAP)?;
+    let frames = resident_frames()?;
+
+    for (word, mask) in masks(&frames) {
+        bitmap.write_all_at(&mask.to_ne_bytes(), word * 8)?;
+    }
+    std::thread::sleep(idle_window);
+
+    let mut referenced = 0;
+    let mut current = None;
+    for &frame in &frames {
+        let word = frame / 64;
+        let bits = match current {
+            Some((index, bits)) if index == word => bits,
+            _ => {
+                let mut bytes = [0; 8];
+                bitmap.read_exact_at(&mut bytes, word * 8)?;
+                let bits = u64::from_ne_bytes(bytes);
+                current = Some((word, bits));
+                bits
+            }
+        };
+        if bits & (1 << (frame % 64)) == 0 {
+            referenced += 1;
+        }
+    }
+    Ok(referenced * page_size())
+}
+
+/// The page frame numbers of the resident pages of current process, sorted and deduplicated.
+fn resident_frames() -> Result<Vec<u64>> {
+    let maps = crate::procfs::read_to_string("self/maps")?;
+    let pagemap = File::open(crate::procfs::path("self/pagemap"))?;
+    let page_size = page_size();
+    let mut frames = vec![];
+    let mut present = 0u64;
+    let mut buf = vec![0u8; CHUNK * 8];
+    for mapping in maps.lines().filter_map(parse_mapping) {
+        let mut page = mapping.start / page_size;
+        let end = mapping.end / page_size;
+        while page < end {
+            let count = (end - page).min(CHUNK as u64) as usize;
+            let read = match pagemap.read_at(&mut buf[..count * 8], page * 8) {
+                Ok(read) => read,
+                // e.g. [vsyscall], out of the user address space.
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => 0,
+                Err(e) => return Err(e),
+            };
+            if read == 0 {
+                break;
+            }
+            for entry in buf[..read].chunks_exact(8) {
+                let entry = u64::from_ne_bytes(entry.try_into().unwrap());
+                if entry & PM_PRESENT != 0 {
+                    present += 1;
+                    let frame = entry & PM_PFN_MASK;
+                    if frame != 0 {
+                        frames.push(frame);
+                    }
+                }
+            }
+            page += (read / 8) as u64;
+        }
+    }
+    // without CAP_SYS_ADMIN the kernel zeroes the page frame numbers.
+    if present > 0 && frames.is_empty() {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Reading page frame numbers requires CAP_SYS_ADMIN",
+        ));
+    }
+    frames.sort_unstable();
+    frames.dedup();
+    Ok(frames)
+}
+
+/// The bitmap words covering sorted `frames`, with the bits of `frames` set.
+fn masks(frames: &[u64]) -> Vec<(u64, u64)> {
+    let mut masks: Vec<(u64, u64)> = vec![];
+    for frame in frames {
+        let (word, bit) = (frame / 64, 1 << (frame % 64));
+        match masks.last_mut() {
+            Some((last, mask)) if *last == word => *mask |= bit,
+            _ => masks.push((word, bit)),
+        }
+    }
+    masks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_masks() {
+        assert_eq!(
+            masks(&[1, 2, 63, 64, 200, 1000]),
+            [
+                (0, (1 << 1) | (1 << 2) | (1 << 63)),
+                (1, 1),
+                (3, 1 << 8),
+                (15, 1 << 40)
+            ]
+        );
+        assert!(masks(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_resident_frames() {
+        match resident_frames() {
+            Ok(frames) => {
+                assert!(!frames.is_empty());
+                assert!(frames.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+            Err(e) => assert_eq!(e.kind(), ErrorKind::PermissionDenied),
+        }
+    }
+
+    // needs root and CONFIG_IDLE_PAGE_TRACKING, skipped otherwise.
+    #[test]
+    fn test_estimate_working_set() {
+        let touched = vec![1u8; 16 << 20];
+        let working_set = match estimate_working_set(Duration::from_millis(100)) {
+            Ok(working_set) => working_set,
+            Err(e) if matches!(e.kind(), ErrorKind::PermissionDenied | ErrorKind::NotFound) => {
+                return
+            }
+            Err(e) => panic!("{}", e),
+        };
+        std::hint::black_box(&touched);
+        let rss = crate::mem::get_process_memory_info()
+            .unwrap()
+            .resident_set_size;
+        assert!(working_set > 0);
+        assert!(working_set <= rss);
+    }
+}
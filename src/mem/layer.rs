@@ -0,0 +1,172 @@
+use std::{sync::OnceLock, time::Duration};
+
+use tracing::{
+    dispatcher::WeakDispatch,
+    field::{Value, ValueSet},
+    span, Dispatch, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use super::CachedMemory;
+
+/// The name of the span field `MemoryLayer` records the RSS into.
+pub const RSS_FIELD: &str = "rss";
+
+/// The RSS when a span was last entered and exited, in bytes, stored in the span extensions
+/// by `MemoryLayer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanMemory {
+    pub enter_rss: u64,
+    pub exit_rss: Option<u64>,
+}
+
+/// A `tracing_subscriber::Layer` attaching the RSS of current process to spans when they are
+/// entered and exited.
+///
+/// The RSS is read at most once per `min_interval`, spans entered in between get the last
+/// reading, so a span heavy program doesn't make a syscall per span.
+///
+/// A span only gets fields declared when it is created, so the RSS is recorded as the `rss`
+/// field of the spans which declare it, and stored as a `SpanMemory` extension of every span
+/// for other layers:
+/// ```
+/// # use std::time::Duration;
+/// # use tracing_subscriber::prelude::*;
+/// # use workflow_perf_monitor::mem::MemoryLayer;
+/// let subscriber = tracing_subscriber::registry().with(MemoryLayer::new(Duration::from_millis(100)));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("import", rss = tracing::field::Empty);
+///     let _entered = span.enter();
+/// });
+/// ```
+pub struct MemoryLayer {
+    cache: CachedMemory,
+    dispatch: OnceLock<WeakDispatch>,
+}
+
+impl MemoryLayer {
+    pub fn new(min_interval: Duration) -> Self {
+        MemoryLayer {
+            cache: CachedMemory::new(min_interval),
+            dispatch: OnceLock::new(),
+        }
+    }
+
+    fn record_rss(&self, id: &span::Id, metadata: &'static Metadata<'static>, rss: u64) {
+        let Some(field) = metadata.fields().field(RSS_FIELD) else {
+            return;
+        };
+        let Some(dispatch) = self.dispatch.get().and_then(WeakDispatch::upgrade) else {
+            return;
+        };
+        // the same as `Span::record`, which a layer has no handle for.
+        let values = [(&field, Some(&rss as &dyn Value))];
+        let values: ValueSet<'_> = metadata.fields().value_set(&values);
+        dispatch.record(id, &span::Record::new(&values));
+    }
+}
+
+impl<S> Layer<S> for MemoryLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        // a weak reference, the dispatch owns this layer.
+        let _ = self.dispatch.set(subscriber.downgrade());
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let (Some(span), Ok(info)) = (ctx.span(id), self.cache.get()) else {
+            return;
+        };
+        let rss = info.resident_set_size;
+        {
+            let mut extensions = span.extensions_mut();
+            match extensions.get_mut::<SpanMemory>() {
+                Some(memory) => memory.enter_rss = rss,
+                None => extensions.insert(SpanMemory {
+                    enter_rss: rss,
+                    exit_rss: None,
+                }),
+            }
+        }
+        // the extensions are released, other layers may use them while recording.
+        self.record_rss(id, span.metadata(), rss);
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let (Some(span), Ok(info)) = (ctx.span(id), self.cache.get()) else {
+            return;
+        };
+        let rss = info.resident_set_size;
+        if let Some(memory) = span.extensions_mut().get_mut::<SpanMemory>() {
+            memory.exit_rss = Some(rss);
+        }
+        self.record_rss(id, span.metadata(), rss);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::prelude::*;
+
+    /// Collects the `rss` values recorded and the `SpanMemory` of closed spans.
+    #[derive(Clone, Default)]
+    struct Capture {
+        recorded: Arc<Mutex<Vec<u64>>>,
+        closed: Arc<Mutex<Vec<SpanMemory>>>,
+    }
+
+    impl Visit for Capture {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == RSS_FIELD {
+                self.recorded.lock().unwrap().push(value);
+            }
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+        fn on_record(&self, _: &span::Id, values: &span::Record<'_>, _: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+
+        fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let memory = span.extensions().get::<SpanMemory>().copied();
+            self.closed.lock().unwrap().extend(memory);
+        }
+    }
+
+    #[test]
+    fn test_memory_layer() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(MemoryLayer::new(Duration::from_secs(60)))
+            .with(capture.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let declared = tracing::info_span!("declared", rss = tracing::field::Empty);
+            declared.in_scope(|| {});
+            let undeclared = tracing::info_span!("undeclared");
+            undeclared.in_scope(|| {});
+        });
+
+        // recorded on enter and exit of the span declaring the field only.
+        let recorded = capture.recorded.lock().unwrap();
+        assert_eq!(recorded.len(), 2);
+        assert!(recorded[0] > 0);
+        // both read within the throttling interval.
+        assert_eq!(recorded[0], recorded[1]);
+
+        let closed = capture.closed.lock().unwrap();
+        assert_eq!(closed.len(), 2);
+        for memory in closed.iter() {
+            assert_eq!(memory.exit_rss, Some(memory.enter_rss));
+        }
+    }
+}
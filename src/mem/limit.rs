@@ -0,0 +1,146 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// The environment variables PaaS set to the memory limit of the instance.
+const MEMORY_LIMIT_VARS: [&str; 2] = ["MEMORY_LIMIT", "CF_INSTANCE_MEMORY"];
+
+/// Parse a memory size like `512M`, `1G` or `1.5gb`, in bytes.
+///
+/// The suffixes `K`, `M`, `G` and `T` are binary multiples as in Cloud Foundry and
+/// Kubernetes, whatever their case, they may be followed by `B` or `i`. A number without
+/// suffix is in bytes.
+pub fn parse_memory_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let digits = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, suffix) = size.split_at(digits);
+    let shift = match suffix.trim_start().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "ki" => 10,
+        "m" | "mb" | "mi" => 20,
+        "g" | "gb" | "gi" => 30,
+        "t" | "tb" | "ti" => 40,
+        _ => return None,
+    };
+    if let Ok(number) = number.parse::<u64>() {
+        return number.checked_mul(1 << shift);
+    }
+    let number: f64 = number.parse().ok()?;
+    let bytes = number * (1u64 << shift) as f64;
+    if !bytes.is_finite() || bytes < 0.0 || bytes >= u64::MAX as f64 {
+        return None;
+    }
+    Some(bytes as u64)
+}
+
+fn effective_limit(
+    cgroup: Option<u64>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<u64>> {
+    let mut limit = cgroup;
+    for var in MEMORY_LIMIT_VARS.iter() {
+        let Some(value) = env(var) else {
+            continue;
+        };
+        let value = parse_memory_size(&value).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid memory size in {}: {}", var, value),
+            )
+        })?;
+        limit = Some(limit.map_or(value, |limit| limit.min(value)));
+    }
+    Ok(limit)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn cgroup_limit() -> Result<Option<u64>> {
+    Ok(super::get_cgroup_memory()?.and_then(|cgroup| cgroup.limit))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn cgroup_limit() -> Result<Option<u64>> {
+    Ok(None)
+}
+
+/// The memory limit of current process, the lowest of the memory cgroup limit on Linux and
+/// the `MEMORY_LIMIT` and `CF_INSTANCE_MEMORY` environment variables some PaaS set instead,
+/// see [`parse_memory_size`] for their format. `None` if there is no limit.
+///
+/// An environment variable which can't be parsed is an `ErrorKind::InvalidData` error.
+pub fn get_effective_memory_limit() -> Result<Option<u64>> {
+    effective_limit(cgroup_limit()?, |var| std::env::var(var).ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(parse_memory_size("512M"), Some(512 << 20));
+        assert_eq!(parse_memory_size("512m"), Some(512 << 20));
+        assert_eq!(parse_memory_size("1G"), Some(1 << 30));
+        assert_eq!(parse_memory_size("2gb"), Some(2 << 30));
+        assert_eq!(parse_memory_size("256Mi"), Some(256 << 20));
+        assert_eq!(parse_memory_size("1.5G"), Some(3 << 29));
+        assert_eq!(parse_memory_size("64 K"), Some(64 << 10));
+        assert_eq!(parse_memory_size("1T"), Some(1 << 40));
+        assert_eq!(parse_memory_size(" 4096\n"), Some(4096));
+        assert_eq!(parse_memory_size("100B"), Some(100));
+
+        assert_eq!(parse_memory_size(""), None);
+        assert_eq!(parse_memory_size("G"), None);
+        assert_eq!(parse_memory_size("512X"), None);
+        assert_eq!(parse_memory_size("-1G"), None);
+        assert_eq!(parse_memory_size("99999999999T"), None);
+    }
+
+    #[test]
+    fn test_effective_limit() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |var: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(effective_limit(None, env(&[])).unwrap(), None);
+        assert_eq!(
+            effective_limit(Some(1 << 30), env(&[])).unwrap(),
+            Some(1 << 30)
+        );
+        assert_eq!(
+            effective_limit(None, env(&[("MEMORY_LIMIT", "512M")])).unwrap(),
+            Some(512 << 20)
+        );
+        // the most restrictive wins, wherever it comes from.
+        assert_eq!(
+            effective_limit(Some(1 << 30), env(&[("MEMORY_LIMIT", "2G")])).unwrap(),
+            Some(1 << 30)
+        );
+        assert_eq!(
+            effective_limit(
+                Some(1 << 30),
+                env(&[("MEMORY_LIMIT", "768M"), ("CF_INSTANCE_MEMORY", "512M")])
+            )
+            .unwrap(),
+            Some(512 << 20)
+        );
+        let err = effective_limit(Some(1 << 30), env(&[("MEMORY_LIMIT", "lots")])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_get_effective_memory_limit() {
+        if MEMORY_LIMIT_VARS
+            .iter()
+            .all(|var| std::env::var(var).is_err())
+        {
+            assert_eq!(
+                get_effective_memory_limit().unwrap(),
+                cgroup_limit().unwrap()
+            );
+        }
+    }
+}
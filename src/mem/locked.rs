@@ -0,0 +1,42 @@
+/// Get the memory locked in RAM by current process, like `mlock` does, in **bytes**.
+///
+/// It is the `VmLck` field of `/proc/self/status`, which the kernel reports in KiB.
+/// Compare it with the `RLIMIT_MEMLOCK` soft limit to get the headroom:
+/// ```
+/// # use workflow_perf_monitor::mem::locked_memory;
+/// # use workflow_perf_monitor::rlimit::{get_rlimit, Resource};
+/// let locked = locked_memory().unwrap();
+/// match get_rlimit(Resource::MemLock).unwrap().soft {
+///     Some(limit) => println!("{} more bytes may be locked", limit.saturating_sub(locked)),
+///     None => println!("no limit on locked memory"),
+/// }
+/// ```
+/// Privileged processes (`CAP_IPC_LOCK`) are not bound by the limit.
+pub fn locked_memory() -> std::io::Result<u64> {
+    let status: super::ProcStatus = crate::procfs::read_to_string("self/status")?.parse()?;
+    Ok(status.vm_lck.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_locked_memory() {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let layout = std::alloc::Layout::from_size_align(4 * page_size, page_size).unwrap();
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        assert!(!ptr.is_null());
+
+        let before = locked_memory().unwrap();
+        if unsafe { libc::mlock(ptr as *const _, layout.size()) } != 0 {
+            // RLIMIT_MEMLOCK too low without CAP_IPC_LOCK.
+            eprintln!("mlock failed, skipped: {}", std::io::Error::last_os_error());
+        } else {
+            let after = locked_memory().unwrap();
+            unsafe { libc::munlock(ptr as *const _, layout.size()) };
+            assert!(after >= before + layout.size() as u64);
+        }
+        unsafe { std::alloc::dealloc(ptr, layout) };
+    }
+}
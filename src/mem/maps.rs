@@ -0,0 +1,134 @@
+//! Parsing of `/proc/[pid]/maps`, which unlike smaps is cheap to read: the kernel lists the
+//! mappings without walking their pages.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::io::{Error, ErrorKind, Result};
+
+/// A mapping of the address space, `start..end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingInfo {
+    pub start: u64,
+    pub end: u64,
+    /// `end - start` in bytes.
+    pub size: u64,
+    /// the pathname, `[heap]` like pseudo-paths included, `None` for anonymous mappings.
+    pub path: Option<String>,
+}
+
+pub(super) fn parse_mapping(line: &str) -> Option<MappingInfo> {
+    // address perms offset dev inode pathname, the pathname is padded with spaces.
+    let mut parts = line.splitn(6, ' ');
+    let (start, end) = parts.next()?.split_once('-')?;
+    let start = u64::from_str_radix(start, 16).ok()?;
+    let end = u64::from_str_radix(end, 16).ok()?;
+    let path = parts
+        .nth(4)
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(String::from);
+    Some(MappingInfo {
+        start,
+        end,
+        size: end.saturating_sub(start),
+        path,
+    })
+}
+
+/// The largest mapping in the content of maps, the first one on ties, `None` if there is none.
+pub fn parse_largest_mapping(maps: &str) -> Option<MappingInfo> {
+    maps.lines()
+        .filter_map(parse_mapping)
+        .fold(
+            None,
+            |largest: Option<MappingInfo>, mapping| match largest {
+                Some(largest) if largest.size >= mapping.size => Some(largest),
+                _ => Some(mapping),
+            },
+        )
+}
+
+/// The largest mapping of current process, by address space size, resident or not.
+///
+/// A guard region reserved without access, like the ones in front of thread stacks or
+/// the huge reservation of some allocators, counts as well.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn largest_mapping() -> Result<MappingInfo> {
+    parse_largest_mapping(&crate::procfs::read_to_string("self/maps")?)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "No mapping in /proc/self/maps"))
+}
+
+/// The number of mappings (VMAs) of current process, the lines of `/proc/self/maps`.
+///
+/// `mmap`, and `malloc` through it, fails with `ENOMEM` once the count reaches
+/// `get_max_map_count`, however much memory is free: compare the two for the headroom.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_mapping_count() -> Result<usize> {
+    let maps = crate::procfs::read("self/maps")?;
+    Ok(maps.iter().filter(|b| **b == b'\n').count())
+}
+
+/// The most mappings a process may have, `vm.max_map_count` from
+/// `/proc/sys/vm/max_map_count`, 65530 by default.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_max_map_count() -> Result<usize> {
+    let max = crate::procfs::read_to_string("sys/vm/max_map_count")?;
+    max.trim().parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid vm.max_map_count {:?}", max),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAPS: &str = "\
+55d4c2a00000-55d4c2a22000 r--p 00000000 08:01 1048602                    /usr/bin/app
+55d4c4000000-55d4c4021000 rw-p 00000000 00:00 0                          [heap]
+7f0e10000000-7f0e50000000 r--s 00000000 08:01 2097153                    /data/big file.bin
+7f0e60000000-7f0e64000000 rw-p 00000000 00:00 0 
+7f0e64000000-7f0e68000000 ---p 00000000 00:00 0 
+7ffd5c3a0000-7ffd5c3c1000 rw-p 00000000 00:00 0                          [stack]
+";
+
+    #[test]
+    fn test_parse_largest_mapping() {
+        assert_eq!(
+            parse_largest_mapping(MAPS),
+            Some(MappingInfo {
+                start: 0x7f0e10000000,
+                end: 0x7f0e50000000,
+                size: 1 << 30,
+                path: Some("/data/big file.bin".to_string()),
+            })
+        );
+
+        // anonymous, and the first of two of the same size.
+        let maps: Vec<_> = MAPS.lines().filter(|l| !l.contains("big file")).collect();
+        let anonymous = parse_largest_mapping(&maps.join("\n")).unwrap();
+        assert_eq!(anonymous.start, 0x7f0e60000000);
+        assert_eq!(anonymous.size, 64 << 20);
+        assert_eq!(anonymous.path, None);
+
+        assert_eq!(parse_largest_mapping(""), None);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_largest_mapping() {
+        let data = std::hint::black_box(vec![0u8; 256 << 20]);
+        let largest = largest_mapping().unwrap();
+        assert!(largest.size >= data.len() as u64);
+        assert_eq!(largest.size, largest.end - largest.start);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_mapping_count() {
+        let count = get_mapping_count().unwrap();
+        let max = get_max_map_count().unwrap();
+        assert!(count > 0);
+        assert!(count < max, "{} mappings, at most {}", count, max);
+    }
+}
@@ -0,0 +1,134 @@
+use super::{get_process_memory_info, ProcessMemoryInfo};
+use std::{
+    io::Result,
+    time::{Duration, Instant},
+};
+
+/// The relative RSS change `measure_after_settle` still considers stable, 1%.
+const SETTLE_EPSILON: f64 = 0.01;
+
+fn resident_set_size() -> Option<i64> {
+    get_process_memory_info()
+        .ok()
+        .map(|info| info.resident_set_size as i64)
+}
+
+/// Run `f` and return its result along with the RSS delta it caused, in bytes.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::measure_memory;
+/// let (buf, delta) = measure_memory(|| vec![1u8; 16 << 20]);
+/// println!("allocating {} bytes grew RSS by {} bytes", buf.len(), delta);
+/// ```
+///
+/// The delta is approximate:
+/// - it is the RSS of the whole process, other threads allocating or freeing meanwhile count too.
+/// - allocators keep freed memory around: memory released by `f` may not lower RSS, and memory
+///   `f` allocates may reuse pages that were already resident and not raise it. On Linux,
+///   calling `release_free_memory` before measuring makes the numbers more stable with glibc.
+/// - the granularity is a page, see `memory_granularity`.
+///
+/// The delta is 0 if the RSS can't be read.
+pub fn measure_memory<T>(f: impl FnOnce() -> T) -> (T, i64) {
+    let before = resident_set_size();
+    let ret = f();
+    let after = resident_set_size();
+    let delta = match (before, after) {
+        (Some(before), Some(after)) => after - before,
+        _ => 0,
+    };
+    (ret, delta)
+}
+
+/// Poll the memory info every `poll` until the RSS stays within 1% for `stable_for`, and
+/// return the last sample, to measure after a workload without catching it in the middle of
+/// freeing or faulting pages in.
+///
+/// The RSS is stable while it stays within 1% of the first sample of the stable period, a
+/// slow drift restarts the period rather than adding up. If it doesn't settle within
+/// `max_wait`, the last sample is returned anyway: compare the time spent with `max_wait` to
+/// tell. Only read errors fail.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use workflow_perf_monitor::mem::measure_after_settle;
+/// let info = measure_after_settle(
+///     Duration::from_secs(5),
+///     Duration::from_millis(50),
+///     Duration::from_millis(10),
+/// )
+/// .unwrap();
+/// println!("settled at {} bytes", info.resident_set_size);
+/// ```
+pub fn measure_after_settle(
+    max_wait: Duration,
+    stable_for: Duration,
+    poll: Duration,
+) -> Result<ProcessMemoryInfo> {
+    let start = Instant::now();
+    let mut anchor = get_process_memory_info()?.resident_set_size;
+    let mut stable_since = start;
+    loop {
+        std::thread::sleep(poll);
+        let info = get_process_memory_info()?;
+        let now = Instant::now();
+        let rss = info.resident_set_size;
+        if rss.abs_diff(anchor) as f64 > anchor as f64 * SETTLE_EPSILON {
+            anchor = rss;
+            stable_since = now;
+        } else if now.duration_since(stable_since) >= stable_for {
+            return Ok(info);
+        }
+        if now.duration_since(start) >= max_wait {
+            return Ok(info);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_measure_memory() {
+        const SIZE: usize = 64 << 20;
+        // `vec!` with a non-zero value writes every byte, so the pages are resident.
+        let (buf, delta) = measure_memory(|| vec![1u8; SIZE]);
+        assert_eq!(buf.len(), SIZE);
+        assert!(delta > (SIZE / 2) as i64, "delta: {}", delta);
+    }
+
+    #[test]
+    fn test_measure_after_settle() {
+        const CHUNK: usize = 8 << 20;
+        const CHUNKS: usize = 8;
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let allocator = std::thread::spawn(move || {
+            let mut chunks = vec![];
+            for _ in 0..CHUNKS {
+                std::thread::sleep(Duration::from_millis(20));
+                chunks.push(vec![1u8; CHUNK]);
+            }
+            done_tx.send(Instant::now()).unwrap();
+            // hold the memory until measured.
+            let _ = release_rx.recv();
+            drop(chunks);
+        });
+
+        let info = measure_after_settle(
+            Duration::from_secs(10),
+            Duration::from_millis(100),
+            Duration::from_millis(5),
+        )
+        .unwrap();
+        let settled = Instant::now();
+        release_tx.send(()).unwrap();
+        allocator.join().unwrap();
+
+        // it waited for the allocations to end, and saw all of them.
+        let done = done_rx.recv().unwrap();
+        assert!(settled >= done + Duration::from_millis(100));
+        assert!(info.resident_set_size >= (CHUNK * CHUNKS) as u64);
+    }
+}
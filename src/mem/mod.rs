@@ -1,20 +1,288 @@
 //! This sub-mod provides some facilities about memory performance profiling.
 //! # Memory usage of current process
 //! There's a platform-related function called `get_process_memory_info` available on MacOS and Windows.
+//! `get_process_memory_info_for_pid` does the same for another process.
+//! `memory_of_executable` reports it for every process running a given executable.
+//! `memory_in_namespace` reads it for a containerized process from the host on Linux, and documents how it differs from the accounting of the container.
+//! `sum_memory` sums it over a list of pids, returning the failures along with the partial sum.
+//! `sort_by_rss_desc` ranks `(pid, ProcessMemoryInfo)` pairs by RSS, for `top`-like tools.
+//! `set_test_override` (`test-util` feature) makes it return a fixed value, for deterministic tests downstream,
+//! `spawn_balloon` starts a child of a known size for the per-pid functions.
+//! `UnifiedMemoryInfo` has the same fields on every platform, the platform specific ones as `Option`.
+//! `MemoryQuery` builds a query with optional expensive fields, like the reserved address space on Windows.
+//! `CachedMemory` puts a max-staleness cache in front of it for hot paths with many readers.
+//! `MemoryMonitor` samples it on a background thread and delivers the samples through a channel,
+//! or a bounded queue with a `Backpressure` policy, and can back off to stay under a CPU budget.
+//! `MemoryMonitor::spawn_sequenced` and `sampled_memory_stream` tag the samples as `SampledMemory`, numbered and timed to detect gaps.
+//! `BucketAggregator` summarizes the samples per fixed time window with min, avg, max and p95, for backends storing pre-aggregated rows.
+//! `BoundedHistory` keeps the recent RSS samples, bounded in age and count, with approximate percentiles.
+//! `GrowthRate` computes the RSS slope over a sliding window, for leak alerting.
+//! `classify_trend` tells a growing series of samples from a stable, shrinking or sawtooth one.
+//! `memory_stream` (`tokio` feature) delivers the samples as an async `Stream` instead.
+//! `MemoryWatch` (`tokio` feature) shares the latest sample with many tasks through a `watch` channel.
+//! `RollingMemory` keeps the last samples with their average, min and max, `SyncRollingMemory` shares it between threads.
+//! `MemoryLayer` (`tracing` feature) attaches the RSS to the spans of a `tracing` subscriber.
+//! `StatsdReporter` (`statsd` feature) sends it as StatsD gauges over UDP.
+//! `SysinfoMemory` and `overlay_sysinfo` (`sysinfo-interop` feature) merge it into the process memory of `sysinfo` 0.37.
+//! `write_baseline` and `check_against_baseline` (`serde` feature) store it as JSON and report the fields regressing past a tolerance, for CI gates.
+//! `BlackBox` writes the samples to a memory-mapped ring buffer file on Unix, `read_black_box` recovers them after a crash.
+//! `format_diff` formats the changes between two samples as an aligned table.
+//! `wasm_guest_memory` (`wasmtime` feature) reports the linear memory of a Wasmtime guest.
+//! `apple::get_metal_memory_info` (`metal` feature) reports the GPU memory of the default Metal device on MacOS.
+//! `get_rusage_memory` reads the peak RSS from `getrusage` on Unix, without procfs.
+//! `reset_peak_rss` resets the peak RSS on Linux, to measure the peak of each phase of a run.
+//! `get_thread_group_memory` reads it from the task view of the calling thread on Linux, the same memory: `is_memory_per_thread` is `false`, don't sum threads.
+//! `measure_memory` returns the RSS delta caused by a closure, `measure_after_settle` waits for the RSS to stabilize before sampling.
+//! `get_oom_score` and `set_oom_score_adj` read and tune the OOM kill priority of current process on Linux.
+//! `locked_memory` reports the memory locked with `mlock` on Linux, to compare with `RLIMIT_MEMLOCK`.
+//! `StatmReader` keeps `/proc/self/statm` open and polls it without opening files, for realtime threads on Linux.
+//! `install_signal_dump` (`signal` feature) dumps it every time a signal is received, on Unix.
+//! `published_snapshot` reads the last sample from atomics, it is safe to call from a signal handler.
+//! `MemoryTimeline` records RSS over the run and exports it as CSV or JSON (`serde` feature) for plotting, or Arrow and Parquet (`arrow` feature) for analytics.
+//! # Memory usage of the system
+//! `get_system_memory_info` reads `/proc/meminfo` on Linux and Android, all values are reported in bytes.
+//! `get_cgroup_memory` reads the usage and limit of the memory cgroup of current process on Linux,
+//! `is_memory_constrained` tells whether there is a limit at all, `get_cgroup_kernel_memory` reads the kernel memory charged to it,
+//! `get_cgroup_working_set` its usage without the inactive page cache, as Kubernetes counts it.
+//! `get_effective_memory_limit` also considers the memory limits some PaaS set in the environment.
+//! `memory_pressure` classifies all of the above into a coarse `Pressure` level.
+//! `available_headroom` tells how many more bytes can be allocated under the system, `RLIMIT_AS` and cgroup limits at once.
+//! `get_vm_activity` reads the system wide paging counters on Linux, `VmActivityMonitor` turns them into rates.
+//! `SwapMonitor` detects swap thrashing from the `VmSwap` and `/proc/vmstat` swap rates on Linux.
+//! `transparent_huge_pages` reports the memory backed by transparent huge pages on Linux.
+//! `estimate_working_set` counts the pages referenced within a time window with idle page tracking on Linux, as root.
+//! `largest_mapping` finds the largest mapping of the address space in `/proc/self/maps` on Linux.
+//! `get_mapping_count` counts the mappings of current process against `get_max_map_count`, `vm.max_map_count`, on Linux.
+//! `shared_library_rss` sums the resident code and shared libraries from `/proc/self/smaps` on Linux.
+//! `get_smaps_fields` sums only the selected `SmapsField`s over the mappings on Linux.
+//! `get_shared_memory_usage` sums the resident POSIX and System V shared memory segments from `/proc/self/smaps` on Linux.
+//! `ProcStatm`, `ProcStatus` and `MemInfo` parse captured `/proc` contents on any platform, with `str::parse`,
+//! `get_proc_status` reads all of `/proc/self/status` at once on Linux, `ProcStatusView` and `read_proc_status` parse single fields on demand without allocating.
+//! `get_numa_memory` breaks the memory of current process down by NUMA node on Linux.
 //! # Memory usage of ALL Rust allocations
 //! We provide a `CountingAllocator` that wraps the system allocator but tracks the bytes used by rust allocations.
+//! `set_alloc_error_hook_snapshot` reports the last published snapshot when it fails to allocate, before the process aborts.
+//! `detected_allocator` guesses the `malloc` implementation in use, each `Allocator` documents how it holds on to freed memory.
+//! `fragmentation_ratio` compares the bytes it counts with the RSS, to monitor fragmentation.
 //! This crate DOES NOT replace the global allocator by default. You need to make it as a `global_allocator` or enable the `allocation_counter` feature.
 //! ```ignore
 //! #[global_allocator]
-//! static _COUNTER: perf_monitor::mem::CountingAllocator = perf_monitor:mem::CountingAllocator;
+//! static _COUNTER: workflow_perf_monitor::mem::CountingAllocator = workflow_perf_monitor::mem::CountingAllocator;
 //! ```
 
 mod allocation_counter;
 
 pub use allocation_counter::CountingAllocator;
 
+mod alloc_error;
+pub use alloc_error::set_alloc_error_hook_snapshot;
+
 mod process_memory_info;
-pub use process_memory_info::{get_process_memory_info, ProcessMemoryInfo};
+pub use process_memory_info::{
+    aggregate_memory_by_name, get_process_memory_info, get_process_memory_info_for_pid,
+    memory_granularity, memory_of_executable, sort_by_rss_desc, sum_memory, MemoryQuery,
+    ProcessMemoryInfo,
+};
+
+#[cfg(feature = "test-util")]
+mod test_util;
+#[cfg(feature = "test-util")]
+pub use test_util::{clear_test_override, set_test_override, spawn_balloon};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod namespace;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use namespace::memory_in_namespace;
+
+mod unified;
+pub use unified::UnifiedMemoryInfo;
+
+mod system_memory_info;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use system_memory_info::get_system_memory_info;
+pub use system_memory_info::{parse_meminfo, SystemMemoryInfo};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod cgroup;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use cgroup::{
+    get_cgroup_kernel_memory, get_cgroup_memory, get_cgroup_working_set, is_memory_constrained,
+    parse_cgroup_kernel_memory, parse_cgroup_limit, CgroupMemory,
+};
+
+mod swap;
+pub use swap::{SwapMonitor, SwapSample};
+
+mod vmstat;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use vmstat::get_vm_activity;
+pub use vmstat::{VmActivity, VmActivityMonitor, VmActivityRate};
+
+mod limit;
+pub use limit::{get_effective_memory_limit, parse_memory_size};
+
+mod headroom;
+pub use headroom::available_headroom;
+
+mod pressure;
+pub use pressure::{memory_pressure, Pressure};
+
+mod smaps;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use smaps::{
+    get_shared_memory_usage, get_smaps_fields, shared_library_rss, transparent_huge_pages,
+};
+pub use smaps::{
+    parse_anon_huge_pages, parse_shared_library_rss, parse_shared_memory_rss, parse_smaps_fields,
+    SmapsField,
+};
+
+mod maps;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use maps::{get_mapping_count, get_max_map_count, largest_mapping};
+pub use maps::{parse_largest_mapping, MappingInfo};
+
+mod procfs;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use procfs::{get_proc_status, read_proc_status};
+pub use procfs::{MemInfo, ProcStatm, ProcStatus, ProcStatusView};
+
+mod numa;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use numa::get_numa_memory;
+pub use numa::{parse_numa_maps, NumaNodeMemory};
+
+mod cached;
+pub use cached::CachedMemory;
+
+mod format;
+#[cfg(feature = "serde")]
+pub use format::Human;
+pub use format::{
+    format_bytes, format_bytes_prec, format_bytes_with, format_diff, ByteSize, ByteUnits,
+};
+
+mod rolling;
+pub use rolling::{RollingMemory, RollingStats, SyncRollingMemory};
+
+mod bucket;
+pub use bucket::{BucketAggregator, MemorySummary};
+
+mod history;
+pub use history::BoundedHistory;
+
+mod growth;
+pub use growth::{classify_trend, GrowthRate, Trend};
+
+mod allocator;
+pub use allocator::{detected_allocator, Allocator};
+
+mod fragmentation;
+pub use fragmentation::fragmentation_ratio;
+
+#[cfg(unix)]
+mod rusage;
+#[cfg(unix)]
+pub use rusage::get_rusage_memory;
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+mod peak;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+pub use peak::reset_peak_rss;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod idle;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use idle::estimate_working_set;
+
+mod thread_group;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use thread_group::get_thread_group_memory;
+pub use thread_group::is_memory_per_thread;
+
+mod measure;
+pub use measure::{measure_after_settle, measure_memory};
+
+#[cfg(target_os = "linux")]
+mod release;
+#[cfg(target_os = "linux")]
+pub use release::release_free_memory;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod locked;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use locked::locked_memory;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod statm;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use statm::StatmReader;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod oom;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use oom::{get_oom_score, get_oom_score_adj, set_oom_score_adj};
+
+mod bounded;
+pub use bounded::{Backpressure, SampleReceiver};
+
+mod black_box;
+#[cfg(unix)]
+pub use black_box::BlackBox;
+pub use black_box::{parse_black_box, read_black_box, BlackBoxRecord, BLACK_BOX_RECORD_SIZE};
+
+mod sampled;
+pub use sampled::SampledMemory;
+
+mod monitor;
+pub use monitor::{MemoryMonitor, MIN_CPU_BUDGET};
+
+#[cfg(feature = "tokio")]
+mod stream;
+#[cfg(feature = "tokio")]
+pub use stream::{memory_stream, sampled_memory_stream};
+#[cfg(feature = "tokio")]
+mod watch;
+#[cfg(feature = "tokio")]
+pub use watch::MemoryWatch;
+
+#[cfg(feature = "statsd")]
+mod statsd;
+#[cfg(feature = "statsd")]
+pub use statsd::StatsdReporter;
+
+#[cfg(feature = "sysinfo-interop")]
+mod sysinfo_interop;
+#[cfg(feature = "sysinfo-interop")]
+pub use sysinfo_interop::{overlay_sysinfo, SysinfoMemory};
+
+#[cfg(feature = "tracing")]
+mod layer;
+#[cfg(feature = "tracing")]
+pub use layer::{MemoryLayer, SpanMemory, RSS_FIELD};
+
+#[cfg(feature = "wasmtime")]
+mod wasm;
+#[cfg(feature = "wasmtime")]
+pub use wasm::wasm_guest_memory;
+
+#[cfg(all(not(target_os = "windows"), feature = "signal"))]
+mod signal;
+#[cfg(all(not(target_os = "windows"), feature = "signal"))]
+pub use signal::install_signal_dump;
+
+mod snapshot;
+pub use snapshot::{publish_snapshot, published_snapshot, PublishedSnapshot};
+
+#[cfg(feature = "serde")]
+mod baseline;
+#[cfg(feature = "serde")]
+pub use baseline::{
+    check_against_baseline, compare_to_baseline, write_baseline, Regression, RegressionReport,
+};
+
+mod timeline;
+pub use timeline::{MemoryTimeline, TimelineSample};
 
 #[cfg(target_os = "macos")]
 pub mod apple;
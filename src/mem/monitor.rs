@@ -0,0 +1,756 @@
+use super::{
+    bounded::{SampleQueue, SampleSender},
+    get_process_memory_info, publish_snapshot,
+    sampled::Sequencer,
+    Backpressure, ProcessMemoryInfo, SampleReceiver, SampledMemory,
+};
+use crate::{
+    clock::{Clock, SystemClock},
+    cpu::ThreadStat,
+};
+use std::{
+    convert::TryFrom,
+    io::Result,
+    mem,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// A background thread sampling `get_process_memory_info` every `interval`.
+///
+/// Samples are delivered through the `Receiver` returned by `spawn`, and published for
+/// `published_snapshot`. Failed reads are skipped.
+/// The thread stops when `stop` is called or the monitor is dropped, it wakes up immediately
+/// instead of finishing its sleep, so stopping takes at most one sample read.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use workflow_perf_monitor::mem::MemoryMonitor;
+/// let (monitor, samples) = MemoryMonitor::spawn(Duration::from_millis(10));
+/// let info = samples.recv().unwrap();
+/// monitor.stop().unwrap();
+/// ```
+#[must_use = "the sampling thread stops when the monitor is dropped"]
+pub struct MemoryMonitor {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+    queue: Option<Arc<SampleQueue>>,
+    budget: Option<Arc<BudgetState>>,
+    /// the buffers the sampling thread allocates upfront, see `self_overhead_bytes`.
+    buffer_bytes: u64,
+}
+
+/// The resident part of the stack of the sampling thread, a few pages of the reserved 2MiB,
+/// and its bookkeeping, an estimate.
+const THREAD_OVERHEAD_BYTES: u64 = 16 * 1024;
+
+/// The lowest CPU budget of `spawn_with_budget`, a millionth of a CPU: a sample costing 1ms
+/// waits about 17 minutes.
+pub const MIN_CPU_BUDGET: f64 = 1e-6;
+
+/// What `spawn_with_budget` measured, in nanoseconds.
+#[derive(Default)]
+struct BudgetState {
+    interval: AtomicU64,
+    cost: AtomicU64,
+}
+
+/// When `spawn_with_budget` samples next, from the CPU cost of the last samples, the waits
+/// being measured against `clock`.
+struct BudgetPacer<C> {
+    interval: Duration,
+    budget: f64,
+    // the average cost per sample of the last samples, weighted with a ratio of 1/4.
+    average: Option<Duration>,
+    // when the next sample is due, `None` when it is too far to be an `Instant`.
+    next: Option<Instant>,
+    clock: C,
+}
+
+impl<C: Clock> BudgetPacer<C> {
+    fn new(interval: Duration, budget: f64, clock: C) -> Self {
+        // also rejects NaN, which max and min would let through.
+        let budget = if budget > MIN_CPU_BUDGET {
+            budget.min(1f64)
+        } else {
+            MIN_CPU_BUDGET
+        };
+        BudgetPacer {
+            interval,
+            budget,
+            average: None,
+            next: Some(clock.now()),
+            clock,
+        }
+    }
+
+    /// Account a sample started at `started` which cost `cost` of CPU, `None` if it couldn't
+    /// be measured, and return the interval until the next one.
+    fn sampled(&mut self, started: Instant, cost: Option<Duration>) -> Duration {
+        if let Some(cost) = cost {
+            self.average = Some(match self.average {
+                Some(average) => (average * 3 + cost) / 4,
+                None => cost,
+            });
+        }
+        let budgeted = self.average.map_or(Duration::ZERO, |average| {
+            Duration::try_from_secs_f64(average.as_secs_f64() / self.budget)
+                .unwrap_or(Duration::MAX)
+        });
+        let interval = self.interval.max(budgeted);
+        self.next = started.checked_add(interval);
+        interval
+    }
+
+    /// The time left until the next sample is due.
+    fn wait(&self) -> Duration {
+        self.next.map_or(Duration::MAX, |next| {
+            next.saturating_duration_since(self.clock.now())
+        })
+    }
+}
+
+impl MemoryMonitor {
+    /// Start sampling every `interval`.
+    pub fn spawn(interval: Duration) -> (Self, Receiver<ProcessMemoryInfo>) {
+        let (tx, rx) = mpsc::channel();
+        let monitor = Self::start(move |stop_rx| loop {
+            if let Ok(info) = get_process_memory_info() {
+                publish_snapshot(&info);
+                if tx.send(info).is_err() {
+                    // nobody listens anymore.
+                    break;
+                }
+            }
+            match stop_rx.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                _ => break,
+            }
+        });
+        (monitor, rx)
+    }
+
+    /// Start sampling every `interval`, and deliver each sample with its sequence number and
+    /// the time elapsed since the previous one, to detect the gaps in the sampling.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use workflow_perf_monitor::mem::MemoryMonitor;
+    /// let interval = Duration::from_millis(10);
+    /// let (monitor, samples) = MemoryMonitor::spawn_sequenced(interval);
+    /// for sample in samples.iter().take(3) {
+    ///     if sample.interval > interval * 2 {
+    ///         println!("sampler delayed before #{}", sample.seq);
+    ///     }
+    /// }
+    /// monitor.stop().unwrap();
+    /// ```
+    pub fn spawn_sequenced(interval: Duration) -> (Self, Receiver<SampledMemory>) {
+        Self::spawn_sequenced_using(interval, get_process_memory_info)
+    }
+
+    fn spawn_sequenced_using<F>(
+        interval: Duration,
+        mut sample: F,
+    ) -> (Self, Receiver<SampledMemory>)
+    where
+        F: FnMut() -> Result<ProcessMemoryInfo> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let monitor = Self::start(move |stop_rx| {
+            let mut sequencer = Sequencer::default();
+            loop {
+                let info = sample();
+                if let Ok(sampled) = sequencer.tag(Instant::now(), info) {
+                    publish_snapshot(&sampled.info);
+                    if tx.send(sampled).is_err() {
+                        break;
+                    }
+                }
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+        (monitor, rx)
+    }
+
+    /// Start sampling every `interval`, and deliver the samples in batches to wake the
+    /// receiver up less often.
+    ///
+    /// A batch is sent once it holds `batch_size` samples, or when its first sample is
+    /// `max_latency` old, whichever comes first. The last partial batch is sent on stop.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use workflow_perf_monitor::mem::MemoryMonitor;
+    /// let (monitor, batches) =
+    ///     MemoryMonitor::spawn_batched(Duration::from_millis(1), 5, Duration::from_secs(1));
+    /// assert_eq!(batches.recv().unwrap().len(), 5);
+    /// monitor.stop().unwrap();
+    /// ```
+    pub fn spawn_batched(
+        interval: Duration,
+        batch_size: usize,
+        max_latency: Duration,
+    ) -> (Self, Receiver<Vec<ProcessMemoryInfo>>) {
+        let batch_size = batch_size.max(1);
+        let (tx, rx) = mpsc::channel();
+        let mut monitor = Self::start(move |stop_rx| {
+            let mut batch = Vec::with_capacity(batch_size);
+            let mut batch_start = Instant::now();
+            loop {
+                if let Ok(info) = get_process_memory_info() {
+                    publish_snapshot(&info);
+                    if batch.is_empty() {
+                        batch_start = Instant::now();
+                    }
+                    batch.push(info);
+                }
+                if batch.len() >= batch_size
+                    || (!batch.is_empty() && batch_start.elapsed() >= max_latency)
+                {
+                    let full = mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                    if tx.send(full).is_err() {
+                        return;
+                    }
+                }
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+            if !batch.is_empty() {
+                let _ = tx.send(batch);
+            }
+        });
+        monitor.buffer_bytes = samples_bytes(batch_size);
+        (monitor, rx)
+    }
+
+    /// Start sampling every `interval` into a queue of at most `capacity` samples, `policy`
+    /// tells what to do when the consumer doesn't keep up and the queue is full.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use workflow_perf_monitor::mem::{Backpressure, MemoryMonitor};
+    /// let (monitor, samples) =
+    ///     MemoryMonitor::spawn_bounded(Duration::from_millis(1), 16, Backpressure::DropOldest);
+    /// let info = samples.recv().unwrap();
+    /// println!("{} samples dropped", monitor.dropped());
+    /// monitor.stop().unwrap();
+    /// ```
+    pub fn spawn_bounded(
+        interval: Duration,
+        capacity: usize,
+        policy: Backpressure,
+    ) -> (Self, SampleReceiver) {
+        let queue = SampleQueue::new(capacity, policy);
+        let tx = SampleSender(queue.clone());
+        let mut monitor = Self::start(move |stop_rx| loop {
+            if let Ok(info) = get_process_memory_info() {
+                publish_snapshot(&info);
+                if !tx.send(info) {
+                    break;
+                }
+            }
+            match stop_rx.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                _ => break,
+            }
+        });
+        monitor.queue = Some(queue.clone());
+        monitor.buffer_bytes =
+            samples_bytes(capacity.max(1)) + mem::size_of::<SampleQueue>() as u64;
+        (monitor, SampleReceiver::new(queue))
+    }
+
+    /// Start sampling every `interval` from a thread pinned to the CPU `core`, so it doesn't
+    /// migrate between cores and perturb the caches of a benchmark.
+    ///
+    /// It uses `sched_setaffinity` on Linux and Android, and `SetThreadAffinityMask` on
+    /// Windows, where `core` is in the processor group of the thread. On MacOS and iOS the
+    /// thread is not pinned: they have no such API, affinity tags are mere hints which Apple
+    /// silicon ignores. The error is the one of pinning, e.g. a `core` the process may not use.
+    pub fn spawn_on_core(
+        core: usize,
+        interval: Duration,
+    ) -> Result<(Self, Receiver<ProcessMemoryInfo>)> {
+        let (pinned_tx, pinned_rx) = mpsc::sync_channel(1);
+        let (tx, rx) = mpsc::channel();
+        let monitor = Self::start(move |stop_rx| {
+            let pinned = crate::utils::affinity::pin_current_thread(core);
+            let failed = pinned.is_err();
+            let _ = pinned_tx.send(pinned);
+            if failed {
+                return;
+            }
+            loop {
+                if let Ok(info) = get_process_memory_info() {
+                    publish_snapshot(&info);
+                    if tx.send(info).is_err() {
+                        break;
+                    }
+                }
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+        match pinned_rx.recv() {
+            Ok(Ok(())) => Ok((monitor, rx)),
+            Ok(Err(e)) => Err(e),
+            // the thread panicked before pinning.
+            Err(_) => Err(std::io::Error::other("sampling thread exited")),
+        }
+    }
+
+    /// Start sampling every `interval` or less often, so that the sampling thread uses at most
+    /// `budget` of a CPU, e.g. `0.005` for 0.5%.
+    ///
+    /// The thread measures the CPU time each sample costs it, averaged over the last samples,
+    /// and starts a sample every `cost / budget` when that is longer than `interval`. A
+    /// `budget` outside of `[MIN_CPU_BUDGET, 1]`, NaN included, is clamped into it.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use workflow_perf_monitor::mem::MemoryMonitor;
+    /// let (monitor, samples) = MemoryMonitor::spawn_with_budget(Duration::from_millis(1), 0.005);
+    /// let info = samples.recv().unwrap();
+    /// println!("sampling every {:?}", monitor.effective_interval().unwrap());
+    /// monitor.stop().unwrap();
+    /// ```
+    pub fn spawn_with_budget(
+        interval: Duration,
+        budget: f64,
+    ) -> (Self, Receiver<ProcessMemoryInfo>) {
+        Self::spawn_with_budget_using(interval, budget, get_process_memory_info, SystemClock)
+    }
+
+    fn spawn_with_budget_using<F, C>(
+        interval: Duration,
+        budget: f64,
+        mut sample: F,
+        clock: C,
+    ) -> (Self, Receiver<ProcessMemoryInfo>)
+    where
+        F: FnMut() -> Result<ProcessMemoryInfo> + Send + 'static,
+        C: Clock + Send + 'static,
+    {
+        let state = Arc::new(BudgetState::default());
+        state.interval.store(nanos(interval), Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+        let shared = state.clone();
+        let mut monitor = Self::start(move |stop_rx| {
+            let mut stat = ThreadStat::cur().ok();
+            let mut pacer = BudgetPacer::new(interval, budget, clock);
+            loop {
+                let started = pacer.clock.now();
+                if let Ok(info) = sample() {
+                    publish_snapshot(&info);
+                    if tx.send(info).is_err() {
+                        break;
+                    }
+                }
+                let cost = stat.as_mut().and_then(|stat| stat.cpu_time().ok());
+                let effective = pacer.sampled(started, cost);
+                if let Some(average) = pacer.average {
+                    shared.cost.store(nanos(average), Ordering::Relaxed);
+                }
+                shared.interval.store(nanos(effective), Ordering::Relaxed);
+                match stop_rx.recv_timeout(pacer.wait()) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+        monitor.budget = Some(state);
+        (monitor, rx)
+    }
+
+    /// The interval `spawn_with_budget` currently waits between samples, `None` for the other
+    /// monitors.
+    pub fn effective_interval(&self) -> Option<Duration> {
+        let budget = self.budget.as_ref()?;
+        Some(Duration::from_nanos(
+            budget.interval.load(Ordering::Relaxed),
+        ))
+    }
+
+    /// The average CPU time a sample of `spawn_with_budget` costs, `None` for the other monitors,
+    /// `Duration::ZERO` until the first sample was measured.
+    pub fn self_cost(&self) -> Option<Duration> {
+        let budget = self.budget.as_ref()?;
+        Some(Duration::from_nanos(budget.cost.load(Ordering::Relaxed)))
+    }
+
+    /// The number of samples dropped because the queue of `spawn_bounded` was full, always 0
+    /// for the other monitors.
+    pub fn dropped(&self) -> u64 {
+        self.queue.as_ref().map_or(0, |queue| queue.dropped())
+    }
+
+    /// An estimate of the memory the monitor itself adds to the RSS it reports, in bytes, to
+    /// subtract for the memory of the application alone.
+    ///
+    /// It is an approximation: the resident stack of the sampling thread as a fixed 16KiB,
+    /// plus the buffers allocated upfront, the queue of `spawn_bounded` and the batch of
+    /// `spawn_batched`, at their full capacity whether or not their pages were touched yet.
+    /// The samples waiting in the channel of the other monitors, and the heap fragmentation,
+    /// are not counted.
+    pub fn self_overhead_bytes(&self) -> u64 {
+        THREAD_OVERHEAD_BYTES + self.buffer_bytes
+    }
+
+    /// Run `body` on the sampling thread, it should return once the stop channel is closed.
+    fn start<F>(body: F) -> Self
+    where
+        F: FnOnce(Receiver<()>) + Send + 'static,
+    {
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let flag = running.clone();
+        let handle = thread::spawn(move || {
+            let _running = RunningGuard(flag);
+            body(stop_rx);
+        });
+
+        MemoryMonitor {
+            stop: Some(stop_tx),
+            handle: Some(handle),
+            running,
+            queue: None,
+            budget: None,
+            buffer_bytes: 0,
+        }
+    }
+
+    /// Whether the sampling thread is still alive.
+    ///
+    /// It may have exited on its own, for example when the `Receiver` was dropped.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Stop the sampling thread and wait for it.
+    ///
+    /// return `Err` with the panic payload if the sampling thread panicked.
+    pub fn stop(mut self) -> thread::Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> thread::Result<()> {
+        // Dropping the sender wakes the thread up, unless it waits for room in the queue.
+        self.stop.take();
+        if let Some(queue) = &self.queue {
+            queue.stop();
+        }
+        match self.handle.take() {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MemoryMonitor {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+fn samples_bytes(samples: usize) -> u64 {
+    (samples * mem::size_of::<ProcessMemoryInfo>()) as u64
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Clears the running flag however the sampling thread exits, panics included.
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drop_joins_thread() {
+        let (monitor, samples) = MemoryMonitor::spawn(Duration::from_secs(3600));
+        assert!(samples.recv().unwrap().resident_set_size > 0);
+        assert!(monitor.is_running());
+
+        let running = monitor.running.clone();
+        let start = Instant::now();
+        drop(monitor);
+        // the thread was sleeping for an hour, it must have been woken up.
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!running.load(Ordering::SeqCst));
+        // and the sender it owned is gone too.
+        assert!(samples.recv().is_err());
+    }
+
+    #[test]
+    fn test_stop() {
+        let (monitor, samples) = MemoryMonitor::spawn(Duration::from_millis(1));
+        for _ in 0..3 {
+            samples.recv().unwrap();
+        }
+        assert!(monitor.stop().is_ok());
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let (monitor, samples) = MemoryMonitor::spawn(Duration::from_millis(1));
+        drop(samples);
+        let start = Instant::now();
+        while monitor.is_running() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(monitor.stop().is_ok());
+    }
+
+    #[test]
+    fn test_sequenced() {
+        let interval = Duration::from_millis(5);
+        let mut reads = 0;
+        let delayed = move || {
+            reads += 1;
+            match reads {
+                // the sampler is starved before the 3rd read.
+                3 => thread::sleep(Duration::from_millis(200)),
+                // the 5th read fails.
+                5 => return Err(std::io::Error::other("failed read")),
+                _ => {}
+            }
+            get_process_memory_info()
+        };
+        let (monitor, samples) = MemoryMonitor::spawn_sequenced_using(interval, delayed);
+        let samples: Vec<_> = samples.iter().take(5).collect();
+        assert!(monitor.stop().is_ok());
+
+        let seqs: Vec<u64> = samples.iter().map(|sample| sample.seq).collect();
+        assert_eq!(seqs, [0, 1, 2, 3, 5]);
+        assert_eq!(samples[0].interval, Duration::ZERO);
+        assert!(samples[1].interval < Duration::from_millis(150));
+        assert!(samples[2].interval >= Duration::from_millis(200));
+        assert!(samples[3].interval < Duration::from_millis(150));
+        assert!(samples[4].interval >= interval * 2);
+        assert!(samples[4].info.resident_set_size > 0);
+    }
+
+    #[test]
+    fn test_batched() {
+        let (monitor, batches) =
+            MemoryMonitor::spawn_batched(Duration::from_millis(1), 5, Duration::from_secs(3600));
+        for _ in 0..3 {
+            assert_eq!(batches.recv().unwrap().len(), 5);
+        }
+        assert!(monitor.stop().is_ok());
+        // at most the last partial batch is left.
+        let rest: Vec<_> = batches.iter().collect();
+        assert!(rest.iter().all(|batch| batch.len() <= 5));
+        assert!(rest.iter().rev().skip(1).all(|batch| batch.len() == 5));
+    }
+
+    #[test]
+    fn test_batched_flush_on_stop() {
+        let (monitor, batches) =
+            MemoryMonitor::spawn_batched(Duration::from_secs(3600), 5, Duration::from_secs(3600));
+        assert!(monitor.stop().is_ok());
+        // the first sample is taken before waiting, and flushed.
+        let batch = batches.recv().unwrap();
+        assert_eq!(batch.len(), 1);
+        assert!(batches.recv().is_err());
+    }
+
+    #[test]
+    fn test_batched_max_latency() {
+        let (monitor, batches) =
+            MemoryMonitor::spawn_batched(Duration::from_millis(1), 1000, Duration::from_millis(20));
+        let batch = batches.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!batch.is_empty() && batch.len() < 1000);
+        assert!(monitor.stop().is_ok());
+    }
+
+    #[test]
+    fn test_bounded_drop_newest() {
+        let (monitor, samples) =
+            MemoryMonitor::spawn_bounded(Duration::from_millis(1), 2, Backpressure::DropNewest);
+        for _ in 0..3 {
+            samples.recv().unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        let dropped = monitor.dropped();
+        assert!(dropped > 0);
+        thread::sleep(Duration::from_millis(20));
+        assert!(monitor.dropped() > dropped);
+        assert!(monitor.stop().is_ok());
+        // the queue was full when stopped, then disconnected.
+        assert!(samples.recv().is_ok());
+        assert!(samples.recv().is_ok());
+        assert!(samples.recv().is_err());
+    }
+
+    #[test]
+    fn test_bounded_block() {
+        let (monitor, samples) =
+            MemoryMonitor::spawn_bounded(Duration::from_millis(1), 1, Backpressure::Block);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(monitor.dropped(), 0);
+        // the thread blocked on the full queue must be woken up.
+        let start = Instant::now();
+        assert!(monitor.stop().is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(samples.try_recv().is_ok());
+        assert!(samples.try_recv().is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_spawn_on_core() {
+        let cores = crate::utils::affinity::test::current_affinity();
+        let (monitor, samples) =
+            MemoryMonitor::spawn_on_core(cores[0], Duration::from_millis(1)).unwrap();
+        samples.recv().unwrap();
+        assert!(monitor.stop().is_ok());
+
+        // a core out of the cpu set of the process.
+        let other = (0..1024).find(|core| !cores.contains(core)).unwrap();
+        let err = MemoryMonitor::spawn_on_core(other, Duration::from_millis(1))
+            .err()
+            .unwrap();
+        assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+    }
+
+    #[test]
+    fn test_budget() {
+        // about 5ms of CPU per sample, a 10% budget needs 50ms between samples.
+        let expensive = || {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(5) {
+                std::hint::black_box((0..1000u64).sum::<u64>());
+            }
+            get_process_memory_info()
+        };
+        let (monitor, samples) = MemoryMonitor::spawn_with_budget_using(
+            Duration::from_millis(1),
+            0.1,
+            expensive,
+            SystemClock,
+        );
+        for _ in 0..5 {
+            samples.recv().unwrap();
+        }
+        assert!(monitor.self_cost().unwrap() >= Duration::from_millis(1));
+        let interval = monitor.effective_interval().unwrap();
+        assert!(interval >= Duration::from_millis(10), "{:?}", interval);
+        assert!(monitor.stop().is_ok());
+
+        let (monitor, _samples) = MemoryMonitor::spawn(Duration::from_secs(1));
+        assert_eq!(monitor.effective_interval(), None);
+        assert_eq!(monitor.self_cost(), None);
+    }
+
+    #[test]
+    fn test_budget_pacer() {
+        let clock = crate::clock::MockClock::new();
+        let mut pacer = BudgetPacer::new(Duration::from_millis(1), 0.1, clock.clone());
+        // 5ms of CPU at 10% of a CPU is a sample every 50ms, counted from its start.
+        let started = clock.now();
+        clock.advance(Duration::from_millis(5));
+        let interval = pacer.sampled(started, Some(Duration::from_millis(5)));
+        assert_eq!(interval, Duration::from_millis(50));
+        assert_eq!(pacer.wait(), Duration::from_millis(45));
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(pacer.wait(), Duration::from_millis(5));
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(pacer.wait(), Duration::ZERO);
+
+        // the average moves by a quarter towards the new cost, (3 * 5 + 1) / 4 = 4ms.
+        let interval = pacer.sampled(clock.now(), Some(Duration::from_millis(1)));
+        assert_eq!(interval, Duration::from_millis(40));
+        // an unmeasured sample keeps the average.
+        assert_eq!(pacer.sampled(clock.now(), None), Duration::from_millis(40));
+
+        // a cheap sample waits the interval.
+        let mut pacer = BudgetPacer::new(Duration::from_millis(10), 0.5, clock.clone());
+        let interval = pacer.sampled(clock.now(), Some(Duration::from_micros(1)));
+        assert_eq!(interval, Duration::from_millis(10));
+        assert_eq!(pacer.wait(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_zero_budget() {
+        let expensive = || {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(2) {
+                std::hint::black_box((0..1000u64).sum::<u64>());
+            }
+            get_process_memory_info()
+        };
+        for budget in [0.0, -1.0, f64::NAN] {
+            let (monitor, samples) = MemoryMonitor::spawn_with_budget_using(
+                Duration::from_millis(1),
+                budget,
+                expensive,
+                SystemClock,
+            );
+            samples.recv().unwrap();
+            let start = Instant::now();
+            while monitor.self_cost() == Some(Duration::ZERO) {
+                assert!(start.elapsed() < Duration::from_secs(10));
+                thread::sleep(Duration::from_millis(1));
+            }
+            // the thread is alive, waiting far longer than the interval.
+            assert!(monitor.is_running());
+            assert!(monitor.effective_interval().unwrap() > Duration::from_secs(60));
+            assert!(monitor.stop().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_bounded_receiver_dropped() {
+        let (monitor, samples) =
+            MemoryMonitor::spawn_bounded(Duration::from_millis(1), 1, Backpressure::Block);
+        thread::sleep(Duration::from_millis(10));
+        drop(samples);
+        let start = Instant::now();
+        while monitor.is_running() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(monitor.stop().is_ok());
+    }
+
+    #[test]
+    fn test_self_overhead_bytes() {
+        let (small, _small_samples) =
+            MemoryMonitor::spawn_bounded(Duration::from_secs(1), 16, Backpressure::DropOldest);
+        let (large, _large_samples) =
+            MemoryMonitor::spawn_bounded(Duration::from_secs(1), 100_000, Backpressure::DropOldest);
+        assert!(large.self_overhead_bytes() > small.self_overhead_bytes());
+        assert!(
+            large.self_overhead_bytes() >= 100_000 * mem::size_of::<ProcessMemoryInfo>() as u64
+        );
+
+        let (plain, _samples) = MemoryMonitor::spawn(Duration::from_secs(1));
+        assert_eq!(plain.self_overhead_bytes(), THREAD_OVERHEAD_BYTES);
+        assert!(small.self_overhead_bytes() > plain.self_overhead_bytes());
+    }
+}
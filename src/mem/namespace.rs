@@ -0,0 +1,74 @@
+//! The memory of processes running in other namespaces, e.g. containers seen from the host.
+use super::{get_process_memory_info_for_pid, ProcessMemoryInfo};
+use std::io::{Error, ErrorKind, Result};
+
+/// Get the memory info of the process `pid` of the PID namespace of our `/proc`, usually the
+/// host pid of a containerized process, from its `/proc/<pid>/statm`.
+///
+/// Namespaces don't virtualize the memory of a process: its statm, read from the host or from
+/// within its container, is the same, only the pid differs, see `NSpid` in
+/// `/proc/<pid>/status` for the pid inside. What differs is the accounting of the container:
+/// - `docker stats` and the like report the usage of the memory cgroup, which includes the page
+///   cache and kernel memory charged to the container and spans all of its processes, see
+///   `get_cgroup_memory`.
+/// - `/proc/meminfo` inside the container shows the totals of the host unless it is
+///   virtualized, e.g. by lxcfs.
+/// - the RSS counts the pages shared with other processes fully, summing it over the processes
+///   of a container overestimates its usage.
+///
+/// Fails with `ErrorKind::NotFound` when there is no such pid, e.g. a pid seen inside the
+/// container rather than the host one, and `ErrorKind::PermissionDenied` when `/proc` hides
+/// the processes of other users (`hidepid`) or a security module denies it.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn memory_in_namespace(pid: u32) -> Result<ProcessMemoryInfo> {
+    let path = || crate::procfs::path(&format!("{}/statm", pid));
+    get_process_memory_info_for_pid(pid).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "no process {} in the PID namespace of {}, a pid seen inside a container \
+                 differs from the host one",
+                pid,
+                path().display()
+            ),
+        ),
+        ErrorKind::PermissionDenied => Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "reading {} was denied, /proc may be mounted with hidepid: {}",
+                path().display(),
+                e
+            ),
+        ),
+        _ => e,
+    })
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod test {
+    use super::*;
+    use crate::mem::{get_process_memory_info, get_process_memory_info_for_pid};
+
+    #[test]
+    fn test_memory_in_namespace() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let ours = memory_in_namespace(child.id());
+        let theirs = get_process_memory_info_for_pid(child.id());
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let (ours, theirs) = (ours.unwrap(), theirs.unwrap());
+        assert!(ours.resident_set_size > 0);
+        // the loader of the child may still map pages between the reads, its text is set.
+        assert_eq!(ours.text, theirs.text);
+
+        // other tests allocate meanwhile, only the text of the binary is stable.
+        let ours = memory_in_namespace(std::process::id()).unwrap();
+        assert_eq!(ours.text, get_process_memory_info().unwrap().text);
+
+        let err = memory_in_namespace(u32::MAX).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+}
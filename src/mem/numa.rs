@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+/// Memory of current process resident on one NUMA node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NumaNodeMemory {
+    /// the node id, `N` in the `N<node>=<pages>` tokens.
+    pub node: u32,
+    /// the number of pages on the node, of any page size.
+    pub pages: u64,
+    /// the bytes on the node, taking the page size of every mapping into account.
+    pub bytes: u64,
+}
+
+/// Parse the content of `/proc/[pid]/numa_maps` and sum the pages of every mapping per node.
+///
+/// Pages are sized by the `kernelpagesize_kB` of their mapping, so huge pages count fully;
+/// a mapping without it is assumed to use 4KiB pages.
+/// One entry is returned per node present, sorted by node id.
+pub fn parse_numa_maps(numa_maps: &str) -> Vec<NumaNodeMemory> {
+    let mut nodes: BTreeMap<u32, NumaNodeMemory> = BTreeMap::new();
+    for line in numa_maps.lines() {
+        let mut page_size = 4096;
+        let mut counts = vec![];
+        for token in line.split_whitespace() {
+            let Some((key, value)) = token.split_once('=') else {
+                continue;
+            };
+            if key == "kernelpagesize_kB" {
+                if let Ok(kb) = value.parse::<u64>() {
+                    page_size = kb * 1024;
+                }
+                continue;
+            }
+            let Some(node) = key.strip_prefix('N').and_then(|n| n.parse::<u32>().ok()) else {
+                continue;
+            };
+            if let Ok(pages) = value.parse::<u64>() {
+                counts.push((node, pages));
+            }
+        }
+        for (node, pages) in counts {
+            let entry = nodes.entry(node).or_insert_with(|| NumaNodeMemory {
+                node,
+                ..Default::default()
+            });
+            entry.pages += pages;
+            entry.bytes += pages * page_size;
+        }
+    }
+    nodes.into_values().collect()
+}
+
+/// Get the memory of current process per NUMA node, from `/proc/self/numa_maps`.
+///
+/// This requires a kernel built with `CONFIG_NUMA`, the file doesn't exist otherwise and
+/// `ErrorKind::NotFound` is returned. On UMA machines there is a single node, `0`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_numa_memory() -> std::io::Result<Vec<NumaNodeMemory>> {
+    Ok(parse_numa_maps(&crate::procfs::read_to_string(
+        "self/numa_maps",
+    )?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NUMA_MAPS: &str = "\
+55f0c2a3e000 default file=/usr/bin/server mapped=12 mapmax=2 N0=8 N1=4 kernelpagesize_kB=4
+55f0c4c00000 default heap anon=300 dirty=300 active=0 N0=100 N1=200 kernelpagesize_kB=4
+7f2a00000000 bind:1 anon=2 dirty=2 N1=2 kernelpagesize_kB=2048
+7f2a3c000000 default
+7ffd1c5e3000 default stack anon=33 dirty=33 N0=33 kernelpagesize_kB=4
+";
+
+    #[test]
+    fn test_parse_numa_maps() {
+        let nodes = parse_numa_maps(NUMA_MAPS);
+        assert_eq!(
+            nodes,
+            vec![
+                NumaNodeMemory {
+                    node: 0,
+                    pages: 8 + 100 + 33,
+                    bytes: (8 + 100 + 33) * 4096,
+                },
+                NumaNodeMemory {
+                    node: 1,
+                    pages: 4 + 200 + 2,
+                    bytes: (4 + 200) * 4096 + 2 * 2048 * 1024,
+                },
+            ]
+        );
+        assert!(parse_numa_maps("").is_empty());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_get_numa_memory() {
+        match get_numa_memory() {
+            Ok(nodes) => assert!(!nodes.is_empty()),
+            Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+        }
+    }
+}
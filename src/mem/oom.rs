@@ -0,0 +1,89 @@
+use std::io::{Error, ErrorKind, Result};
+
+const OOM_SCORE_ADJ_MIN: i32 = -1000;
+const OOM_SCORE_ADJ_MAX: i32 = 1000;
+
+fn clamp_oom_score_adj(value: i32) -> i32 {
+    value.clamp(OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX)
+}
+
+/// Read `path` under procfs.
+fn read_i32(path: &str) -> Result<i32> {
+    let value = crate::procfs::read_to_string(path)?;
+    let path = crate::procfs::path(path);
+    value.trim().parse().map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid {}: {}", path.display(), e),
+        )
+    })
+}
+
+/// Get the OOM score of current process from `/proc/self/oom_score`, in `0..=2000`.
+///
+/// The process with the highest score is the first one killed by the OOM killer.
+pub fn get_oom_score() -> Result<i32> {
+    read_i32("self/oom_score")
+}
+
+/// Get the adjustment added to the OOM score of current process, from `/proc/self/oom_score_adj`.
+pub fn get_oom_score_adj() -> Result<i32> {
+    read_i32("self/oom_score_adj")
+}
+
+/// Set the adjustment added to the OOM score of current process, `value` is clamped to `-1000..=1000`.
+///
+/// `-1000` disables the OOM killer for the process, `1000` makes it the first candidate.
+/// Lowering the value below what it was requires `CAP_SYS_RESOURCE`, `ErrorKind::PermissionDenied`
+/// is returned otherwise.
+pub fn set_oom_score_adj(value: i32) -> Result<()> {
+    let value = clamp_oom_score_adj(value);
+    std::fs::write(crate::procfs::path("self/oom_score_adj"), value.to_string()).map_err(|e| {
+        if e.kind() == ErrorKind::PermissionDenied {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "setting oom_score_adj to {} requires CAP_SYS_RESOURCE: {}",
+                    value, e
+                ),
+            )
+        } else {
+            e
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_oom_score() {
+        let score = get_oom_score().unwrap();
+        assert!((0..=2000).contains(&score));
+        let adj = get_oom_score_adj().unwrap();
+        assert!((OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(&adj));
+    }
+
+    #[test]
+    fn test_clamp_oom_score_adj() {
+        assert_eq!(clamp_oom_score_adj(-5000), -1000);
+        assert_eq!(clamp_oom_score_adj(-1000), -1000);
+        assert_eq!(clamp_oom_score_adj(0), 0);
+        assert_eq!(clamp_oom_score_adj(300), 300);
+        assert_eq!(clamp_oom_score_adj(i32::MAX), 1000);
+    }
+
+    #[test]
+    fn test_set_oom_score_adj() {
+        // raising it is always allowed, and it doesn't hurt the test process.
+        let adj = get_oom_score_adj().unwrap();
+        let raised = clamp_oom_score_adj(adj + 1);
+        set_oom_score_adj(raised).unwrap();
+        assert_eq!(get_oom_score_adj().unwrap(), raised);
+
+        // lowering it back is allowed too, down to the value it had when privileges last set it.
+        set_oom_score_adj(adj).unwrap();
+        assert_eq!(get_oom_score_adj().unwrap(), adj);
+    }
+}
@@ -0,0 +1,31 @@
+use std::io::Result;
+
+/// Reset the peak RSS of current process to its current RSS, to measure the peak of each
+/// phase of a run separately.
+///
+/// - Linux and Android: writes `5` to `/proc/self/clear_refs`, which resets `VmHWM` of
+///   `/proc/self/status` since Linux 4.0.
+/// - Windows: the peak working set can't be reset. `EmptyWorkingSet` is called instead: it
+///   trims the working set, so the pages touched by the next phase are faulted in again and
+///   show in its working set, but `resident_set_size_peak` keeps the peak of the whole run.
+/// - MacOS and iOS: unsupported, the function is not available.
+pub fn reset_peak_rss() -> Result<()> {
+    reset_peak_rss_impl()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn reset_peak_rss_impl() -> Result<()> {
+    // the values written are documented in proc(5), 5 is "reset the peak resident set size".
+    std::fs::write(crate::procfs::path("self/clear_refs"), "5")
+}
+
+#[cfg(target_os = "windows")]
+fn reset_peak_rss_impl() -> Result<()> {
+    use windows_sys::Win32::System::{
+        ProcessStatus::EmptyWorkingSet, Threading::GetCurrentProcess,
+    };
+    if unsafe { EmptyWorkingSet(GetCurrentProcess()) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
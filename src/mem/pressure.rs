@@ -0,0 +1,178 @@
+use std::io::Result;
+
+/// Coarse memory pressure level, ordered from `Low` to `Critical`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pressure {
+    /// below 60% of the memory the process can use.
+    Low,
+    /// 60% to 80%.
+    Medium,
+    /// 80% to 95%.
+    High,
+    /// 95% and above.
+    Critical,
+}
+
+impl Pressure {
+    /// Classify a fraction of used memory, in `0.0..=1.0`.
+    pub fn from_ratio(ratio: f64) -> Self {
+        if ratio < 0.6 {
+            Pressure::Low
+        } else if ratio < 0.8 {
+            Pressure::Medium
+        } else if ratio < 0.95 {
+            Pressure::High
+        } else {
+            Pressure::Critical
+        }
+    }
+}
+
+#[cfg_attr(
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "windows",
+        test
+    )),
+    allow(dead_code)
+)]
+fn ratio(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 1.0;
+    }
+    used as f64 / total as f64
+}
+
+/// The pressure for a process using `rss` bytes while `available` more are available on the
+/// system, and its cgroup uses `usage` bytes of `limit`.
+///
+/// The higher of the two pressures wins.
+#[cfg_attr(
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "windows",
+        test
+    )),
+    allow(dead_code)
+)]
+fn classify(rss: u64, available: u64, cgroup: Option<(u64, u64)>) -> Pressure {
+    let system = Pressure::from_ratio(ratio(rss, rss.saturating_add(available)));
+    let cgroup = cgroup.map(|(usage, limit)| Pressure::from_ratio(ratio(usage, limit)));
+    system.max(cgroup.unwrap_or(Pressure::Low))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn memory_pressure_impl() -> Result<Pressure> {
+    let rss = super::get_process_memory_info()?.resident_set_size;
+    let available = super::get_system_memory_info()?.available;
+    let cgroup = super::get_cgroup_memory()?
+        .and_then(|cgroup| cgroup.limit.map(|limit| (cgroup.usage, limit)));
+    Ok(classify(rss, available, cgroup))
+}
+
+#[cfg(target_os = "windows")]
+fn memory_pressure_impl() -> Result<Pressure> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let rss = super::get_process_memory_info()?.resident_set_size;
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(classify(rss, status.ullAvailPhys, None))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn memory_pressure_impl() -> Result<Pressure> {
+    // the level behind DISPATCH_SOURCE_TYPE_MEMORYPRESSURE, one of DISPATCH_MEMORYPRESSURE_*.
+    const NORMAL: libc::c_int = 1;
+    const WARN: libc::c_int = 2;
+    const CRITICAL: libc::c_int = 4;
+
+    let mut level: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    let ret = unsafe {
+        libc::sysctlbyname(
+            b"kern.memorystatus_vm_pressure_level\0".as_ptr() as *const libc::c_char,
+            &mut level as *mut libc::c_int as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    match level {
+        NORMAL => Ok(Pressure::Low),
+        WARN => Ok(Pressure::High),
+        CRITICAL => Ok(Pressure::Critical),
+        level => Err(std::io::Error::other(format!(
+            "Unknown memory pressure level {}",
+            level
+        ))),
+    }
+}
+
+/// Get a coarse memory pressure level for current process.
+///
+/// On Linux, Android and Windows it is the share of RSS in the memory the process could use,
+/// that is its RSS plus the available system memory; on Linux the usage of the cgroup against
+/// its limit is considered too and the higher level wins. The thresholds are documented on
+/// [`Pressure`].
+///
+/// On MacOS and iOS the system-wide level of the kernel is used instead, the one
+/// `DISPATCH_SOURCE_TYPE_MEMORYPRESSURE` reports: normal is `Low`, warn is `High` and critical
+/// is `Critical`, `Medium` is never returned.
+pub fn memory_pressure() -> Result<Pressure> {
+    memory_pressure_impl()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(GIB, 9 * GIB, None), Pressure::Low);
+        assert_eq!(classify(6 * GIB, 4 * GIB, None), Pressure::Medium);
+        assert_eq!(classify(8 * GIB, 2 * GIB, None), Pressure::High);
+        assert_eq!(classify(96 * GIB, 4 * GIB, None), Pressure::Critical);
+        assert_eq!(classify(0, 0, None), Pressure::Critical);
+
+        // a cgroup limit tighter than the system.
+        assert_eq!(classify(GIB, 9 * GIB, Some((GIB, 2 * GIB))), Pressure::Low);
+        assert_eq!(
+            classify(GIB, 9 * GIB, Some((GIB * 17 / 10, 2 * GIB))),
+            Pressure::High
+        );
+        assert_eq!(
+            classify(8 * GIB, 2 * GIB, Some((GIB, 10 * GIB))),
+            Pressure::High
+        );
+        assert_eq!(
+            classify(GIB, 9 * GIB, Some((2 * GIB, 2 * GIB))),
+            Pressure::Critical
+        );
+    }
+
+    #[test]
+    fn test_from_ratio() {
+        assert_eq!(Pressure::from_ratio(0.0), Pressure::Low);
+        assert_eq!(Pressure::from_ratio(0.59), Pressure::Low);
+        assert_eq!(Pressure::from_ratio(0.6), Pressure::Medium);
+        assert_eq!(Pressure::from_ratio(0.8), Pressure::High);
+        assert_eq!(Pressure::from_ratio(0.95), Pressure::Critical);
+        assert!(Pressure::Low < Pressure::Critical);
+    }
+
+    #[test]
+    fn test_memory_pressure() {
+        memory_pressure().unwrap();
+    }
+}
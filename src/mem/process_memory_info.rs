@@ -1,7 +1,18 @@
-use std::io::{Error, Result};
+use std::{convert::TryFrom, io::Result};
 
 /// Process Memory Info returned by `get_process_memory_info`
+///
+/// With the `serde` feature it serializes every field as a raw byte count,
+/// wrap it in `Human` to get formatted sizes instead. It deserializes from the same form, the
+/// fields missing, e.g. those of another platform, default to 0.
+///
+/// On MacOS and iOS the `minimal-macos` feature removes `phys_footprint` and `compressed`, and
+/// reads the sizes with `MACH_TASK_BASIC_INFO` rather than `TASK_VM_INFO`. This only saves
+/// the code handling the large `task_vm_info` struct, a few hundred bytes: worth it for
+/// the most size-constrained apps only, measure the gain on yours.
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ProcessMemoryInfo {
     /// this is the non-swapped physical memory a process has used.
     /// On UNIX it matches `top`'s RES column.
@@ -19,6 +30,27 @@ pub struct ProcessMemoryInfo {
     /// Usage" "VM Size" column of taskmgr.exe.
     pub virtual_memory_size: u64,
 
+    /// the resident pages backed by a file or shared memory, on Linux and Android only.
+    ///
+    /// This is the third field of `/proc/self/statm`, the `RssFile` plus `RssShmem` of
+    /// `/proc/self/status`.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub shared: u64,
+
+    /// the size of the executable code, on Linux and Android only.
+    ///
+    /// This is the fourth field of `/proc/self/statm`, it excludes the shared libraries.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub text: u64,
+
+    /// the address space reserved or committed by the process, on Windows only.
+    ///
+    /// Unlike `virtual_memory_size` it includes regions reserved but not committed, which
+    /// count towards address space exhaustion. It is only computed by a `MemoryQuery` with
+    /// `virtual_reserved(true)`, it is 0 otherwise.
+    #[cfg(target_os = "windows")]
+    pub virtual_reserved: u64,
+
     ///  This is the sum of:
     ///
     ///    + (internal - alternate_accounting)
@@ -34,28 +66,160 @@ pub struct ProcessMemoryInfo {
     ///    + page_table
     ///
     /// details: <https://github.com/apple/darwin-xnu/blob/master/osfmk/kern/task.c>
-    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    ///
+    /// Not available with the `minimal-macos` feature.
+    #[cfg(all(
+        any(target_os = "macos", target_os = "ios"),
+        not(feature = "minimal-macos")
+    ))]
     #[cfg_attr(doc, doc(macos))]
     pub phys_footprint: u64,
 
-    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    /// Not available with the `minimal-macos` feature.
+    #[cfg(all(
+        any(target_os = "macos", target_os = "ios"),
+        not(feature = "minimal-macos")
+    ))]
     #[cfg_attr(doc, doc(macos))]
     pub compressed: u64,
 }
 
+impl ProcessMemoryInfo {
+    /// The fields present on this platform with their names, in declaration order.
+    pub(crate) fn fields(&self) -> Vec<(&'static str, u64)> {
+        #[allow(unused_mut)]
+        let mut fields = vec![("resident_set_size", self.resident_set_size)];
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        fields.push(("resident_set_size_peak", self.resident_set_size_peak));
+        fields.push(("virtual_memory_size", self.virtual_memory_size));
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            fields.push(("shared", self.shared));
+            fields.push(("text", self.text));
+        }
+        #[cfg(target_os = "windows")]
+        fields.push(("virtual_reserved", self.virtual_reserved));
+        #[cfg(all(
+            any(target_os = "macos", target_os = "ios"),
+            not(feature = "minimal-macos")
+        ))]
+        {
+            fields.push(("phys_footprint", self.phys_footprint));
+            fields.push(("compressed", self.compressed));
+        }
+        fields
+    }
+
+    /// Check `names` against the fields present on this platform, for the exporters which
+    /// emit a selection of them.
+    #[cfg(any(feature = "prometheus", feature = "statsd"))]
+    pub(crate) fn field_names(names: &[&str]) -> Result<Vec<&'static str>> {
+        let known = Self::default().fields();
+        names
+            .iter()
+            .map(|name| {
+                known
+                    .iter()
+                    .find(|(known, _)| known == name)
+                    .map(|(known, _)| *known)
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("Unknown memory field {}", name),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// The address space mapped but not resident, `virtual_memory_size - resident_set_size`,
+    /// 0 rather than negative when the RSS is the larger one.
+    ///
+    /// A large gap is usually harmless: reserved regions never touched, like the stacks of
+    /// threads, allocator arenas or big `mmap`s faulted in lazily, cost address space only. It
+    /// also counts pages swapped out, or on MacOS compressed, so compare with the swap before
+    /// blaming reservations. On Windows `virtual_memory_size` is the committed memory, the gap
+    /// is the commit charge not in the working set.
+    pub fn unbacked(&self) -> u64 {
+        self.virtual_memory_size
+            .saturating_sub(self.resident_set_size)
+    }
+
+    /// Whether every field present on this platform differs from `other` by at most
+    /// `tolerance_bytes`.
+    pub fn approx_eq(&self, other: &Self, tolerance_bytes: u64) -> bool {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .all(|((_, a), (_, b))| a.abs_diff(b) <= tolerance_bytes)
+    }
+
+    /// Whether every field present on this platform differs from `other` by at most `percent`
+    /// percent of the larger of the two values.
+    pub fn approx_eq_pct(&self, other: &Self, percent: f64) -> bool {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .all(|((_, a), (_, b))| a.abs_diff(b) as f64 <= a.max(b) as f64 * percent / 100f64)
+    }
+
+    /// The fields which differ in `other`, a later sample, with the name of the field and its
+    /// change since `self`, in declaration order. The changes saturate at the bounds of `i64`.
+    pub fn changed_fields(&self, other: &Self) -> Vec<(&'static str, i64)> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((name, before), (_, after))| {
+                let delta = i64::try_from(after.abs_diff(before)).unwrap_or(i64::MAX);
+                (name, if after > before { delta } else { -delta })
+            })
+            .collect()
+    }
+
+    /// Add the fields of `other` into `self`.
+    pub(crate) fn accumulate(&mut self, other: &ProcessMemoryInfo) {
+        self.resident_set_size += other.resident_set_size;
+        self.virtual_memory_size += other.virtual_memory_size;
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        {
+            self.resident_set_size_peak += other.resident_set_size_peak;
+        }
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            self.shared += other.shared;
+            self.text += other.text;
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.virtual_reserved += other.virtual_reserved;
+        }
+        #[cfg(all(
+            any(target_os = "macos", target_os = "ios"),
+            not(feature = "minimal-macos")
+        ))]
+        {
+            self.phys_footprint += other.phys_footprint;
+            self.compressed += other.compressed;
+        }
+    }
+}
+
 #[cfg(target_os = "windows")]
-fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
+fn process_memory_info(
+    handle: windows_sys::Win32::Foundation::HANDLE,
+) -> Result<ProcessMemoryInfo> {
+    use std::io::Error;
     use std::mem::MaybeUninit;
     use windows_sys::Win32::System::ProcessStatus::GetProcessMemoryInfo;
     use windows_sys::Win32::System::ProcessStatus::PROCESS_MEMORY_COUNTERS;
-    use windows_sys::Win32::System::Threading::GetCurrentProcess;
     let mut process_memory_counters = MaybeUninit::<PROCESS_MEMORY_COUNTERS>::uninit();
     let ret = unsafe {
         // If the function succeeds, the return value is nonzero.
         // If the function fails, the return value is zero.
         // https://docs.microsoft.com/en-us/windows/win32/api/psapi/nf-psapi-getprocessmemoryinfo
         GetProcessMemoryInfo(
-            GetCurrentProcess(),
+            handle,
             process_memory_counters.as_mut_ptr(),
             std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
         )
@@ -68,56 +232,259 @@ fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
         resident_set_size: process_memory_counters.WorkingSetSize as u64,
         resident_set_size_peak: process_memory_counters.PeakWorkingSetSize as u64,
         virtual_memory_size: process_memory_counters.PagefileUsage as u64,
+        virtual_reserved: 0,
     })
 }
 
-#[cfg(any(target_os = "linux", target_os = "android"))]
+/// Sum the regions of the address space of `handle` which are reserved or committed.
+///
+/// This walks every region with `VirtualQueryEx`, one call per region: thousands of calls
+/// for a large process, orders of magnitude slower than `GetProcessMemoryInfo`.
+/// The handle needs `PROCESS_QUERY_INFORMATION`.
+#[cfg(target_os = "windows")]
+fn virtual_reserved(handle: windows_sys::Win32::Foundation::HANDLE) -> Result<u64> {
+    use std::io::Error;
+    use std::mem::MaybeUninit;
+    use windows_sys::Win32::System::Memory::{
+        VirtualQueryEx, MEMORY_BASIC_INFORMATION, MEM_COMMIT, MEM_RESERVE,
+    };
+
+    let mut total = 0;
+    let mut address: usize = 0;
+    loop {
+        let mut info = MaybeUninit::<MEMORY_BASIC_INFORMATION>::uninit();
+        let ret = unsafe {
+            VirtualQueryEx(
+                handle,
+                address as *const _,
+                info.as_mut_ptr(),
+                std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+        if ret == 0 {
+            // the walk ends with ERROR_INVALID_PARAMETER past the highest user address.
+            if address == 0 {
+                return Err(Error::last_os_error());
+            }
+            break;
+        }
+        let info = unsafe { info.assume_init() };
+        if info.State == MEM_COMMIT || info.State == MEM_RESERVE {
+            total += info.RegionSize as u64;
+        }
+        let Some(next) = (info.BaseAddress as usize).checked_add(info.RegionSize) else {
+            break;
+        };
+        address = next;
+    }
+    Ok(total)
+}
+
+#[cfg(target_os = "windows")]
+fn virtual_reserved_impl(pid: Option<u32>) -> Result<u64> {
+    use crate::utils::ptr_upgrade::HandleUpgrade;
+    use crate::utils::windows_handle::Handle;
+    use windows_sys::Win32::Foundation::FALSE;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, PROCESS_QUERY_INFORMATION,
+    };
+
+    let Some(pid) = pid else {
+        return virtual_reserved(unsafe { GetCurrentProcess() });
+    };
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION, FALSE, pid) }
+        .upgrade()
+        .map(|x| unsafe { Handle::new(x) });
+    let Some(handle) = handle else {
+        return Err(std::io::Error::last_os_error());
+    };
+    virtual_reserved(handle.as_handle())
+}
+
+#[cfg(target_os = "windows")]
+fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
+    use windows_sys::Win32::System::Threading::GetCurrentProcess;
+    process_memory_info(unsafe { GetCurrentProcess() })
+}
+
+#[cfg(target_os = "windows")]
+fn get_process_memory_info_for_pid_impl(pid: u32) -> Result<ProcessMemoryInfo> {
+    use crate::utils::ptr_upgrade::HandleUpgrade;
+    use crate::utils::windows_handle::Handle;
+    use std::io::Error;
+    use windows_sys::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_INVALID_PARAMETER, FALSE};
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_VM_READ,
+    };
+
+    let open = |access| {
+        unsafe { OpenProcess(access, FALSE, pid) }
+            .upgrade()
+            .map(|x| unsafe { Handle::new(x) })
+            .ok_or_else(Error::last_os_error)
+    };
+    // GetProcessMemoryInfo is documented to need PROCESS_VM_READ, which protected processes
+    // never grant; recent Windows versions are fine with the limited right alone.
+    let handle = match open(PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_VM_READ) {
+        Err(e) if e.raw_os_error() == Some(ERROR_ACCESS_DENIED as i32) => {
+            open(PROCESS_QUERY_LIMITED_INFORMATION)
+        }
+        ret => ret,
+    };
+    let handle = match handle {
+        Ok(handle) => handle,
+        // that's what OpenProcess reports for a pid which doesn't exist.
+        Err(e) if e.raw_os_error() == Some(ERROR_INVALID_PARAMETER as i32) => {
+            return Err(Error::new(std::io::ErrorKind::NotFound, e))
+        }
+        Err(e) => return Err(e),
+    };
+    // `handle` is closed when it goes out of scope, on the error path too.
+    process_memory_info(handle.as_handle())
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
 #[inline]
-fn page_size() -> u64 {
+pub(crate) fn page_size() -> u64 {
     static INIT: std::sync::Once = std::sync::Once::new();
     static mut PAGE_SIZE: u64 = 0;
 
     unsafe {
-        INIT.call_once(|| PAGE_SIZE = libc::sysconf(libc::_SC_PAGESIZE) as u64);
+        INIT.call_once(|| PAGE_SIZE = read_page_size());
         PAGE_SIZE
     }
 }
 
+#[cfg(not(all(
+    any(target_os = "linux", target_os = "android"),
+    feature = "pure-syscall"
+)))]
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
+fn read_page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+/// With `pure-syscall` the page size comes from the `AT_PAGESZ` entry the kernel passed to
+/// the process, so the memory path doesn't call into libc.
+///
+/// `/proc/self/auxv` is on the same procfs as `/proc/self/statm`, if it can't be read the
+/// memory info can't either: the usual 4096 is only a placeholder in that case.
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    feature = "pure-syscall"
+))]
+fn read_page_size() -> u64 {
+    std::fs::read(crate::procfs::path("self/auxv"))
+        .ok()
+        .and_then(|auxv| parse_auxv_page_size(&auxv))
+        .unwrap_or(4096)
+}
+
+/// The `AT_PAGESZ` value of an auxiliary vector, a list of native word pairs `(type, value)`
+/// ended by `AT_NULL`.
 #[cfg(any(target_os = "linux", target_os = "android"))]
-fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
-    // https://www.kernel.org/doc/Documentation/filesystems/proc.txt
-    let statm = std::fs::read_to_string("/proc/self/statm")?;
-    let mut parts = statm.split(' ');
-    let Some(virtual_memory_size_pages): Option<u64> = parts.next().and_then(|s| s.parse().ok())
-    else {
-        return Err(Error::new(
-            std::io::ErrorKind::Other,
-            "Invalid VmSize in /proc/self/statm",
-        ));
-    };
-    let Some(resident_set_size_pages): Option<u64> = parts.next().and_then(|s| s.parse().ok())
-    else {
-        return Err(Error::new(
-            std::io::ErrorKind::Other,
-            "Invalid VmRSS in /proc/self/statm",
-        ));
-    };
+#[cfg_attr(not(feature = "pure-syscall"), allow(dead_code))]
+fn parse_auxv_page_size(auxv: &[u8]) -> Option<u64> {
+    use std::convert::TryInto;
+
+    const AT_NULL: usize = 0;
+    const AT_PAGESZ: usize = 6;
+    const WORD: usize = std::mem::size_of::<usize>();
+
+    let mut words = auxv
+        .chunks_exact(WORD)
+        .map(|word| usize::from_ne_bytes(word.try_into().unwrap()));
+    while let (Some(key), Some(value)) = (words.next(), words.next()) {
+        match key {
+            AT_NULL => break,
+            AT_PAGESZ => return Some(value as u64),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn parse_statm(statm: &str) -> Result<ProcessMemoryInfo> {
+    let statm: super::ProcStatm = statm.parse()?;
     Ok(ProcessMemoryInfo {
-        virtual_memory_size: virtual_memory_size_pages * page_size(),
-        resident_set_size: resident_set_size_pages * page_size(),
+        virtual_memory_size: statm.size * page_size(),
+        resident_set_size: statm.resident * page_size(),
+        shared: statm.shared * page_size(),
+        text: statm.text * page_size(),
     })
 }
 
-#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[cfg(any(target_os = "linux", target_os = "android"))]
 fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
+    parse_statm(&crate::procfs::read_to_string("self/statm")?)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn get_process_memory_info_for_pid_impl(pid: u32) -> Result<ProcessMemoryInfo> {
+    parse_statm(&crate::procfs::read_to_string(&format!("{}/statm", pid))?)
+}
+
+// The bindings are generated from the SDK headers, check at build time that they still have the
+// layout documented in osfmk/mach/task_info.h, a drift would otherwise read garbage at runtime.
+#[cfg(all(
+    any(target_os = "macos", target_os = "ios"),
+    not(feature = "minimal-macos")
+))]
+const _: () = {
     use crate::bindings::task_vm_info;
+    use mach::vm_types::natural_t;
+    use std::mem::{offset_of, size_of};
+
+    // TASK_VM_INFO_COUNT is `sizeof(task_vm_info_data_t) / sizeof(natural_t)`.
+    assert!(size_of::<task_vm_info>() % size_of::<natural_t>() == 0);
+    // TASK_VM_INFO_REV1_COUNT, the kernel fills at least up to `phys_footprint` since macOS 10.11.
+    assert!(size_of::<task_vm_info>() / size_of::<natural_t>() >= 38);
+
+    assert!(offset_of!(task_vm_info, virtual_size) == 0);
+    assert!(offset_of!(task_vm_info, resident_size) == 16);
+    assert!(offset_of!(task_vm_info, resident_size_peak) == 24);
+    assert!(offset_of!(task_vm_info, compressed) == 120);
+    // TASK_VM_INFO_REV0_COUNT (36) natural_t ends right before `phys_footprint`.
+    assert!(offset_of!(task_vm_info, phys_footprint) == 36 * size_of::<natural_t>());
+};
+
+/// Whether the `size` bytes at `offset` of a `task_info` struct are within the first `count`
+/// `natural_t` the kernel filled.
+///
+/// `task_info` is given the capacity of the struct in `natural_t` and lowers the count to the
+/// number it wrote: an older kernel knows a shorter revision of the struct, e.g.
+/// `TASK_VM_INFO_REV0_COUNT` ends before `phys_footprint`, and leaves the rest untouched.
+#[cfg_attr(not(any(target_os = "macos", target_os = "ios")), allow(dead_code))]
+fn task_info_filled(count: u32, offset: usize, size: usize) -> bool {
+    offset + size <= count as usize * std::mem::size_of::<u32>()
+}
+
+#[cfg(all(
+    any(target_os = "macos", target_os = "ios"),
+    not(feature = "minimal-macos")
+))]
+fn task_memory_info(task: mach::port::mach_port_t) -> Result<ProcessMemoryInfo> {
+    use crate::bindings::task_vm_info;
+    use crate::utils::kern_return::kern_return_error;
     use mach::{
         kern_return::KERN_SUCCESS, message::mach_msg_type_number_t, task::task_info,
-        task_info::TASK_VM_INFO, traps::mach_task_self, vm_types::natural_t,
+        task_info::TASK_VM_INFO, vm_types::natural_t,
     };
-    use std::mem::MaybeUninit;
+    use std::mem::{offset_of, MaybeUninit};
 
-    let mut task_vm_info = MaybeUninit::<task_vm_info>::uninit();
+    // zeroed, the fields past the count the kernel returns stay initialized.
+    let mut task_vm_info = MaybeUninit::<task_vm_info>::zeroed();
 
     // https://github.com/apple/darwin-xnu/blob/master/osfmk/mach/task_info.h line 396
     // #define TASK_VM_INFO_COUNT	((mach_msg_type_number_t) \
@@ -128,29 +495,649 @@ fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
 
     let kern_ret = unsafe {
         task_info(
-            mach_task_self(),
+            task,
             TASK_VM_INFO,
             task_vm_info.as_mut_ptr() as *mut _,
             &mut task_info_cnt,
         )
     };
     if kern_ret != KERN_SUCCESS {
-        // see https://docs.rs/mach/0.2.3/mach/kern_return/index.html for more details
-        return Err(Error::new(
-            std::io::ErrorKind::Other,
-            format!("DARWIN_KERN_RET_CODE:{}", kern_ret),
-        ));
+        return Err(kern_return_error("task_info", kern_ret));
     }
     let task_vm_info = unsafe { task_vm_info.assume_init() };
+    // a field the kernel didn't fill is reported as 0.
+    let filled = |offset: usize, value: u64| {
+        if task_info_filled(task_info_cnt, offset, std::mem::size_of::<u64>()) {
+            value
+        } else {
+            0
+        }
+    };
     Ok(ProcessMemoryInfo {
-        resident_set_size: task_vm_info.resident_size,
-        resident_set_size_peak: task_vm_info.resident_size_peak,
-        virtual_memory_size: task_vm_info.virtual_size,
-        phys_footprint: task_vm_info.phys_footprint,
-        compressed: task_vm_info.compressed,
+        resident_set_size: filled(
+            offset_of!(task_vm_info, resident_size),
+            task_vm_info.resident_size,
+        ),
+        resident_set_size_peak: filled(
+            offset_of!(task_vm_info, resident_size_peak),
+            task_vm_info.resident_size_peak,
+        ),
+        virtual_memory_size: filled(
+            offset_of!(task_vm_info, virtual_size),
+            task_vm_info.virtual_size,
+        ),
+        phys_footprint: filled(
+            offset_of!(task_vm_info, phys_footprint),
+            task_vm_info.phys_footprint,
+        ),
+        compressed: filled(
+            offset_of!(task_vm_info, compressed),
+            task_vm_info.compressed,
+        ),
     })
 }
 
+/// `mach_task_basic_info` of osfmk/mach/task_info.h, which mach doesn't define.
+#[cfg(all(any(target_os = "macos", target_os = "ios"), feature = "minimal-macos"))]
+#[repr(C, packed(4))]
+#[allow(non_camel_case_types)]
+struct mach_task_basic_info {
+    virtual_size: mach::vm_types::mach_vm_size_t,
+    resident_size: mach::vm_types::mach_vm_size_t,
+    resident_size_max: mach::vm_types::mach_vm_size_t,
+    user_time: [mach::vm_types::integer_t; 2],
+    system_time: [mach::vm_types::integer_t; 2],
+    policy: mach::vm_types::integer_t,
+    suspend_count: mach::vm_types::integer_t,
+}
+
+/// `MACH_TASK_BASIC_INFO` only has the sizes, it is cheaper than `TASK_VM_INFO` and doesn't
+/// need the generated `task_vm_info` binding.
+#[cfg(all(any(target_os = "macos", target_os = "ios"), feature = "minimal-macos"))]
+fn task_memory_info(task: mach::port::mach_port_t) -> Result<ProcessMemoryInfo> {
+    use crate::utils::kern_return::kern_return_error;
+    use mach::{
+        kern_return::KERN_SUCCESS, message::mach_msg_type_number_t, task::task_info,
+        task_info::MACH_TASK_BASIC_INFO, vm_types::natural_t,
+    };
+    use std::mem::{offset_of, MaybeUninit};
+
+    // zeroed, the fields past the count the kernel returns stay initialized.
+    let mut basic_info = MaybeUninit::<mach_task_basic_info>::zeroed();
+    // MACH_TASK_BASIC_INFO_COUNT
+    let mut count = (std::mem::size_of::<mach_task_basic_info>() / std::mem::size_of::<natural_t>())
+        as mach_msg_type_number_t;
+    let kern_ret = unsafe {
+        task_info(
+            task,
+            MACH_TASK_BASIC_INFO,
+            basic_info.as_mut_ptr() as *mut _,
+            &mut count,
+        )
+    };
+    if kern_ret != KERN_SUCCESS {
+        return Err(kern_return_error("task_info", kern_ret));
+    }
+    let basic_info = unsafe { basic_info.assume_init() };
+    // a field the kernel didn't fill is reported as 0.
+    let filled = |offset: usize, value: u64| {
+        if task_info_filled(count, offset, std::mem::size_of::<u64>()) {
+            value
+        } else {
+            0
+        }
+    };
+    Ok(ProcessMemoryInfo {
+        resident_set_size: filled(
+            offset_of!(mach_task_basic_info, resident_size),
+            basic_info.resident_size,
+        ),
+        resident_set_size_peak: filled(
+            offset_of!(mach_task_basic_info, resident_size_max),
+            basic_info.resident_size_max,
+        ),
+        virtual_memory_size: filled(
+            offset_of!(mach_task_basic_info, virtual_size),
+            basic_info.virtual_size,
+        ),
+    })
+}
+
+/// `mach_task_self()` is not a trap, it reads the `mach_task_self_` global libsystem sets at
+/// startup and after `fork`, so there is nothing to cache per thread: a cached port would
+/// only go stale in a forked child.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
+    task_memory_info(unsafe { mach::traps::mach_task_self() })
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn get_process_memory_info_for_pid_impl(pid: u32) -> Result<ProcessMemoryInfo> {
+    use crate::utils::kern_return::task_for_pid_error;
+    use mach::{
+        kern_return::KERN_SUCCESS,
+        mach_port::mach_port_deallocate,
+        port::mach_port_name_t,
+        traps::{mach_task_self, task_for_pid},
+    };
+
+    let mut task: mach_port_name_t = 0;
+    let kern_ret = unsafe { task_for_pid(mach_task_self(), pid as libc::c_int, &mut task) };
+    if kern_ret != KERN_SUCCESS {
+        return Err(task_for_pid_error(pid, kern_ret));
+    }
+    let info = task_memory_info(task);
+    unsafe { mach_port_deallocate(mach_task_self(), task) };
+    info
+}
+
 pub fn get_process_memory_info() -> Result<ProcessMemoryInfo> {
+    #[cfg(feature = "test-util")]
+    if let Some(info) = super::test_util::test_override() {
+        return Ok(info);
+    }
     get_process_memory_info_impl()
 }
+
+/// The quantum in which the OS accounts memory, in bytes.
+///
+/// RSS and VSZ only move in multiples of it: the page size on Linux, Android, MacOS and iOS
+/// (16KiB on Apple silicon), the allocation granularity on Windows, usually 64KiB, which is the
+/// alignment of every region `VirtualAlloc` reserves. A delta smaller than this between two
+/// snapshots is noise, not a change of the memory usage.
+pub fn memory_granularity() -> u64 {
+    memory_granularity_impl()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn memory_granularity_impl() -> u64 {
+    page_size()
+}
+
+#[cfg(target_os = "windows")]
+fn memory_granularity_impl() -> u64 {
+    use std::mem::MaybeUninit;
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+    let mut system_info = MaybeUninit::<SYSTEM_INFO>::uninit();
+    // GetSystemInfo can't fail.
+    let system_info = unsafe {
+        GetSystemInfo(system_info.as_mut_ptr());
+        system_info.assume_init()
+    };
+    system_info.dwAllocationGranularity as u64
+}
+
+/// Get the memory info of the process `pid`.
+///
+/// On MacOS and iOS this goes through `task_for_pid`, which is only granted for other processes
+/// with the proper entitlements or as root; a refusal is reported as `ErrorKind::PermissionDenied`.
+/// On Windows the process is opened with `PROCESS_QUERY_LIMITED_INFORMATION`, plus `PROCESS_VM_READ`
+/// when granted, so that protected processes can be queried too.
+///
+/// A `pid` which doesn't exist is reported as `ErrorKind::NotFound` on every platform but MacOS and iOS.
+pub fn get_process_memory_info_for_pid(pid: u32) -> Result<ProcessMemoryInfo> {
+    get_process_memory_info_for_pid_impl(pid)
+}
+
+/// A builder for memory info queries, for the fields too expensive to be always computed.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::MemoryQuery;
+/// let info = MemoryQuery::new().query().unwrap();
+/// let same = MemoryQuery::new().pid(std::process::id()).query().unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemoryQuery {
+    pid: Option<u32>,
+    #[cfg(target_os = "windows")]
+    virtual_reserved: bool,
+}
+
+impl MemoryQuery {
+    /// Query current process with the default fields, as `get_process_memory_info`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Query the process `pid` instead, as `get_process_memory_info_for_pid`.
+    pub fn pid(mut self, pid: u32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Compute `virtual_reserved` by walking the address space, see its cost there.
+    ///
+    /// Querying another process needs `PROCESS_QUERY_INFORMATION` then, which protected
+    /// processes don't grant.
+    #[cfg(target_os = "windows")]
+    pub fn virtual_reserved(mut self, enabled: bool) -> Self {
+        self.virtual_reserved = enabled;
+        self
+    }
+
+    pub fn query(&self) -> Result<ProcessMemoryInfo> {
+        #[allow(unused_mut)]
+        let mut info = match self.pid {
+            Some(pid) => get_process_memory_info_for_pid(pid)?,
+            None => get_process_memory_info()?,
+        };
+        #[cfg(target_os = "windows")]
+        if self.virtual_reserved {
+            info.virtual_reserved = virtual_reserved_impl(self.pid)?;
+        }
+        Ok(info)
+    }
+}
+
+/// Sum the memory info of `pids`, as far as it can be read.
+///
+/// A failure on a pid doesn't stop the scan: the sum covers the pids which could be read and
+/// every failure is returned along with its pid, typically `ErrorKind::NotFound` for a process
+/// which exited meanwhile.
+pub fn sum_memory(pids: &[u32]) -> (ProcessMemoryInfo, Vec<(u32, crate::PerfError)>) {
+    let mut total = ProcessMemoryInfo::default();
+    let mut errors = vec![];
+    for &pid in pids {
+        match get_process_memory_info_for_pid(pid) {
+            Ok(info) => total.accumulate(&info),
+            Err(e) => errors.push((pid, e.into())),
+        }
+    }
+    (total, errors)
+}
+
+/// Rank processes by memory, the largest RSS first.
+///
+/// Ties are broken by the largest VSZ, then by the smallest pid, so the order is deterministic.
+pub fn sort_by_rss_desc(processes: &mut [(u32, ProcessMemoryInfo)]) {
+    processes.sort_unstable_by(|(pid, info), (other_pid, other)| {
+        other
+            .resident_set_size
+            .cmp(&info.resident_set_size)
+            .then(other.virtual_memory_size.cmp(&info.virtual_memory_size))
+            .then(pid.cmp(other_pid))
+    });
+}
+
+/// Sum the memory info of all processes whose name matches `pattern`,
+/// see [`crate::process::find_processes_by_name`] for the matching rules.
+///
+/// Pages shared between the processes are counted once per process, as `top` would.
+/// Processes which exit during the scan are skipped.
+pub fn aggregate_memory_by_name(pattern: &str) -> Result<ProcessMemoryInfo> {
+    let mut total = ProcessMemoryInfo::default();
+    for pid in crate::process::find_processes_by_name(pattern)? {
+        match get_process_memory_info_for_pid(pid) {
+            Ok(info) => total.accumulate(&info),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+/// return the memory info of every process running the executable at `path`, see
+/// `process::find_processes_by_executable` for the matching.
+///
+/// Several instances may run at once, hence one entry per pid, in no particular order.
+/// Processes exiting during the scan are left out.
+pub fn memory_of_executable(path: &std::path::Path) -> Result<Vec<(u32, ProcessMemoryInfo)>> {
+    let mut memory = vec![];
+    for pid in crate::process::find_processes_by_executable(path)? {
+        match get_process_memory_info_for_pid(pid) {
+            Ok(info) => memory.push((pid, info)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(memory)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory_info_for_pid() {
+        let info = get_process_memory_info_for_pid(std::process::id()).unwrap();
+        assert!(info.resident_set_size > 0);
+        assert!(info.virtual_memory_size >= info.resident_set_size);
+
+        assert!(get_process_memory_info_for_pid(u32::MAX).is_err());
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn test_consecutive_reads() {
+        let task = unsafe { mach::traps::mach_task_self() };
+        let first = get_process_memory_info().unwrap();
+        let second = get_process_memory_info().unwrap();
+        assert_eq!(unsafe { mach::traps::mach_task_self() }, task);
+        assert!(second.resident_set_size_peak >= first.resident_set_size_peak);
+        assert!(first.approx_eq_pct(&second, 10f64));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_parse_auxv_page_size() {
+        let auxv: Vec<u8> = [33usize, 0x1234, 6, 16384, 0, 0, 6, 1]
+            .iter()
+            .flat_map(|word| word.to_ne_bytes())
+            .collect();
+        assert_eq!(parse_auxv_page_size(&auxv), Some(16384));
+        assert_eq!(
+            parse_auxv_page_size(&auxv[..2 * std::mem::size_of::<usize>()]),
+            None
+        );
+
+        let auxv = std::fs::read(crate::procfs::path("self/auxv")).unwrap();
+        let libc_page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        assert_eq!(parse_auxv_page_size(&auxv), Some(libc_page_size));
+        assert_eq!(page_size(), libc_page_size);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_parse_statm() {
+        let info = parse_statm("3000 1000 400 20 0 1500 0\n").unwrap();
+        assert_eq!(info.virtual_memory_size, 3000 * page_size());
+        assert_eq!(info.resident_set_size, 1000 * page_size());
+        assert_eq!(info.shared, 400 * page_size());
+        assert_eq!(info.text, 20 * page_size());
+
+        let info = get_process_memory_info().unwrap();
+        assert!(info.shared <= info.resident_set_size);
+        assert!(info.text > 0);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_memory_info_for_child_no_handle_leak() {
+        use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessHandleCount};
+
+        fn handle_count() -> u32 {
+            let mut count = 0;
+            assert_ne!(
+                unsafe { GetProcessHandleCount(GetCurrentProcess(), &mut count) },
+                0
+            );
+            count
+        }
+
+        let mut child = std::process::Command::new("cmd")
+            .args(["/C", "ping -n 30 127.0.0.1 > NUL"])
+            .spawn()
+            .unwrap();
+        let before = handle_count();
+        for _ in 0..1000 {
+            let info = get_process_memory_info_for_pid(child.id()).unwrap();
+            assert!(info.resident_set_size > 0);
+            let err = get_process_memory_info_for_pid(u32::MAX).err().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        }
+        // leave some room for handles opened by other tests meanwhile.
+        assert!(handle_count() < before + 100);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_changed_fields() {
+        let before = ProcessMemoryInfo {
+            resident_set_size: 100 << 20,
+            virtual_memory_size: 1 << 30,
+            ..Default::default()
+        };
+        assert!(before.changed_fields(&before).is_empty());
+
+        let after = ProcessMemoryInfo {
+            resident_set_size: 96 << 20,
+            ..before.clone()
+        };
+        assert_eq!(
+            before.changed_fields(&after),
+            vec![("resident_set_size", -(4 << 20))]
+        );
+        assert_eq!(
+            after.changed_fields(&before),
+            vec![("resident_set_size", 4 << 20)]
+        );
+
+        let huge = ProcessMemoryInfo {
+            virtual_memory_size: u64::MAX,
+            ..before.clone()
+        };
+        assert_eq!(
+            ProcessMemoryInfo::default().changed_fields(&huge)[1],
+            ("virtual_memory_size", i64::MAX)
+        );
+    }
+
+    fn set_field(info: &mut ProcessMemoryInfo, name: &str, value: u64) {
+        let field = match name {
+            "resident_set_size" => &mut info.resident_set_size,
+            #[cfg(not(any(target_os = "android", target_os = "linux")))]
+            "resident_set_size_peak" => &mut info.resident_set_size_peak,
+            "virtual_memory_size" => &mut info.virtual_memory_size,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            "shared" => &mut info.shared,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            "text" => &mut info.text,
+            #[cfg(target_os = "windows")]
+            "virtual_reserved" => &mut info.virtual_reserved,
+            #[cfg(all(
+                any(target_os = "macos", target_os = "ios"),
+                not(feature = "minimal-macos")
+            ))]
+            "phys_footprint" => &mut info.phys_footprint,
+            #[cfg(all(
+                any(target_os = "macos", target_os = "ios"),
+                not(feature = "minimal-macos")
+            ))]
+            "compressed" => &mut info.compressed,
+            _ => panic!("no field {}", name),
+        };
+        *field = value;
+    }
+
+    // every field of this platform at 100MiB.
+    fn uniform() -> ProcessMemoryInfo {
+        let mut info = ProcessMemoryInfo::default();
+        for (name, _) in ProcessMemoryInfo::default().fields() {
+            set_field(&mut info, name, 100 << 20);
+        }
+        info
+    }
+
+    #[test]
+    fn test_approx_eq_fields() {
+        let base = uniform();
+        for (name, value) in base.fields() {
+            let mut other = base.clone();
+            set_field(&mut other, name, value + 4096);
+            assert!(base.approx_eq(&other, 4096), "{}", name);
+            assert!(other.approx_eq(&base, 4096), "{}", name);
+            assert!(!base.approx_eq(&other, 4095), "{}", name);
+
+            set_field(&mut other, name, value + (1 << 20));
+            assert!(base.approx_eq_pct(&other, 1.0), "{}", name);
+            assert!(!base.approx_eq_pct(&other, 0.5), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let base = ProcessMemoryInfo {
+            resident_set_size: 100 << 20,
+            virtual_memory_size: 1 << 30,
+            ..Default::default()
+        };
+        assert!(base.approx_eq(&base, 0));
+
+        let mut rss = base.clone();
+        rss.resident_set_size += 4096;
+        assert!(base.approx_eq(&rss, 4096));
+        assert!(rss.approx_eq(&base, 4096));
+        assert!(!base.approx_eq(&rss, 4095));
+
+        let mut vsz = base.clone();
+        vsz.virtual_memory_size -= 8192;
+        assert!(base.approx_eq(&vsz, 8192));
+        assert!(!base.approx_eq(&vsz, 4096));
+
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        {
+            let mut peak = base.clone();
+            peak.resident_set_size_peak = 1;
+            assert!(base.approx_eq(&peak, 1));
+            assert!(!base.approx_eq(&peak, 0));
+        }
+    }
+
+    #[test]
+    fn test_approx_eq_pct() {
+        let base = ProcessMemoryInfo {
+            resident_set_size: 100 << 20,
+            virtual_memory_size: 1 << 30,
+            ..Default::default()
+        };
+        let mut other = base.clone();
+        other.resident_set_size = 101 << 20;
+        assert!(base.approx_eq_pct(&other, 1.0));
+        assert!(!base.approx_eq_pct(&other, 0.5));
+
+        other.virtual_memory_size = (1 << 30) + (20 << 20);
+        assert!(base.approx_eq_pct(&other, 2.0));
+        assert!(!base.approx_eq_pct(&other, 1.0));
+        assert!(ProcessMemoryInfo::default().approx_eq_pct(&ProcessMemoryInfo::default(), 0.0));
+    }
+
+    #[test]
+    fn test_task_info_filled() {
+        // the offsets of task_vm_info, in bytes.
+        let (resident_size_peak, compressed, phys_footprint) = (24, 120, 144);
+        // TASK_VM_INFO_REV0_COUNT stops right before phys_footprint.
+        assert!(task_info_filled(36, compressed, 8));
+        assert!(!task_info_filled(36, phys_footprint, 8));
+        assert!(task_info_filled(38, phys_footprint, 8));
+        // half a field isn't filled.
+        assert!(!task_info_filled(37, phys_footprint, 8));
+        assert!(!task_info_filled(7, resident_size_peak, 8));
+        assert!(!task_info_filled(0, 0, 8));
+    }
+
+    #[test]
+    fn test_unbacked() {
+        let info = ProcessMemoryInfo {
+            resident_set_size: 100 << 20,
+            virtual_memory_size: 1 << 30,
+            ..Default::default()
+        };
+        assert_eq!(info.unbacked(), (1 << 30) - (100 << 20));
+
+        let info = ProcessMemoryInfo {
+            resident_set_size: 2 << 20,
+            virtual_memory_size: 1 << 20,
+            ..Default::default()
+        };
+        assert_eq!(info.unbacked(), 0);
+    }
+
+    #[test]
+    fn test_sum_memory() {
+        let pid = std::process::id();
+        let (total, errors) = sum_memory(&[pid, u32::MAX]);
+        assert!(total.resident_set_size > 0);
+        // the other tests allocate meanwhile, only the text of the binary is stable.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert_eq!(
+            total.text,
+            get_process_memory_info_for_pid(pid).unwrap().text
+        );
+        // the valid pid has no error, the bogus one exactly one.
+        let pids: Vec<u32> = errors.iter().map(|(pid, _)| *pid).collect();
+        assert_eq!(pids, [u32::MAX]);
+
+        let (total, errors) = sum_memory(&[]);
+        assert_eq!(total.resident_set_size, 0);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_rss_desc() {
+        let info = |rss, vsz| ProcessMemoryInfo {
+            resident_set_size: rss,
+            virtual_memory_size: vsz,
+            ..Default::default()
+        };
+        let mut processes = vec![
+            (4, info(10, 100)),
+            (3, info(30, 100)),
+            (2, info(10, 200)),
+            (1, info(10, 100)),
+        ];
+        sort_by_rss_desc(&mut processes);
+        let pids: Vec<u32> = processes.iter().map(|(pid, _)| *pid).collect();
+        assert_eq!(pids, [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn test_memory_query() {
+        let info = MemoryQuery::new().query().unwrap();
+        assert!(info.resident_set_size > 0);
+        let info = MemoryQuery::new().pid(std::process::id()).query().unwrap();
+        assert!(info.resident_set_size > 0);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_virtual_reserved() {
+        let info = get_process_memory_info().unwrap();
+        assert_eq!(info.virtual_reserved, 0);
+
+        for query in [
+            MemoryQuery::new(),
+            MemoryQuery::new().pid(std::process::id()),
+        ] {
+            let info = query.virtual_reserved(true).query().unwrap();
+            assert!(info.virtual_reserved >= info.virtual_memory_size);
+        }
+    }
+
+    #[test]
+    fn test_memory_granularity() {
+        let granularity = memory_granularity();
+        assert!(granularity.is_power_of_two());
+        assert!(granularity >= 4096);
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn test_aggregate_memory_by_name() {
+        let exe = std::env::current_exe().unwrap();
+        let name: String = exe
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .chars()
+            .take(10)
+            .collect();
+        let total = aggregate_memory_by_name(&name).unwrap();
+        assert!(total.resident_set_size > 0);
+
+        let total = aggregate_memory_by_name("no-such-process-name").unwrap();
+        assert_eq!(total.resident_set_size, 0);
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn test_memory_of_executable() {
+        let exe = std::env::current_exe().unwrap();
+        let memory = memory_of_executable(&exe).unwrap();
+        let (_, info) = memory
+            .iter()
+            .find(|(pid, _)| *pid == std::process::id())
+            .unwrap();
+        assert!(info.resident_set_size > 0);
+    }
+}
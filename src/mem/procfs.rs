@@ -0,0 +1,433 @@
+//! Parsers of the `/proc` files read by this crate, usable on captured contents on any platform.
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result},
+    str::FromStr,
+};
+
+use super::system_memory_info::parse_kib;
+
+/// The content of `/proc/[pid]/statm`, all values are in **pages**.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcStatm {
+    /// total program size, as `VmSize` in `/proc/[pid]/status`.
+    pub size: u64,
+    /// resident set size, as `VmRSS` in `/proc/[pid]/status`.
+    pub resident: u64,
+    /// resident shared pages, backed by a file, as `RssFile + RssShmem`.
+    pub shared: u64,
+    /// text (code).
+    pub text: u64,
+    /// data + stack.
+    pub data: u64,
+}
+
+impl FromStr for ProcStatm {
+    type Err = Error;
+
+    /// See <https://man7.org/linux/man-pages/man5/proc_pid_statm.5.html> for the format.
+    fn from_str(statm: &str) -> Result<Self> {
+        let mut parts = statm.split_whitespace();
+        let mut next = |field: &str| {
+            parts
+                .next()
+                .and_then(|s| s.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Invalid {} in statm", field),
+                    )
+                })
+        };
+        let size = next("VmSize")?;
+        let resident = next("VmRSS")?;
+        let shared = next("shared")?;
+        let text = next("text")?;
+        // lib, unused since Linux 2.6.
+        next("lib")?;
+        let data = next("data")?;
+        Ok(ProcStatm {
+            size,
+            resident,
+            shared,
+            text,
+            data,
+        })
+    }
+}
+
+impl TryFrom<&str> for ProcStatm {
+    type Error = Error;
+
+    fn try_from(statm: &str) -> Result<Self> {
+        statm.parse()
+    }
+}
+
+/// The memory related fields of `/proc/[pid]/status`, memory values are in **bytes**.
+///
+/// The `Vm*` and `Rss*` fields are `None` for kernel threads, which have no address space,
+/// and for fields older kernels don't report.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcStatus {
+    /// the command name, `comm`.
+    pub name: String,
+    pub pid: u32,
+    pub ppid: u32,
+    pub threads: u64,
+    /// peak virtual memory size.
+    pub vm_peak: Option<u64>,
+    /// virtual memory size.
+    pub vm_size: Option<u64>,
+    /// peak resident set size, the "high water mark".
+    pub vm_hwm: Option<u64>,
+    /// memory locked in RAM, as `mlock` does.
+    pub vm_lck: Option<u64>,
+    /// resident set size, `rss_anon + rss_file + rss_shmem`.
+    pub vm_rss: Option<u64>,
+    /// resident anonymous memory.
+    pub rss_anon: Option<u64>,
+    /// resident file mappings.
+    pub rss_file: Option<u64>,
+    /// resident shared memory, including System V shm, shmem on tmpfs and shared anonymous mappings.
+    pub rss_shmem: Option<u64>,
+    /// size of the private data segments, the heap and anonymous mappings.
+    pub vm_data: Option<u64>,
+    /// size of the stack of the main thread.
+    pub vm_stk: Option<u64>,
+    /// size of the text segments of the executable.
+    pub vm_exe: Option<u64>,
+    /// size of the code of the shared libraries.
+    pub vm_lib: Option<u64>,
+    /// anonymous memory swapped out.
+    pub vm_swap: Option<u64>,
+    /// the context switches of the main thread only when the file is `/proc/[pid]/status`,
+    /// `/proc/[pid]/task/[tid]/status` has the ones of each thread.
+    pub voluntary_ctxt_switches: u64,
+    pub nonvoluntary_ctxt_switches: u64,
+}
+
+impl FromStr for ProcStatus {
+    type Err = Error;
+
+    /// See <https://man7.org/linux/man-pages/man5/proc_pid_status.5.html> for the format.
+    fn from_str(status: &str) -> Result<Self> {
+        let mut info = ProcStatus::default();
+        let mut name = None;
+        let mut pid = None;
+        for line in status.lines() {
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let invalid = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid {} in status", field),
+                )
+            };
+            let number = || value.trim().parse().map_err(|_| invalid());
+            let pid_number = || value.trim().parse::<u32>().map_err(|_| invalid());
+            let bytes = || parse_kib(value).map(|kib| kib.bytes()).ok_or_else(invalid);
+            match field {
+                "Name" => name = Some(value.trim().to_string()),
+                "Pid" => pid = Some(pid_number()?),
+                "PPid" => info.ppid = pid_number()?,
+                "Threads" => info.threads = number()?,
+                "VmPeak" => info.vm_peak = Some(bytes()?),
+                "VmSize" => info.vm_size = Some(bytes()?),
+                "VmLck" => info.vm_lck = Some(bytes()?),
+                "VmHWM" => info.vm_hwm = Some(bytes()?),
+                "VmRSS" => info.vm_rss = Some(bytes()?),
+                "RssAnon" => info.rss_anon = Some(bytes()?),
+                "RssFile" => info.rss_file = Some(bytes()?),
+                "RssShmem" => info.rss_shmem = Some(bytes()?),
+                "VmData" => info.vm_data = Some(bytes()?),
+                "VmStk" => info.vm_stk = Some(bytes()?),
+                "VmExe" => info.vm_exe = Some(bytes()?),
+                "VmLib" => info.vm_lib = Some(bytes()?),
+                "VmSwap" => info.vm_swap = Some(bytes()?),
+                "voluntary_ctxt_switches" => info.voluntary_ctxt_switches = number()?,
+                "nonvoluntary_ctxt_switches" => info.nonvoluntary_ctxt_switches = number()?,
+                _ => continue,
+            }
+        }
+        info.name =
+            name.ok_or_else(|| Error::new(ErrorKind::InvalidData, "Missing Name in status"))?;
+        info.pid =
+            pid.ok_or_else(|| Error::new(ErrorKind::InvalidData, "Missing Pid in status"))?;
+        Ok(info)
+    }
+}
+
+impl TryFrom<&str> for ProcStatus {
+    type Error = Error;
+
+    fn try_from(status: &str) -> Result<Self> {
+        status.parse()
+    }
+}
+
+/// A borrowed view of the content of `/proc/[pid]/status`, parsing the fields on demand.
+///
+/// Nothing is parsed nor allocated up front: each accessor scans the lines for its field, so
+/// reading one or two fields costs less than parsing a whole `ProcStatus`. Sizes are in
+/// **bytes**, `None` for a missing or invalid field.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::ProcStatusView;
+/// let view = ProcStatusView::new("Name:\tcat\nVmRSS:\t  1024 kB\n");
+/// assert_eq!(view.name(), Some("cat"));
+/// assert_eq!(view.vm_rss(), Some(1024 * 1024));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ProcStatusView<'a> {
+    status: &'a str,
+}
+
+impl<'a> ProcStatusView<'a> {
+    pub fn new(status: &'a str) -> Self {
+        ProcStatusView { status }
+    }
+
+    /// The raw value of `field`, without the surrounding whitespace.
+    pub fn field(&self, field: &str) -> Option<&'a str> {
+        self.status.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key == field).then(|| value.trim())
+        })
+    }
+
+    /// The size `field`, like `VmRSS`, converted from kB to bytes.
+    pub fn bytes(&self, field: &str) -> Option<u64> {
+        self.field(field).and_then(parse_kib).map(|kib| kib.bytes())
+    }
+
+    /// The number `field`, like `Threads`.
+    pub fn number(&self, field: &str) -> Option<u64> {
+        self.field(field)?.parse().ok()
+    }
+
+    pub fn name(&self) -> Option<&'a str> {
+        self.field("Name")
+    }
+
+    pub fn threads(&self) -> Option<u64> {
+        self.number("Threads")
+    }
+
+    pub fn vm_size(&self) -> Option<u64> {
+        self.bytes("VmSize")
+    }
+
+    pub fn vm_rss(&self) -> Option<u64> {
+        self.bytes("VmRSS")
+    }
+
+    pub fn vm_hwm(&self) -> Option<u64> {
+        self.bytes("VmHWM")
+    }
+
+    pub fn vm_swap(&self) -> Option<u64> {
+        self.bytes("VmSwap")
+    }
+}
+
+/// Read `/proc/self/status` into `buf`, replacing its content, and view it, on Linux and
+/// Android.
+///
+/// Once `buf` has grown to the size of the file, sampling allocates nothing. This reads
+/// directly, without the timeout of `crate::procfs::set_read_timeout`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn read_proc_status(buf: &mut String) -> Result<ProcStatusView<'_>> {
+    use std::io::Read;
+    buf.clear();
+    std::fs::File::open(crate::procfs::path("self/status"))?.read_to_string(buf)?;
+    Ok(ProcStatusView::new(buf))
+}
+
+/// Parse `/proc/self/status` at once, in a single read, on Linux and Android.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_proc_status() -> Result<ProcStatus> {
+    crate::procfs::read_to_string("self/status")?.parse()
+}
+
+/// The content of `/proc/meminfo`, see `parse_meminfo`.
+pub type MemInfo = super::SystemMemoryInfo;
+
+impl FromStr for MemInfo {
+    type Err = Error;
+
+    fn from_str(meminfo: &str) -> Result<Self> {
+        super::parse_meminfo(meminfo)
+    }
+}
+
+impl TryFrom<&str> for MemInfo {
+    type Error = Error;
+
+    fn try_from(meminfo: &str) -> Result<Self> {
+        meminfo.parse()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STATUS: &str = "\
+Name:\tpostgres
+Umask:\t0077
+State:\tS (sleeping)
+Tgid:\t1042
+Ngid:\t0
+Pid:\t1042
+PPid:\t1
+TracerPid:\t0
+Uid:\t999\t999\t999\t999
+FDSize:\t64
+Groups:\t999
+VmPeak:\t  221112 kB
+VmSize:\t  221076 kB
+VmLck:\t       0 kB
+VmPin:\t       0 kB
+VmHWM:\t   29792 kB
+VmRSS:\t   29620 kB
+RssAnon:\t    2868 kB
+RssFile:\t   12452 kB
+RssShmem:\t   14300 kB
+VmData:\t    1176 kB
+VmStk:\t     132 kB
+VmExe:\t    5956 kB
+VmLib:\t   13512 kB
+VmPTE:\t      88 kB
+VmSwap:\t       0 kB
+Threads:\t1
+SigQ:\t0/63412
+voluntary_ctxt_switches:\t1555
+nonvoluntary_ctxt_switches:\t21
+";
+
+    #[test]
+    fn test_proc_statm() {
+        let statm: ProcStatm = "55060 7405 3575 1543 0 4914 0\n".parse().unwrap();
+        assert_eq!(
+            statm,
+            ProcStatm {
+                size: 55060,
+                resident: 7405,
+                shared: 3575,
+                text: 1543,
+                data: 4914,
+            }
+        );
+        assert_eq!(
+            ProcStatm::try_from("55060 7405 3575 1543 0 4914 0").unwrap(),
+            statm
+        );
+        let err = "55060".parse::<ProcStatm>().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid VmRSS in statm");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_proc_status() {
+        let status: ProcStatus = STATUS.parse().unwrap();
+        assert_eq!(
+            status,
+            ProcStatus {
+                name: "postgres".to_string(),
+                pid: 1042,
+                ppid: 1,
+                threads: 1,
+                vm_peak: Some(221112 * 1024),
+                vm_size: Some(221076 * 1024),
+                vm_lck: Some(0),
+                vm_hwm: Some(29792 * 1024),
+                vm_rss: Some(29620 * 1024),
+                rss_anon: Some(2868 * 1024),
+                rss_file: Some(12452 * 1024),
+                rss_shmem: Some(14300 * 1024),
+                vm_data: Some(1176 * 1024),
+                vm_stk: Some(132 * 1024),
+                vm_exe: Some(5956 * 1024),
+                vm_lib: Some(13512 * 1024),
+                vm_swap: Some(0),
+                voluntary_ctxt_switches: 1555,
+                nonvoluntary_ctxt_switches: 21,
+            }
+        );
+
+        // a kernel thread.
+        let kthread =
+            ProcStatus::try_from("Name:\tkworker/0:1\nPid:\t25\nPPid:\t2\nThreads:\t1\n").unwrap();
+        assert_eq!(kthread.name, "kworker/0:1");
+        assert_eq!(kthread.vm_rss, None);
+
+        assert!("Name:\tx\nPid:\t1\nVmRSS:\t12 MB\n"
+            .parse::<ProcStatus>()
+            .is_err());
+        let err = "Name:\tx\n".parse::<ProcStatus>().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        // a pid out of the range of u32 is invalid rather than truncated.
+        let err = "Name:\tx\nPid:\t4294967297\n"
+            .parse::<ProcStatus>()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid Pid in status");
+    }
+
+    #[test]
+    fn test_proc_status_view() {
+        let view = ProcStatusView::new(STATUS);
+        // borrowed from the fixture, not copied.
+        let name = view.name().unwrap();
+        assert_eq!(name, "postgres");
+        assert!(STATUS.as_bytes().as_ptr_range().contains(&name.as_ptr()));
+        assert_eq!(view.field("State"), Some("S (sleeping)"));
+        assert_eq!(view.vm_rss(), Some(29620 * 1024));
+        assert_eq!(view.vm_hwm(), Some(29792 * 1024));
+        assert_eq!(view.vm_swap(), Some(0));
+        assert_eq!(view.threads(), Some(1));
+        assert_eq!(view.number("voluntary_ctxt_switches"), Some(1555));
+        assert_eq!(view.field("VmNone"), None);
+        // Vm prefixes VmRSS but isn't a field.
+        assert_eq!(view.bytes("Vm"), None);
+        assert_eq!(view.bytes("Name"), None);
+    }
+
+    #[test]
+    fn test_meminfo() {
+        let meminfo: MemInfo = "MemTotal:       16384 kB\nMemFree:         1024 kB\n"
+            .parse()
+            .unwrap();
+        assert_eq!(meminfo.total, 16384 * 1024);
+        assert!(MemInfo::try_from("MemFree: 1 kB").is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_live_files() {
+        let statm: ProcStatm = std::fs::read_to_string(crate::procfs::path("self/statm"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(statm.resident > 0);
+        let status: ProcStatus = std::fs::read_to_string(crate::procfs::path("self/status"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(status.pid, std::process::id());
+        assert!(status.vm_rss.unwrap() > 0);
+
+        let mut buf = String::new();
+        let view = read_proc_status(&mut buf).unwrap();
+        assert_eq!(view.number("Pid"), Some(std::process::id() as u64));
+        let capacity = buf.capacity();
+        read_proc_status(&mut buf).unwrap();
+        assert_eq!(buf.capacity(), capacity);
+
+        let status = get_proc_status().unwrap();
+        assert!(status.threads > 0);
+        assert!(status.vm_exe.unwrap() > 0);
+        assert!(status.vm_size >= status.vm_rss);
+    }
+}
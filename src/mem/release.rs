@@ -0,0 +1,28 @@
+use std::io::Result;
+
+/// Ask the allocator to return its free pages to the OS, so that the next
+/// `get_process_memory_info` reflects memory which was freed but kept cached.
+///
+/// glibc keeps freed memory in its arenas, this calls [`malloc_trim(0)`][malloc_trim] which
+/// releases the free pages of every arena. It only applies to the system allocator: other
+/// allocators (musl's, jemalloc, mimalloc ...) are not affected, and on non-glibc targets
+/// this returns `ErrorKind::Unsupported`.
+///
+/// [malloc_trim]: https://man7.org/linux/man-pages/man3/malloc_trim.3.html
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub fn release_free_memory() -> Result<()> {
+    // returns 1 if memory was actually released, 0 if there was nothing to release.
+    unsafe { libc::malloc_trim(0) };
+    Ok(())
+}
+
+/// Ask the allocator to return its free pages to the OS.
+///
+/// Only glibc provides `malloc_trim`, this target returns `ErrorKind::Unsupported`.
+#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+pub fn release_free_memory() -> Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "malloc_trim is only available with glibc",
+    ))
+}
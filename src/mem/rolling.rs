@@ -0,0 +1,170 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use super::ProcessMemoryInfo;
+
+/// Statistics over the RSS samples of a rolling window, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RollingStats {
+    pub count: usize,
+    pub avg: f64,
+    pub min: u64,
+    pub max: u64,
+}
+
+/// The last `capacity` RSS samples, pushing past it evicts the oldest one.
+#[derive(Clone, Debug)]
+pub struct RollingMemory {
+    capacity: usize,
+    samples: VecDeque<u64>,
+    sum: u128,
+}
+
+impl RollingMemory {
+    /// `capacity` is at least 1.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        RollingMemory {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            sum: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn push(&mut self, info: &ProcessMemoryInfo) {
+        self.push_rss(info.resident_set_size);
+    }
+
+    fn push_rss(&mut self, rss: u64) {
+        if self.samples.len() == self.capacity {
+            if let Some(oldest) = self.samples.pop_front() {
+                self.sum -= oldest as u128;
+            }
+        }
+        self.samples.push_back(rss);
+        self.sum += rss as u128;
+    }
+
+    /// `None` until a sample is pushed.
+    pub fn stats(&self) -> Option<RollingStats> {
+        let min = *self.samples.iter().min()?;
+        let max = *self.samples.iter().max()?;
+        Some(RollingStats {
+            count: self.samples.len(),
+            avg: self.sum as f64 / self.samples.len() as f64,
+            min,
+            max,
+        })
+    }
+}
+
+/// A `RollingMemory` shared between threads, clones push into the same window.
+///
+/// Pushes and reads take a mutex, so `snapshot_stats` always computes its statistics over one
+/// state of the window, never over a window half updated by a concurrent `push`.
+#[derive(Clone, Debug)]
+pub struct SyncRollingMemory {
+    inner: Arc<Mutex<RollingMemory>>,
+}
+
+impl SyncRollingMemory {
+    pub fn new(capacity: usize) -> Self {
+        SyncRollingMemory {
+            inner: Arc::new(Mutex::new(RollingMemory::new(capacity))),
+        }
+    }
+
+    pub fn push(&self, info: &ProcessMemoryInfo) {
+        self.lock().push(info);
+    }
+
+    pub fn snapshot_stats(&self) -> Option<RollingStats> {
+        self.lock().stats()
+    }
+
+    /// A copy of the window, to compute something else than `RollingStats` out of the lock.
+    pub fn snapshot(&self) -> RollingMemory {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RollingMemory> {
+        // nothing panics while the lock is held, the window is consistent even if poisoned.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(rss: u64) -> ProcessMemoryInfo {
+        ProcessMemoryInfo {
+            resident_set_size: rss,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rolling_memory() {
+        let mut rolling = RollingMemory::new(3);
+        assert_eq!(rolling.stats(), None);
+        for rss in [10, 20, 30, 40] {
+            rolling.push(&info(rss));
+        }
+        assert_eq!(
+            rolling.stats(),
+            Some(RollingStats {
+                count: 3,
+                avg: 30.0,
+                min: 20,
+                max: 40,
+            })
+        );
+        assert_eq!(RollingMemory::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn test_sync_rolling_memory() {
+        const CAPACITY: usize = 64;
+        let rolling = SyncRollingMemory::new(CAPACITY);
+        let writers: Vec<_> = (1..=4u64)
+            .map(|thread| {
+                let rolling = rolling.clone();
+                std::thread::spawn(move || {
+                    for i in 0..10_000 {
+                        rolling.push(&info(thread * 1000 + i % 7));
+                    }
+                })
+            })
+            .collect();
+
+        let mut reads = 0;
+        while writers.iter().any(|w| !w.is_finished()) || reads == 0 {
+            if let Some(stats) = rolling.snapshot_stats() {
+                assert!(stats.count >= 1 && stats.count <= CAPACITY);
+                assert!(stats.min >= 1000 && stats.max <= 4006);
+                assert!(stats.min as f64 <= stats.avg && stats.avg <= stats.max as f64);
+                reads += 1;
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(rolling.snapshot_stats().unwrap().count, CAPACITY);
+        assert_eq!(rolling.snapshot().len(), CAPACITY);
+    }
+}
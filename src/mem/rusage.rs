@@ -0,0 +1,36 @@
+use std::io::{Error, Result};
+
+/// Get the peak RSS of current process from `getrusage(RUSAGE_SELF)`, in bytes.
+///
+/// It needs no procfs, so it works in sandboxes without `/proc` and on the BSDs. `ru_maxrss` is
+/// in KiB on Linux, android and the BSDs, but in bytes on MacOS and iOS: the value is
+/// normalized to bytes. It is the peak of the whole run, except on Linux where
+/// `reset_peak_rss` resets it too.
+pub fn get_rusage_memory() -> Result<u64> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    let unit = if cfg!(any(target_os = "macos", target_os = "ios")) {
+        1
+    } else {
+        1024
+    };
+    Ok(usage.ru_maxrss.max(0) as u64 * unit)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_rusage_memory() {
+        const SIZE: usize = 32 << 20;
+        let buf = std::hint::black_box(vec![1u8; SIZE]);
+        let peak = get_rusage_memory().unwrap();
+        drop(buf);
+        // a unit mismatch would be off by 1024 either way.
+        assert!(peak >= SIZE as u64, "{}", peak);
+        assert!(peak < 1 << 40, "{}", peak);
+    }
+}
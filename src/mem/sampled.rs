@@ -0,0 +1,75 @@
+use super::ProcessMemoryInfo;
+use std::time::{Duration, Instant};
+
+/// A sample of a sequenced monitor or stream, with what is needed to detect scheduling gaps.
+#[derive(Clone)]
+pub struct SampledMemory {
+    /// the number of the read, counting from 0. Failed reads are not delivered but still
+    /// numbered, a jump of the sequence is a lost sample.
+    pub seq: u64,
+    /// when the sample was read.
+    pub at: Instant,
+    /// time since the previous sample delivered, `Duration::ZERO` for the first one. Much
+    /// longer than the sampling interval when the sampler was delayed, e.g. starved of CPU or
+    /// paused by the runtime.
+    pub interval: Duration,
+    pub info: ProcessMemoryInfo,
+}
+
+/// Numbers the reads of a sampler and measures the intervals between them.
+#[derive(Default)]
+pub(crate) struct Sequencer {
+    next: u64,
+    last: Option<Instant>,
+}
+
+impl Sequencer {
+    /// Number the read which returned `info` at `at`, its error if it failed: the failed read
+    /// takes a number, but not the start of the next interval.
+    pub(crate) fn tag<E>(
+        &mut self,
+        at: Instant,
+        info: std::result::Result<ProcessMemoryInfo, E>,
+    ) -> std::result::Result<SampledMemory, E> {
+        let seq = self.next;
+        self.next += 1;
+        let info = info?;
+        let interval = self
+            .last
+            .map_or(Duration::ZERO, |last| at.saturating_duration_since(last));
+        self.last = Some(at);
+        Ok(SampledMemory {
+            seq,
+            at,
+            interval,
+            info,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequencer() {
+        let start = Instant::now();
+        let mut sequencer = Sequencer::default();
+        let first = sequencer
+            .tag::<()>(start, Ok(ProcessMemoryInfo::default()))
+            .unwrap();
+        assert_eq!((first.seq, first.interval), (0, Duration::ZERO));
+
+        assert!(sequencer
+            .tag(start + Duration::from_secs(1), Err(()))
+            .is_err());
+        let third = sequencer
+            .tag::<()>(
+                start + Duration::from_secs(2),
+                Ok(ProcessMemoryInfo::default()),
+            )
+            .unwrap();
+        // the failed read is numbered, the interval spans it.
+        assert_eq!((third.seq, third.interval), (2, Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,164 @@
+//! Dump the memory info when a signal is received.
+//!
+//! A signal handler may only call async-signal-safe functions, which
+//! `get_process_memory_info` is not: it allocates and opens files. So the handler only
+//! writes a byte to a pipe, `write` being async-signal-safe, and a helper thread blocked on
+//! the other end of the pipe reads the memory info and calls the sink, outside of the signal
+//! context. The write end is non-blocking: when signals arrive faster than the thread dumps,
+//! the extra ones are coalesced instead of blocking the handler. The handler saves and
+//! restores `errno`, which `write` may change under the interrupted code.
+use std::{
+    io::{Error, ErrorKind, Result},
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use super::{get_process_memory_info, ProcessMemoryInfo};
+use crate::utils::errno::errno;
+
+/// Above the real-time signals of every supported platform.
+const MAX_SIGNAL: usize = 65;
+
+/// The write end of the pipe of each signal with a dump installed, -1 for none.
+static PIPES: [AtomicI32; MAX_SIGNAL] = [const { AtomicI32::new(-1) }; MAX_SIGNAL];
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    let Some(pipe) = PIPES.get(signal as usize) else {
+        return;
+    };
+    let fd = pipe.load(Ordering::Relaxed);
+    if fd < 0 {
+        return;
+    }
+    unsafe {
+        let saved = *errno();
+        libc::write(fd, b"\0".as_ptr() as *const libc::c_void, 1);
+        *errno() = saved;
+    }
+}
+
+fn set_flags(fd: libc::c_int, flags: libc::c_int) -> Result<()> {
+    unsafe {
+        if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0 {
+            return Err(Error::last_os_error());
+        }
+        let status = libc::fcntl(fd, libc::F_GETFL);
+        if status < 0 || libc::fcntl(fd, libc::F_SETFL, status | flags) != 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn close_pipe(read: libc::c_int, write: libc::c_int) {
+    unsafe {
+        libc::close(read);
+        libc::close(write);
+    }
+}
+
+/// Call `sink` with the memory info of current process every time `signal` is received,
+/// e.g. `libc::SIGUSR1` for `kill -USR1 <pid>`.
+///
+/// The sink is called on a dedicated thread, see the module documentation. A signal can only
+/// have one dump, and it stays installed for the lifetime of the process. Signals received
+/// faster than the dumps are done are coalesced, and failed reads are skipped.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::install_signal_dump;
+/// install_signal_dump(libc::SIGUSR1, |info| {
+///     eprintln!("rss: {} bytes", info.resident_set_size);
+/// })
+/// .unwrap();
+/// ```
+pub fn install_signal_dump(
+    signal: i32,
+    sink: impl Fn(ProcessMemoryInfo) + Send + 'static,
+) -> Result<()> {
+    if signal <= 0
+        || signal as usize >= MAX_SIGNAL
+        || signal == libc::SIGKILL
+        || signal == libc::SIGSTOP
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Signal {} can't be handled", signal),
+        ));
+    }
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    let [read, write] = fds;
+    if let Err(e) = set_flags(read, 0).and_then(|_| set_flags(write, libc::O_NONBLOCK)) {
+        close_pipe(read, write);
+        return Err(e);
+    }
+    if PIPES[signal as usize]
+        .compare_exchange(-1, write, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        close_pipe(read, write);
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("A dump is already installed for signal {}", signal),
+        ));
+    }
+
+    let thread = std::thread::Builder::new()
+        .name("perf-signal-dump".to_string())
+        .spawn(move || {
+            let mut buf = [0u8; 64];
+            loop {
+                let n =
+                    unsafe { libc::read(read, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                if n > 0 {
+                    if let Ok(info) = get_process_memory_info() {
+                        sink(info);
+                    }
+                } else if n == 0 || Error::last_os_error().kind() != ErrorKind::Interrupted {
+                    break;
+                }
+            }
+        });
+    if let Err(e) = thread {
+        PIPES[signal as usize].store(-1, Ordering::SeqCst);
+        close_pipe(read, write);
+        return Err(e);
+    }
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+    if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+        // the dump thread blocked on the pipe keeps it alive, leave both ends open.
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    fn test_signal_dump() {
+        let (tx, rx) = mpsc::channel();
+        install_signal_dump(libc::SIGUSR2, move |info| {
+            let _ = tx.send(info);
+        })
+        .unwrap();
+        for _ in 0..2 {
+            assert_eq!(unsafe { libc::raise(libc::SIGUSR2) }, 0);
+            let info = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(info.resident_set_size > 0);
+        }
+
+        let err = install_signal_dump(libc::SIGUSR2, |_| {}).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        let err = install_signal_dump(libc::SIGKILL, |_| {}).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
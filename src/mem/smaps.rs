@@ -0,0 +1,392 @@
+//! Parsing of `/proc/[pid]/smaps`.
+//!
+//! Reading smaps is expensive: the kernel walks the page tables of every mapping while holding
+//! the mmap lock of the process, which takes milliseconds for a process with a large or
+//! fragmented address space and stalls its page faults and `mmap` calls meanwhile. Don't read
+//! it at a high frequency.
+use super::system_memory_info::parse_kib;
+use std::collections::HashMap;
+
+/// One mapping of smaps, with its `Key: N kB` fields in bytes.
+pub(crate) struct SmapsMapping<'a> {
+    pub(crate) perms: &'a str,
+    /// the pathname, `[heap]` like pseudo-paths included, `None` for anonymous mappings.
+    pub(crate) path: Option<&'a str>,
+    fields: Vec<(&'a str, u64)>,
+}
+
+impl<'a> SmapsMapping<'a> {
+    /// The field `key` in bytes, 0 if missing.
+    pub(crate) fn field(&self, key: &str) -> u64 {
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map_or(0, |(_, v)| *v)
+    }
+
+    fn parse_header(line: &'a str) -> Option<Self> {
+        // address perms offset dev inode pathname, the pathname is padded with spaces.
+        let mut parts = line.splitn(6, ' ');
+        let range = parts.next()?;
+        if !range.contains('-') || range.contains(':') {
+            return None;
+        }
+        let perms = parts.next()?;
+        let path = parts.nth(3).map(str::trim).filter(|p| !p.is_empty());
+        Some(SmapsMapping {
+            perms,
+            path,
+            fields: vec![],
+        })
+    }
+}
+
+/// Split the content of smaps into its mappings, lines which are not `N kB` fields are skipped.
+pub(crate) fn parse_smaps_mappings(smaps: &str) -> Vec<SmapsMapping<'_>> {
+    let mut mappings: Vec<SmapsMapping<'_>> = vec![];
+    for line in smaps.lines() {
+        if let Some(mapping) = SmapsMapping::parse_header(line) {
+            mappings.push(mapping);
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (Some(mapping), Some(value)) = (mappings.last_mut(), parse_kib(value)) else {
+            continue;
+        };
+        mapping.fields.push((key, value.bytes()));
+    }
+    mappings
+}
+
+fn is_shared_library(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.ends_with(".so") || name.contains(".so.")
+}
+
+/// Sum the `Rss` of the mappings of shared libraries in the content of smaps, in bytes.
+///
+/// The mappings counted are every mapping of a file named `*.so` or `*.so.*`, code and data
+/// alike, and the executable mappings of any other file, like the main executable or a
+/// library with an unusual name.
+pub fn parse_shared_library_rss(smaps: &str) -> u64 {
+    parse_smaps_mappings(smaps)
+        .iter()
+        .filter(|mapping| match mapping.path {
+            Some(path) if path.starts_with('/') => {
+                is_shared_library(path) || mapping.perms.contains('x')
+            }
+            _ => false,
+        })
+        .map(|mapping| mapping.field("Rss"))
+        .sum()
+}
+
+/// Get the resident memory of the shared libraries and other code mapped by current process,
+/// from `/proc/self/smaps`, see `parse_shared_library_rss`.
+///
+/// Pages shared with other processes count fully. Reading smaps is expensive, see the
+/// [module docs](self).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn shared_library_rss() -> std::io::Result<u64> {
+    Ok(parse_shared_library_rss(&crate::procfs::read_to_string(
+        "self/smaps",
+    )?))
+}
+
+/// Sum the `AnonHugePages` of the content of smaps or smaps_rollup, in bytes.
+pub fn parse_anon_huge_pages(smaps: &str) -> u64 {
+    parse_smaps_mappings(smaps)
+        .iter()
+        .map(|mapping| mapping.field("AnonHugePages"))
+        .sum()
+}
+
+/// Get the anonymous memory of current process backed by transparent huge pages, in bytes.
+///
+/// It reads `/proc/self/smaps_rollup`, or `/proc/self/smaps` on kernels older than 4.14,
+/// see the [module docs](self) for the cost. It is 0 unless THP is enabled, see
+/// `/sys/kernel/mm/transparent_hugepage/enabled`: with `madvise` only the regions advised
+/// with `MADV_HUGEPAGE` get huge pages.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn transparent_huge_pages() -> std::io::Result<u64> {
+    let smaps = match crate::procfs::read_to_string("self/smaps_rollup") {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            crate::procfs::read_to_string("self/smaps")?
+        }
+        smaps => smaps?,
+    };
+    Ok(parse_anon_huge_pages(&smaps))
+}
+
+fn is_shared_memory(path: &str) -> bool {
+    // SysV segments are mappings of `/SYSV<key> (deleted)` on the internal shm mount.
+    path.starts_with("/dev/shm/") || path.starts_with("/SYSV")
+}
+
+/// Sum the `Rss` of the shared memory segments in the content of smaps, in bytes.
+///
+/// The segments are the mappings of POSIX shared memory, files under `/dev/shm` as
+/// `shm_open` creates them, and of System V segments attached with `shmat`.
+pub fn parse_shared_memory_rss(smaps: &str) -> u64 {
+    parse_smaps_mappings(smaps)
+        .iter()
+        .filter(|mapping| mapping.path.is_some_and(is_shared_memory))
+        .map(|mapping| mapping.field("Rss"))
+        .sum()
+}
+
+/// Get the resident memory of the shared memory segments mapped by current process, from
+/// `/proc/self/smaps`, see `parse_shared_memory_rss`.
+///
+/// Shared pages are charged to every process mapping them: the segments are counted fully here
+/// and in the RSS of each of their users, summing this over processes counts them several times.
+/// Only the pages this process touched are resident in its mappings. Reading smaps is
+/// expensive, see the [module docs](self).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_shared_memory_usage() -> std::io::Result<u64> {
+    Ok(parse_shared_memory_rss(&crate::procfs::read_to_string(
+        "self/smaps",
+    )?))
+}
+
+/// A `Key: N kB` field of smaps, summed over the mappings by `get_smaps_fields`.
+///
+/// They are the fields `smaps_rollup` sums too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SmapsField {
+    Rss,
+    Pss,
+    SharedClean,
+    SharedDirty,
+    PrivateClean,
+    PrivateDirty,
+    Referenced,
+    Anonymous,
+    LazyFree,
+    AnonHugePages,
+    ShmemPmdMapped,
+    SharedHugetlb,
+    PrivateHugetlb,
+    Swap,
+    SwapPss,
+    Locked,
+}
+
+impl SmapsField {
+    /// The key of the field in smaps, e.g. `Shared_Clean`.
+    pub fn key(self) -> &'static str {
+        match self {
+            SmapsField::Rss => "Rss",
+            SmapsField::Pss => "Pss",
+            SmapsField::SharedClean => "Shared_Clean",
+            SmapsField::SharedDirty => "Shared_Dirty",
+            SmapsField::PrivateClean => "Private_Clean",
+            SmapsField::PrivateDirty => "Private_Dirty",
+            SmapsField::Referenced => "Referenced",
+            SmapsField::Anonymous => "Anonymous",
+            SmapsField::LazyFree => "LazyFree",
+            SmapsField::AnonHugePages => "AnonHugePages",
+            SmapsField::ShmemPmdMapped => "ShmemPmdMapped",
+            SmapsField::SharedHugetlb => "Shared_Hugetlb",
+            SmapsField::PrivateHugetlb => "Private_Hugetlb",
+            SmapsField::Swap => "Swap",
+            SmapsField::SwapPss => "SwapPss",
+            SmapsField::Locked => "Locked",
+        }
+    }
+}
+
+/// Sum `fields` over the mappings in the content of smaps or smaps_rollup, in bytes.
+///
+/// Every requested field is in the map, 0 when missing. The lines of other fields, and the
+/// mapping headers, are skipped on their key without parsing their value.
+pub fn parse_smaps_fields(smaps: &str, fields: &[SmapsField]) -> HashMap<SmapsField, u64> {
+    let mut sums: HashMap<SmapsField, u64> = fields.iter().map(|field| (*field, 0)).collect();
+    for line in smaps.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(field) = fields.iter().find(|field| field.key() == key) else {
+            continue;
+        };
+        if let Some(value) = parse_kib(value) {
+            *sums.entry(*field).or_default() += value.bytes();
+        }
+    }
+    sums
+}
+
+/// Get the sums of `fields` over the mappings of current process, see `parse_smaps_fields`.
+///
+/// It reads `/proc/self/smaps_rollup`, which the kernel sums already, or `/proc/self/smaps` on
+/// kernels older than 4.14: see the [module docs](self) for the cost, which reading fewer fields
+/// doesn't lower on the kernel side.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_smaps_fields(fields: &[SmapsField]) -> std::io::Result<HashMap<SmapsField, u64>> {
+    let smaps = match crate::procfs::read_to_string("self/smaps_rollup") {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            crate::procfs::read_to_string("self/smaps")?
+        }
+        smaps => smaps?,
+    };
+    Ok(parse_smaps_fields(&smaps, fields))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SMAPS: &str = "\
+5588febe5000-5588febe7000 r--p 00000000 fe:00 280762                     /usr/bin/server
+Size:                  8 kB
+Rss:                   8 kB
+Pss:                   8 kB
+VmFlags: rd mr mw me
+5588febe7000-5588febed000 r-xp 00002000 fe:00 280762                     /usr/bin/server
+Size:                 24 kB
+Rss:                  24 kB
+Pss:                  24 kB
+5588ffa00000-5588ffb00000 rw-p 00000000 00:00 0                          [heap]
+Size:               1024 kB
+Rss:                 900 kB
+Pss:                 900 kB
+AnonHugePages:         0 kB
+7f1c00000000-7f1c00400000 rw-p 00000000 00:00 0 
+Size:               4096 kB
+Rss:                4096 kB
+Pss:                4096 kB
+AnonHugePages:      2048 kB
+7f1c10000000-7f1c10028000 r--p 00000000 fe:00 1311    /usr/lib/x86_64-linux-gnu/libc.so.6
+Size:                160 kB
+Rss:                 160 kB
+Pss:                  16 kB
+7f1c10028000-7f1c101bd000 r-xp 00028000 fe:00 1311    /usr/lib/x86_64-linux-gnu/libc.so.6
+Size:               1620 kB
+Rss:                1000 kB
+Pss:                  90 kB
+7f1c20000000-7f1c20100000 rw-p 00010000 fe:00 2048    /opt/plugins/libplugin.so
+Size:               1024 kB
+Rss:                 100 kB
+Pss:                 100 kB
+7f1c30000000-7f1c30100000 r--s 00000000 fe:00 4096    /var/data/table.bin
+Size:               1024 kB
+Rss:                 512 kB
+Pss:                 512 kB
+7f1c40000000-7f1c40800000 rw-s 00000000 00:1a 77      /dev/shm/ipc-ring
+Size:               8192 kB
+Rss:                2048 kB
+Pss:                1024 kB
+7f1c50000000-7f1c50100000 rw-s 00000000 00:01 32769   /SYSV0000162e (deleted)
+Size:               1024 kB
+Rss:                 256 kB
+Pss:                 256 kB
+";
+
+    #[test]
+    fn test_parse_smaps_mappings() {
+        let mappings = parse_smaps_mappings(SMAPS);
+        assert_eq!(mappings.len(), 10);
+        assert_eq!(mappings[1].perms, "r-xp");
+        assert_eq!(mappings[1].path, Some("/usr/bin/server"));
+        assert_eq!(mappings[2].path, Some("[heap]"));
+        assert_eq!(mappings[3].path, None);
+        assert_eq!(mappings[3].field("AnonHugePages"), 2048 * 1024);
+        assert_eq!(mappings[3].field("Missing"), 0);
+        assert_eq!(
+            mappings[4].path,
+            Some("/usr/lib/x86_64-linux-gnu/libc.so.6")
+        );
+    }
+
+    #[test]
+    fn test_parse_shared_library_rss() {
+        // the executable text of the binary, libc and the plugin, not the data file.
+        assert_eq!(
+            parse_shared_library_rss(SMAPS),
+            (24 + 160 + 1000 + 100) * 1024
+        );
+        assert_eq!(parse_shared_library_rss(""), 0);
+    }
+
+    #[test]
+    fn test_parse_shared_memory_rss() {
+        assert_eq!(parse_shared_memory_rss(SMAPS), (2048 + 256) * 1024);
+        assert_eq!(parse_shared_memory_rss(""), 0);
+    }
+
+    #[test]
+    fn test_parse_smaps_fields() {
+        let sums = parse_smaps_fields(SMAPS, &[SmapsField::Pss, SmapsField::AnonHugePages]);
+        assert_eq!(sums.len(), 2);
+        assert_eq!(
+            sums[&SmapsField::Pss],
+            (8 + 24 + 900 + 4096 + 16 + 90 + 100 + 512 + 1024 + 256) * 1024
+        );
+        assert_eq!(sums[&SmapsField::AnonHugePages], 2048 * 1024);
+
+        let sums = parse_smaps_fields(SMAPS, &[SmapsField::Swap]);
+        assert_eq!(sums[&SmapsField::Swap], 0);
+        assert!(!sums.contains_key(&SmapsField::Rss));
+    }
+
+    #[test]
+    fn test_parse_anon_huge_pages() {
+        assert_eq!(parse_anon_huge_pages(SMAPS), 2048 * 1024);
+        let rollup = "\
+5637f3db1000-7fffe2e89000 ---p 00000000 00:00 0                          [rollup]
+Rss:               81408 kB
+AnonHugePages:     12288 kB
+";
+        assert_eq!(parse_anon_huge_pages(rollup), 12288 * 1024);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_shared_library_rss() {
+        assert!(shared_library_rss().unwrap() > 0);
+        transparent_huge_pages().unwrap();
+        let sums = get_smaps_fields(&[SmapsField::Rss, SmapsField::Pss]).unwrap();
+        assert!(sums[&SmapsField::Rss] >= sums[&SmapsField::Pss]);
+        assert!(sums[&SmapsField::Pss] > 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_shared_memory_usage() {
+        use std::os::unix::io::AsRawFd;
+
+        const SIZE: usize = 4 * 1024 * 1024;
+        let path = format!("/dev/shm/perf-monitor-test-{}", std::process::id());
+        let Ok(file) = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+        else {
+            // no tmpfs at /dev/shm in this sandbox.
+            return;
+        };
+        std::fs::remove_file(&path).unwrap();
+        file.set_len(SIZE as u64).unwrap();
+
+        let before = get_shared_memory_usage().unwrap();
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        assert_ne!(ptr, libc::MAP_FAILED);
+        unsafe { std::ptr::write_bytes(ptr as *mut u8, 1, SIZE) };
+        let after = get_shared_memory_usage().unwrap();
+        unsafe { libc::munmap(ptr, SIZE) };
+
+        assert!(after >= before + SIZE as u64, "{} -> {}", before, after);
+    }
+}
@@ -0,0 +1,130 @@
+//! The last known memory usage, published into atomics for crash and signal handlers.
+use super::ProcessMemoryInfo;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// RSS and VSZ of the last published sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PublishedSnapshot {
+    /// see `ProcessMemoryInfo::resident_set_size`.
+    pub resident_set_size: u64,
+    /// see `ProcessMemoryInfo::virtual_memory_size`.
+    pub virtual_memory_size: u64,
+    /// how many samples were published so far, starting at 1.
+    pub seq: u64,
+}
+
+struct AtomicMemory {
+    resident_set_size: AtomicU64,
+    virtual_memory_size: AtomicU64,
+    seq: AtomicU64,
+}
+
+impl AtomicMemory {
+    const fn new() -> Self {
+        AtomicMemory {
+            resident_set_size: AtomicU64::new(0),
+            virtual_memory_size: AtomicU64::new(0),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    fn publish(&self, info: &ProcessMemoryInfo) {
+        self.resident_set_size
+            .store(info.resident_set_size, Ordering::Relaxed);
+        self.virtual_memory_size
+            .store(info.virtual_memory_size, Ordering::Relaxed);
+        self.seq.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Option<PublishedSnapshot> {
+        let seq = self.seq.load(Ordering::Relaxed);
+        if seq == 0 {
+            return None;
+        }
+        Some(PublishedSnapshot {
+            resident_set_size: self.resident_set_size.load(Ordering::Relaxed),
+            virtual_memory_size: self.virtual_memory_size.load(Ordering::Relaxed),
+            seq,
+        })
+    }
+}
+
+static LAST: AtomicMemory = AtomicMemory::new();
+
+/// Publish `info` as the last known memory usage.
+///
+/// `MemoryMonitor` publishes every sample it takes, call this from your own sampler otherwise.
+pub fn publish_snapshot(info: &ProcessMemoryInfo) {
+    LAST.publish(info)
+}
+
+/// The last published memory usage, `None` if nothing was published yet.
+///
+/// # Async-signal-safety
+///
+/// This is nothing but three `Relaxed` atomic loads of lock-free `AtomicU64`s: it neither
+/// allocates, locks, nor makes a syscall, so it is safe to call from a signal handler, a
+/// crash reporter or an allocation failure path.
+///
+/// The fields are loaded one by one without any lock, so a read racing with a publish may mix
+/// fields of two consecutive samples, and `seq` may be off by one. Each field on its own is
+/// always a value that was actually published. A seqlock would avoid that, but a signal handler
+/// interrupting the publishing thread would then spin forever.
+pub fn published_snapshot() -> Option<PublishedSnapshot> {
+    LAST.load()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    #[test]
+    fn test_concurrent_reads() {
+        let memory = Arc::new(AtomicMemory::new());
+        assert!(memory.load().is_none());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let writer = {
+            let memory = memory.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                for i in 1..=100_000u64 {
+                    memory.publish(&ProcessMemoryInfo {
+                        resident_set_size: i * 4096,
+                        virtual_memory_size: i * 8192,
+                        ..Default::default()
+                    });
+                }
+                done.store(true, Ordering::SeqCst);
+            })
+        };
+
+        let mut last = PublishedSnapshot::default();
+        while !done.load(Ordering::SeqCst) {
+            let Some(snapshot) = memory.load() else {
+                continue;
+            };
+            assert_eq!(snapshot.resident_set_size % 4096, 0);
+            assert_eq!(snapshot.virtual_memory_size % 8192, 0);
+            assert!(snapshot.resident_set_size >= last.resident_set_size);
+            assert!(snapshot.virtual_memory_size >= last.virtual_memory_size);
+            last = snapshot;
+        }
+        writer.join().unwrap();
+
+        let snapshot = memory.load().unwrap();
+        assert_eq!(snapshot.resident_set_size, 100_000 * 4096);
+        assert_eq!(snapshot.virtual_memory_size, 100_000 * 8192);
+        assert_eq!(snapshot.seq, 100_000);
+    }
+
+    #[test]
+    fn test_publish() {
+        publish_snapshot(&ProcessMemoryInfo {
+            resident_set_size: 1,
+            ..Default::default()
+        });
+        assert!(published_snapshot().unwrap().seq > 0);
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fs::File, io::Result, os::unix::fs::FileExt};
+
+use super::ProcessMemoryInfo;
+
+/// Reads the memory info of current process from `/proc/self/statm` kept open, for threads
+/// which must not block on file system calls.
+///
+/// The file is opened once by `open`, `poll_once` then reads it with a `pread` into a stack
+/// buffer: no `open` or `close` call, which can block on the file system locks, and no heap
+/// allocation, so it can run on a realtime thread such as an audio callback. The `pread`
+/// itself is a syscall served from kernel counters without I/O, it doesn't sleep on disk
+/// but it is not strictly wait free either.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::StatmReader;
+/// let reader = StatmReader::open().unwrap();
+/// // on the realtime thread:
+/// let rss = reader.poll_once().unwrap().resident_set_size;
+/// ```
+pub struct StatmReader {
+    file: File,
+}
+
+impl StatmReader {
+    /// Open `/proc/self/statm`, call it at startup outside of the realtime thread.
+    pub fn open() -> Result<Self> {
+        let file = File::open(crate::procfs::path("self/statm"))?;
+        // the page size is computed once, not on the first poll.
+        super::memory_granularity();
+        Ok(StatmReader { file })
+    }
+
+    /// Read the memory info without opening any file or allocating.
+    pub fn poll_once(&self) -> Result<ProcessMemoryInfo> {
+        // 7 numbers of at most 20 digits each.
+        let mut buf = [0u8; 160];
+        let len = self.file.read_at(&mut buf, 0)?;
+        let statm = std::str::from_utf8(&buf[..len])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        super::process_memory_info::parse_statm(statm)
+    }
+}
+
+impl std::os::unix::io::AsRawFd for StatmReader {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn test_poll_once() {
+        let reader = StatmReader::open().unwrap();
+        let fd = reader.as_raw_fd();
+        let before = crate::fd::fd_count_cur().unwrap();
+        for _ in 0..1000 {
+            let info = reader.poll_once().unwrap();
+            assert!(info.resident_set_size > 0);
+            assert!(info.virtual_memory_size >= info.resident_set_size);
+        }
+        assert_eq!(reader.as_raw_fd(), fd);
+        // other tests may open a few files meanwhile, leaking one per poll would show anyway.
+        assert!(crate::fd::fd_count_cur().unwrap() < before + 100);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    io::Result,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+use super::{get_process_memory_info, ProcessMemoryInfo};
+
+/// Sends the memory info of current process as StatsD gauges over UDP.
+///
+/// Every `report` sends one datagram with a line per gauge, in bytes:
+/// ```text
+/// <prefix>.rss:123456|g
+/// <prefix>.vsz:456789|g
+/// ```
+/// plus `<prefix>.rss_peak` where the platform reports it. The format is understood by StatsD
+/// and DogStatsD alike.
+pub struct StatsdReporter {
+    socket: UdpSocket,
+    prefix: String,
+    fields: Vec<&'static str>,
+}
+
+impl StatsdReporter {
+    /// Bind a local UDP socket to send to `addr`, nothing is sent until `report`.
+    pub fn new(addr: SocketAddr, prefix: &str) -> Result<Self> {
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        // a full send buffer must not block the caller, the sample is dropped instead.
+        socket.set_nonblocking(true)?;
+        Ok(StatsdReporter {
+            socket,
+            prefix: prefix.trim_end_matches('.').to_string(),
+            fields: ProcessMemoryInfo::field_names(&[
+                "resident_set_size",
+                "virtual_memory_size",
+                #[cfg(not(any(target_os = "android", target_os = "linux")))]
+                "resident_set_size_peak",
+            ])?,
+        })
+    }
+
+    /// Send only these fields of [`ProcessMemoryInfo`], by their names and in this order.
+    ///
+    /// `resident_set_size`, `virtual_memory_size` and `resident_set_size_peak` are sent as
+    /// `rss`, `vsz` and `rss_peak`, the other fields under their own name. `InvalidInput` if a
+    /// name is not a field on this platform.
+    pub fn with_fields(mut self, names: &[&str]) -> Result<Self> {
+        self.fields = ProcessMemoryInfo::field_names(names)?;
+        Ok(self)
+    }
+
+    /// Capture the memory info and send it.
+    ///
+    /// Failures, including a StatsD agent which is not listening, are returned rather than
+    /// panicking; monitoring callers will usually ignore them.
+    pub fn report(&self) -> Result<()> {
+        self.report_info(&get_process_memory_info()?)
+    }
+
+    /// Send `info` captured by the caller.
+    pub fn report_info(&self, info: &ProcessMemoryInfo) -> Result<()> {
+        self.socket.send(self.format(info).as_bytes())?;
+        Ok(())
+    }
+
+    fn format(&self, info: &ProcessMemoryInfo) -> String {
+        let values = info.fields();
+        let gauges: Vec<String> = self
+            .fields
+            .iter()
+            .filter_map(|field| values.iter().find(|(name, _)| name == field))
+            .map(|&(field, value)| {
+                let name = match field {
+                    "resident_set_size" => "rss",
+                    "virtual_memory_size" => "vsz",
+                    "resident_set_size_peak" => "rss_peak",
+                    field => field,
+                };
+                self.gauge(name, value)
+            })
+            .collect();
+        gauges.join("\n")
+    }
+
+    fn gauge(&self, name: &str, value: u64) -> String {
+        format!("{}.{}:{}|g", self.prefix, name, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_statsd_reporter() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+            .unwrap();
+        let reporter = StatsdReporter::new(server.local_addr().unwrap(), "app.mem.").unwrap();
+        reporter.report().unwrap();
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let datagram = std::str::from_utf8(&buf[..len]).unwrap();
+        let gauges: Vec<(&str, u64)> = datagram
+            .lines()
+            .map(|line| {
+                let (name, rest) = line.split_once(':').unwrap();
+                let value = rest.strip_suffix("|g").unwrap();
+                (name, value.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(gauges[0].0, "app.mem.rss");
+        assert!(gauges[0].1 > 0);
+        assert_eq!(gauges[1].0, "app.mem.vsz");
+        assert!(gauges[1].1 >= gauges[0].1);
+    }
+
+    #[test]
+    fn test_with_fields() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let reporter = StatsdReporter::new(server.local_addr().unwrap(), "app")
+            .unwrap()
+            .with_fields(&["resident_set_size"])
+            .unwrap();
+        let info = get_process_memory_info().unwrap();
+        let datagram = reporter.format(&info);
+        assert_eq!(datagram, format!("app.rss:{}|g", info.resident_set_size));
+
+        let err = StatsdReporter::new(server.local_addr().unwrap(), "app")
+            .unwrap()
+            .with_fields(&["vsz"])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_no_listener() {
+        // nothing listens there, sending must not panic whatever the OS reports.
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let reporter = StatsdReporter::new(port, "app").unwrap();
+        for _ in 0..3 {
+            let _ = reporter.report();
+        }
+    }
+}
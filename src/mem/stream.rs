@@ -0,0 +1,89 @@
+use std::{
+    io::Result,
+    time::{Duration, Instant},
+};
+
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_stream::{wrappers::IntervalStream, Stream, StreamExt};
+
+use super::{get_process_memory_info, sampled::Sequencer, ProcessMemoryInfo, SampledMemory};
+use crate::runner::MIN_INTERVAL;
+
+/// A stream of memory info sampled every `period`, the first item comes right away.
+///
+/// `period` is at least `runner::MIN_INTERVAL`, 1ms: a zero one, on which tokio's `interval`
+/// panics, is raised to it.
+///
+/// Reads happen on the blocking pool with `spawn_blocking`, so a slow `/proc` doesn't stall
+/// the runtime. Ticks missed because the consumer is late are skipped, not caught up in a
+/// burst, and dropping the stream stops sampling.
+///
+/// It must be polled within a tokio runtime, as the timer and the blocking pool belong to it.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use workflow_perf_monitor::mem::memory_stream;
+/// use tokio_stream::StreamExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut rss = memory_stream(Duration::from_millis(10))
+///     .map(|info| info.map(|info| info.resident_set_size))
+///     .take(2);
+/// while let Some(rss) = rss.next().await {
+///     println!("{} bytes", rss.unwrap());
+/// }
+/// # }
+/// ```
+pub fn memory_stream(period: Duration) -> impl Stream<Item = Result<ProcessMemoryInfo>> + Unpin {
+    let mut ticks = interval(period.max(MIN_INTERVAL));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    IntervalStream::new(ticks)
+        .then(|_| tokio::task::spawn_blocking(get_process_memory_info))
+        .map(|ret| ret.unwrap_or_else(|e| Err(std::io::Error::other(e))))
+}
+
+/// Same as `memory_stream`, with each sample numbered and timed as a `SampledMemory`.
+///
+/// Skipped ticks are not numbered, they show as a longer `interval`. Failed reads are, as with
+/// `MemoryMonitor::spawn_sequenced`, the errors are yielded in their place.
+pub fn sampled_memory_stream(
+    period: Duration,
+) -> impl Stream<Item = Result<SampledMemory>> + Unpin {
+    let mut sequencer = Sequencer::default();
+    memory_stream(period).map(move |info| sequencer.tag(Instant::now(), info))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_stream() {
+        let samples: Vec<_> = memory_stream(Duration::from_millis(5))
+            .take(3)
+            .collect()
+            .await;
+        assert_eq!(samples.len(), 3);
+        for sample in samples {
+            assert!(sample.unwrap().resident_set_size > 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zero_period() {
+        let samples: Vec<_> = memory_stream(Duration::ZERO).take(2).collect().await;
+        assert_eq!(samples.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sampled_memory_stream() {
+        let samples: Vec<_> = sampled_memory_stream(Duration::from_millis(5))
+            .take(3)
+            .collect()
+            .await;
+        let seqs: Vec<u64> = samples.iter().map(|s| s.as_ref().unwrap().seq).collect();
+        assert_eq!(seqs, [0, 1, 2]);
+        assert!(samples[2].as_ref().unwrap().interval > Duration::ZERO);
+    }
+}
@@ -0,0 +1,232 @@
+use super::vmstat::{vmstat_values, Elapsed};
+use crate::clock::{Clock, SystemClock};
+use std::{io::Result, time::Instant};
+
+/// A point of the swap activity, all values are in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapSample {
+    /// `VmSwap` of current process.
+    pub vm_swap: u64,
+    /// Total swapped in by the system since boot, `pswpin` of `/proc/vmstat`.
+    pub swapped_in: u64,
+    /// Total swapped out by the system since boot, `pswpout` of `/proc/vmstat`.
+    pub swapped_out: u64,
+}
+
+impl SwapSample {
+    /// Read `/proc/self/status` and `/proc/vmstat`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn read() -> Result<Self> {
+        Self::parse(
+            &crate::procfs::read_to_string("self/status")?,
+            &crate::procfs::read_to_string("vmstat")?,
+            super::memory_granularity(),
+        )
+    }
+
+    /// The sample of the contents of `/proc/self/status` and `/proc/vmstat`, the swap counters
+    /// being in pages of `page_size` bytes.
+    #[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+    fn parse(status: &str, vmstat: &str, page_size: u64) -> Result<Self> {
+        let status: super::ProcStatus = status.parse()?;
+        let (pswpin, pswpout) = parse_vmstat_swap(vmstat);
+        Ok(SwapSample {
+            vm_swap: status.vm_swap.unwrap_or(0),
+            swapped_in: pswpin.saturating_mul(page_size),
+            swapped_out: pswpout.saturating_mul(page_size),
+        })
+    }
+}
+
+/// `pswpin` and `pswpout` of `/proc/vmstat` in pages, 0 when missing, e.g. without swap support.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn parse_vmstat_swap(vmstat: &str) -> (u64, u64) {
+    let (mut pswpin, mut pswpout) = (0, 0);
+    for (key, value) in vmstat_values(vmstat) {
+        match key {
+            "pswpin" => pswpin = value,
+            "pswpout" => pswpout = value,
+            _ => {}
+        }
+    }
+    (pswpin, pswpout)
+}
+
+/// Detect swap thrashing from the rates between the two latest samples.
+///
+/// The system is thrashing when the pages go back and forth: the swap in and out rates summed
+/// exceed the threshold, while pages are swapped in. Only swapping out is just the kernel
+/// pushing cold pages away, it is not reported.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::SwapMonitor;
+/// // 10 MiB/s of churn.
+/// let mut monitor = SwapMonitor::new(10.0 * 1024.0 * 1024.0);
+/// # #[cfg(target_os = "linux")]
+/// monitor.sample().unwrap();
+/// # #[cfg(target_os = "linux")]
+/// monitor.sample().unwrap();
+/// println!("thrashing: {}", monitor.is_thrashing());
+/// ```
+pub struct SwapMonitor<C = SystemClock> {
+    threshold: f64,
+    last: Option<(Instant, SwapSample)>,
+    swap_in_rate: f64,
+    swap_out_rate: f64,
+    vm_swap_rate: f64,
+    clock: C,
+}
+
+impl SwapMonitor {
+    /// `threshold` is the swap in plus swap out rate in bytes per second.
+    pub fn new(threshold: f64) -> Self {
+        Self::with_clock(threshold, SystemClock)
+    }
+}
+
+impl<C: Clock> SwapMonitor<C> {
+    /// As `new`, with `sample` reading the time from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(threshold: f64, clock: C) -> Self {
+        SwapMonitor {
+            threshold,
+            last: None,
+            swap_in_rate: 0.0,
+            swap_out_rate: 0.0,
+            vm_swap_rate: 0.0,
+            clock,
+        }
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Read a sample and observe it now, according to the clock.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn sample(&mut self) -> Result<()> {
+        let sample = SwapSample::read()?;
+        let now = self.clock.now();
+        self.observe(now, sample);
+        Ok(())
+    }
+
+    /// Update the swap rates, and so `is_thrashing`, with `sample` read at `now`.
+    ///
+    /// The swap totals are system wide counters while `VmSwap` is the current size of our own
+    /// swapped out memory, its rate goes negative when it is swapped back in. A sample at
+    /// the same `now` as the previous one is ignored.
+    pub fn observe(&mut self, now: Instant, sample: SwapSample) {
+        if let Some((then, last)) = self.last {
+            let Some(elapsed) = Elapsed::between(then, now) else {
+                return;
+            };
+            self.swap_in_rate = elapsed.rate(last.swapped_in, sample.swapped_in);
+            self.swap_out_rate = elapsed.rate(last.swapped_out, sample.swapped_out);
+            self.vm_swap_rate = elapsed.change(last.vm_swap, sample.vm_swap);
+        }
+        self.last = Some((now, sample));
+    }
+
+    /// The system wide swap in rate, in bytes per second.
+    pub fn swap_in_rate(&self) -> f64 {
+        self.swap_in_rate
+    }
+
+    /// The system wide swap out rate, in bytes per second.
+    pub fn swap_out_rate(&self) -> f64 {
+        self.swap_out_rate
+    }
+
+    /// The change of `VmSwap` of current process in bytes per second, negative when it shrinks.
+    pub fn vm_swap_rate(&self) -> f64 {
+        self.vm_swap_rate
+    }
+
+    pub fn is_thrashing(&self) -> bool {
+        self.swap_in_rate > 0.0 && self.swap_in_rate + self.swap_out_rate > self.threshold
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    const PAGE: u64 = 4096;
+
+    fn vmstat(pswpin: u64, pswpout: u64) -> String {
+        format!(
+            "nr_free_pages 1000\npswpin {}\npswpout {}\npgfault 123\n",
+            pswpin, pswpout
+        )
+    }
+
+    fn sample(vm_swap: u64, pswpin: u64, pswpout: u64) -> SwapSample {
+        let status = format!("Name:\tx\nPid:\t1\nVmSwap:\t{} kB\n", vm_swap / 1024);
+        SwapSample::parse(&status, &vmstat(pswpin, pswpout), PAGE).unwrap()
+    }
+
+    #[test]
+    fn test_parse_vmstat_swap() {
+        assert_eq!(parse_vmstat_swap(&vmstat(12, 34)), (12, 34));
+        assert_eq!(parse_vmstat_swap("nr_free_pages 1000\n"), (0, 0));
+    }
+
+    #[test]
+    fn test_thrashing() {
+        let start = Instant::now();
+        let mut monitor = SwapMonitor::new((1000 * PAGE) as f64);
+        monitor.observe(start, sample(0, 100, 100));
+        assert!(!monitor.is_thrashing());
+
+        // 2000 pages out over 2 seconds, nothing comes back.
+        monitor.observe(start + Duration::from_secs(2), sample(8 * PAGE, 100, 2100));
+        assert_eq!(monitor.swap_out_rate(), (1000 * PAGE) as f64);
+        assert_eq!(monitor.swap_in_rate(), 0.0);
+        assert_eq!(monitor.vm_swap_rate(), (4 * PAGE) as f64);
+        assert!(!monitor.is_thrashing());
+
+        // 800 pages in and 800 out in a second.
+        monitor.observe(start + Duration::from_secs(3), sample(4 * PAGE, 900, 2900));
+        assert_eq!(monitor.swap_in_rate(), (800 * PAGE) as f64);
+        assert_eq!(monitor.vm_swap_rate(), -((4 * PAGE) as f64));
+        assert!(monitor.is_thrashing());
+
+        // calmed down.
+        monitor.observe(start + Duration::from_secs(4), sample(4 * PAGE, 910, 2910));
+        assert!(!monitor.is_thrashing());
+    }
+
+    #[test]
+    fn test_sample() {
+        let first = sample(8 * PAGE, 10, 20);
+        assert_eq!(
+            first,
+            SwapSample {
+                vm_swap: 8 * PAGE,
+                swapped_in: 10 * PAGE,
+                swapped_out: 20 * PAGE,
+            }
+        );
+        let start = Instant::now();
+        let mut monitor = SwapMonitor::new(f64::MAX);
+        monitor.observe(start, first);
+        // 40 pages in and 60 out in 4 seconds, the process got back 4 of its pages.
+        monitor.observe(start + Duration::from_secs(4), sample(4 * PAGE, 50, 80));
+        assert_eq!(monitor.swap_in_rate(), (10 * PAGE) as f64);
+        assert_eq!(monitor.swap_out_rate(), (15 * PAGE) as f64);
+        assert_eq!(monitor.vm_swap_rate(), -(PAGE as f64));
+
+        // a counter of garbage size saturates.
+        assert_eq!(sample(0, u64::MAX, 0).swapped_in, u64::MAX);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_read() {
+        let mut monitor = SwapMonitor::new(f64::MAX);
+        monitor.sample().unwrap();
+        monitor.sample().unwrap();
+        assert!(!monitor.is_thrashing());
+    }
+}
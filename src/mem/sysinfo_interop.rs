@@ -0,0 +1,86 @@
+//! Interop with the process memory of `sysinfo` 0.37.
+use super::{get_process_memory_info_for_pid, ProcessMemoryInfo};
+use std::collections::HashMap;
+
+/// The memory fields of `sysinfo::Process` 0.37, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SysinfoMemory {
+    /// `Process::memory`, the resident set size.
+    pub memory: u64,
+    /// `Process::virtual_memory`, the virtual memory size.
+    pub virtual_memory: u64,
+}
+
+impl From<ProcessMemoryInfo> for SysinfoMemory {
+    fn from(info: ProcessMemoryInfo) -> Self {
+        SysinfoMemory {
+            memory: info.resident_set_size,
+            virtual_memory: info.virtual_memory_size,
+        }
+    }
+}
+
+impl From<&sysinfo::Process> for SysinfoMemory {
+    fn from(process: &sysinfo::Process) -> Self {
+        SysinfoMemory {
+            memory: process.memory(),
+            virtual_memory: process.virtual_memory(),
+        }
+    }
+}
+
+/// The memory of every process of a `sysinfo` snapshot, with the RSS and VSZ read by this crate
+/// wherever it can read them.
+///
+/// `sysinfo::Process` can't be modified, so the overlaid values come in a map next to it.
+/// Processes this crate can't read, e.g. for lack of rights or because they exited since the
+/// snapshot was refreshed, keep the values of `sysinfo`.
+pub fn overlay_sysinfo(system: &sysinfo::System) -> HashMap<sysinfo::Pid, SysinfoMemory> {
+    system
+        .processes()
+        .iter()
+        .map(|(pid, process)| {
+            let memory = get_process_memory_info_for_pid(pid.as_u32())
+                .map(SysinfoMemory::from)
+                .unwrap_or_else(|_| process.into());
+            (*pid, memory)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_process_memory_info() {
+        let info = ProcessMemoryInfo {
+            resident_set_size: 12 * 1024 * 1024,
+            virtual_memory_size: 345 * 1024 * 1024,
+            ..Default::default()
+        };
+        assert_eq!(
+            SysinfoMemory::from(info),
+            SysinfoMemory {
+                memory: 12 * 1024 * 1024,
+                virtual_memory: 345 * 1024 * 1024,
+            }
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn test_overlay_sysinfo() {
+        let pid = sysinfo::Pid::from_u32(std::process::id());
+        let mut system = sysinfo::System::new();
+        system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+        let theirs = SysinfoMemory::from(system.process(pid).unwrap());
+
+        let ours = overlay_sysinfo(&system)[&pid];
+        assert!(ours.memory > 0);
+        assert!(ours.virtual_memory >= ours.memory);
+        // both report bytes of the same process, a unit mismatch would be off by 1024 at least.
+        let ratio = ours.memory as f64 / theirs.memory as f64;
+        assert!(ratio > 0.5 && ratio < 2.0, "{:?} vs {:?}", ours, theirs);
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// System wide memory info returned by `get_system_memory_info`.
+///
+/// All values are in **bytes**, whatever unit the underlying interface reports.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemMemoryInfo {
+    /// total usable physical memory.
+    pub total: u64,
+    /// physical memory left unused by the system.
+    pub free: u64,
+    /// an estimate of how much memory is available for starting new applications, without swapping.
+    ///
+    /// On kernels older than 3.14 which lack `MemAvailable`, this falls back to `free + buffers + cached`.
+    pub available: u64,
+    /// memory in buffer cache.
+    pub buffers: u64,
+    /// memory in the page cache, excluding swap cache.
+    pub cached: u64,
+    /// total amount of swap space available.
+    pub swap_total: u64,
+    /// amount of swap space that is currently unused.
+    pub swap_free: u64,
+}
+
+/// A value reported in KiB by `/proc/meminfo`.
+///
+/// The only way out is `bytes`, so a value can't reach `SystemMemoryInfo` unscaled.
+#[derive(Clone, Copy)]
+pub(crate) struct KiB(u64);
+
+impl KiB {
+    pub(crate) fn bytes(self) -> u64 {
+        self.0.saturating_mul(1024)
+    }
+}
+
+pub(crate) fn parse_kib(value: &str) -> Option<KiB> {
+    let mut parts = value.split_whitespace();
+    let number = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some("kB") => Some(KiB(number)),
+        _ => None,
+    }
+}
+
+/// Parse the content of `/proc/meminfo`, values are normalized to bytes.
+///
+/// See <https://man7.org/linux/man-pages/man5/proc.5.html> for the format.
+pub fn parse_meminfo(meminfo: &str) -> Result<SystemMemoryInfo> {
+    let mut info = SystemMemoryInfo::default();
+    let mut total = None;
+    let mut available = None;
+
+    for line in meminfo.lines() {
+        let Some((field, value)) = line.split_once(':') else {
+            continue;
+        };
+        if !matches!(
+            field,
+            "MemTotal"
+                | "MemAvailable"
+                | "MemFree"
+                | "Buffers"
+                | "Cached"
+                | "SwapTotal"
+                | "SwapFree"
+        ) {
+            continue;
+        }
+        let Some(value) = parse_kib(value) else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid {} in /proc/meminfo", field),
+            ));
+        };
+        let bytes = value.bytes();
+        match field {
+            "MemTotal" => total = Some(bytes),
+            "MemAvailable" => available = Some(bytes),
+            "MemFree" => info.free = bytes,
+            "Buffers" => info.buffers = bytes,
+            "Cached" => info.cached = bytes,
+            "SwapTotal" => info.swap_total = bytes,
+            _ => info.swap_free = bytes,
+        }
+    }
+
+    info.total = total
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Missing MemTotal in /proc/meminfo"))?;
+    info.available = available.unwrap_or_else(|| {
+        info.free
+            .saturating_add(info.buffers)
+            .saturating_add(info.cached)
+    });
+    Ok(info)
+}
+
+/// Get the system wide memory info. Only linux and android are supported.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_system_memory_info() -> Result<SystemMemoryInfo> {
+    parse_meminfo(&crate::procfs::read_to_string("meminfo")?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MEMINFO: &str = "\
+MemTotal:       16384000 kB
+MemFree:         1024000 kB
+MemAvailable:    8192000 kB
+Buffers:          204800 kB
+Cached:          4096000 kB
+SwapCached:            0 kB
+Active:          6144000 kB
+SwapTotal:       2097148 kB
+SwapFree:        2097148 kB
+HugePages_Total:       0
+Hugepagesize:       2048 kB
+";
+
+    #[test]
+    fn test_parse_meminfo() {
+        let info = parse_meminfo(MEMINFO).unwrap();
+        assert_eq!(info.total, 16384000 * 1024);
+        assert_eq!(info.free, 1024000 * 1024);
+        assert_eq!(info.available, 8192000 * 1024);
+        assert_eq!(info.buffers, 204800 * 1024);
+        assert_eq!(info.cached, 4096000 * 1024);
+        assert_eq!(info.swap_total, 2097148 * 1024);
+        assert_eq!(info.swap_free, 2097148 * 1024);
+    }
+
+    #[test]
+    fn test_parse_meminfo_without_available() {
+        let meminfo = MEMINFO.replace("MemAvailable:    8192000 kB\n", "");
+        let info = parse_meminfo(&meminfo).unwrap();
+        assert_eq!(info.available, (1024000 + 204800 + 4096000) * 1024);
+
+        let missing = parse_meminfo("MemFree: 1 kB\n").unwrap_err();
+        assert_eq!(missing.kind(), ErrorKind::InvalidData);
+        let huge = format!(
+            "MemTotal: 1 kB\nMemFree: {0} kB\nCached: {0} kB\n",
+            u64::MAX
+        );
+        assert_eq!(parse_meminfo(&huge).unwrap().available, u64::MAX);
+        let invalid = parse_meminfo("MemTotal: 1\n").unwrap_err();
+        assert_eq!(invalid.kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_system_memory_info() {
+        let info = get_system_memory_info().unwrap();
+        assert!(info.total > 0);
+        assert!(info.available <= info.total);
+    }
+}
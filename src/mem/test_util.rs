@@ -0,0 +1,133 @@
+//! Deterministic memory info for the tests of dependent crates, with the `test-util` feature.
+use super::ProcessMemoryInfo;
+use std::{
+    cell::RefCell,
+    io::{BufRead, BufReader, Error, ErrorKind, Result},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+};
+
+/// The name of the binary `spawn_balloon` runs.
+const BALLOON: &str = "perf-balloon";
+/// Overrides where `spawn_balloon` looks for the binary.
+const BALLOON_ENV: &str = "PERF_BALLOON_BIN";
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<ProcessMemoryInfo>> = const { RefCell::new(None) };
+}
+
+/// Make `get_process_memory_info` return `info` on the calling thread, until
+/// `clear_test_override`.
+///
+/// The override is per thread so that tests running in parallel don't see each other's
+/// values. The threads the crate spawns, like the one of `MemoryMonitor`, keep reading the
+/// real memory.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::{
+/// #     clear_test_override, get_process_memory_info, set_test_override, ProcessMemoryInfo,
+/// # };
+/// set_test_override(ProcessMemoryInfo {
+///     resident_set_size: 100 << 20,
+///     ..Default::default()
+/// });
+/// assert_eq!(get_process_memory_info().unwrap().resident_set_size, 100 << 20);
+/// clear_test_override();
+/// ```
+pub fn set_test_override(info: ProcessMemoryInfo) {
+    OVERRIDE.with(|cell| *cell.borrow_mut() = Some(info));
+}
+
+/// Return to the real reads of `get_process_memory_info` on the calling thread.
+pub fn clear_test_override() {
+    OVERRIDE.with(|cell| *cell.borrow_mut() = None);
+}
+
+pub(crate) fn test_override() -> Option<ProcessMemoryInfo> {
+    OVERRIDE.with(|cell| cell.borrow().clone())
+}
+
+/// Spawn the `perf-balloon` binary of this crate, which allocates and touches `size_bytes`,
+/// as a deterministic target for the per-pid memory functions.
+///
+/// It returns once the memory is resident. The child holds it until its stdin is closed:
+/// dropping `Child::stdin`, or the `Child` itself, lets it exit, kill it to stop it at once.
+/// Its RSS is `size_bytes` plus the little a Rust binary needs by itself, about 2MiB.
+///
+/// The binary is built with `cargo build --features test-util --bin perf-balloon`, cargo
+/// builds it for the integration tests of this crate. It is looked for at `PERF_BALLOON_BIN`,
+/// then next to the running test executable and in its parent directory, where cargo puts the
+/// binaries of `target/<profile>/deps` tests.
+pub fn spawn_balloon(size_bytes: u64) -> Result<Child> {
+    let mut child = Command::new(balloon_path()?)
+        .arg(size_bytes.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut ready = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        BufReader::new(stdout).read_line(&mut ready)?;
+    }
+    if ready.trim_end() != "ready" {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(Error::other(format!(
+            "{} exited without allocating {} bytes",
+            BALLOON, size_bytes
+        )));
+    }
+    Ok(child)
+}
+
+fn balloon_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(BALLOON_ENV) {
+        return Ok(path.into());
+    }
+    let name = format!("{}{}", BALLOON, std::env::consts::EXE_SUFFIX);
+    let exe = std::env::current_exe()?;
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "{} not found near {}, build it with `cargo build --features test-util --bin {}` \
+                     or set {}",
+                    BALLOON,
+                    exe.display(),
+                    BALLOON,
+                    BALLOON_ENV
+                ),
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mem::get_process_memory_info;
+
+    #[test]
+    fn test_override() {
+        let info = ProcessMemoryInfo {
+            resident_set_size: 12345,
+            virtual_memory_size: 67890,
+            ..Default::default()
+        };
+        set_test_override(info);
+        let read = get_process_memory_info().unwrap();
+        assert_eq!(read.resident_set_size, 12345);
+        assert_eq!(read.virtual_memory_size, 67890);
+        // other threads are not affected.
+        let other = std::thread::spawn(get_process_memory_info).join().unwrap();
+        assert_ne!(other.unwrap().resident_set_size, 12345);
+
+        clear_test_override();
+        let real = get_process_memory_info().unwrap();
+        assert_ne!(real.resident_set_size, 12345);
+        assert!(real.resident_set_size > 0);
+    }
+}
@@ -0,0 +1,46 @@
+/// Whether the OS accounts memory per thread. Always `false`: on every supported platform
+/// the threads of a process share its address space, so RSS and the other sizes are the ones
+/// of the whole process.
+///
+/// Summing a per-thread view of the memory, like `/proc/self/task/*/statm` on Linux, counts
+/// the process once per thread. To compare multi-process with multi-thread setups, sum the
+/// memory of the processes, and take it once for the threads.
+pub fn is_memory_per_thread() -> bool {
+    false
+}
+
+/// The memory of the thread group the calling thread belongs to, from its
+/// `/proc/self/task/<tid>/statm` view, on Linux and Android.
+///
+/// It is the same as `get_process_memory_info`, whichever thread calls it: the task views
+/// report the memory of the thread group, which shares one address space, see
+/// `is_memory_per_thread`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_thread_group_memory() -> std::io::Result<super::ProcessMemoryInfo> {
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+    super::process_memory_info::parse_statm(&crate::procfs::read_to_string(&format!(
+        "self/task/{}/statm",
+        tid
+    ))?)
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_threads_share_rss() {
+        assert!(!is_memory_per_thread());
+        let main = get_thread_group_memory().unwrap();
+        let (other, process) = std::thread::spawn(|| {
+            let process = super::super::get_process_memory_info().unwrap();
+            (get_thread_group_memory().unwrap(), process)
+        })
+        .join()
+        .unwrap();
+        // the same address space, only the pages touched between the reads may differ.
+        let tolerance = 1024 * super::super::memory_granularity();
+        assert!(main.approx_eq(&other, tolerance));
+        assert!(other.approx_eq(&process, tolerance));
+    }
+}
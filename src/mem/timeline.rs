@@ -0,0 +1,378 @@
+use super::{get_process_memory_info, ProcessMemoryInfo};
+use crate::clock::{Clock, SystemClock};
+use std::{
+    fmt::Write,
+    io::Result,
+    time::{Duration, Instant},
+};
+
+/// One point of a `MemoryTimeline`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TimelineSample {
+    /// time since the timeline was created, serialized as fractional seconds.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "elapsed_secs", serialize_with = "as_secs")
+    )]
+    pub elapsed: Duration,
+    /// resident set size in bytes.
+    #[cfg_attr(feature = "serde", serde(rename = "rss_bytes"))]
+    pub rss: u64,
+}
+
+#[cfg(feature = "serde")]
+fn as_secs<S: serde::Serializer>(
+    elapsed: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(elapsed.as_secs_f64())
+}
+
+/// RSS samples over the run, ready to be exported to plotting tools.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::MemoryTimeline;
+/// let mut timeline = MemoryTimeline::new();
+/// timeline.record().unwrap();
+/// let csv = timeline.decimate(1_000).to_csv();
+/// assert!(csv.starts_with("elapsed_secs,rss_bytes\n"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MemoryTimeline<C = SystemClock> {
+    start: Instant,
+    samples: Vec<TimelineSample>,
+    // the values of `ProcessMemoryInfo::fields` of each sample, `None` for those pushed
+    // without them.
+    fields: Vec<Option<Box<[u64]>>>,
+    clock: C,
+}
+
+impl Default for MemoryTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryTimeline {
+    /// Create an empty timeline, elapsed times are measured from now.
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock + Clone> MemoryTimeline<C> {
+    /// As `new`, with `record` reading the time from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(clock: C) -> Self {
+        MemoryTimeline {
+            start: clock.now(),
+            samples: vec![],
+            fields: vec![],
+            clock,
+        }
+    }
+
+    /// Sample the memory info of current process and append it, timed by the clock.
+    pub fn record(&mut self) -> Result<()> {
+        let info = get_process_memory_info()?;
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        self.push_info(elapsed, &info);
+        Ok(())
+    }
+
+    /// Append a sample taken elsewhere, only its RSS is known.
+    pub fn push(&mut self, sample: TimelineSample) {
+        self.samples.push(sample);
+        self.fields.push(None);
+    }
+
+    /// Append the memory info read elsewhere `elapsed` after the creation of the timeline,
+    /// all its fields are kept for `to_record_batch`.
+    pub fn push_info(&mut self, elapsed: Duration, info: &ProcessMemoryInfo) {
+        self.samples.push(TimelineSample {
+            elapsed,
+            rss: info.resident_set_size,
+        });
+        let values = info.fields().into_iter().map(|(_, value)| value).collect();
+        self.fields.push(Some(values));
+    }
+
+    pub fn samples(&self) -> &[TimelineSample] {
+        &self.samples
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Reduce the timeline to at most `max_points` samples.
+    ///
+    /// Samples are split into consecutive buckets of equal size, and only the sample
+    /// with the highest RSS of each bucket is kept, so peaks survive the downsampling.
+    pub fn decimate(&self, max_points: usize) -> Self {
+        if max_points == 0 {
+            return MemoryTimeline {
+                start: self.start,
+                samples: vec![],
+                fields: vec![],
+                clock: self.clock.clone(),
+            };
+        }
+        let bucket = self.samples.len().div_ceil(max_points);
+        if bucket <= 1 {
+            return self.clone();
+        }
+        let (samples, fields) = self
+            .samples
+            .chunks(bucket)
+            .zip(self.fields.chunks(bucket))
+            .filter_map(|(samples, fields)| {
+                samples
+                    .iter()
+                    .zip(fields)
+                    .max_by_key(|(sample, _)| sample.rss)
+                    .map(|(sample, fields)| (*sample, fields.clone()))
+            })
+            .unzip();
+        MemoryTimeline {
+            start: self.start,
+            samples,
+            fields,
+            clock: self.clock.clone(),
+        }
+    }
+
+    /// Export as CSV with a `elapsed_secs,rss_bytes` header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("elapsed_secs,rss_bytes\n");
+        for sample in &self.samples {
+            let _ = writeln!(csv, "{:.6},{}", sample.elapsed.as_secs_f64(), sample.rss);
+        }
+        csv
+    }
+
+    /// Export as a JSON array of `{"elapsed_secs": .., "rss_bytes": ..}` objects.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.samples).unwrap_or_default()
+    }
+
+    /// Export as an Arrow 56 record batch (`arrow` feature), with the columns of `to_csv`
+    /// followed by one per other field of `ProcessMemoryInfo` on this platform, in bytes:
+    ///
+    /// | column | type |
+    /// | -- | -- |
+    /// | `elapsed_secs` | `Float64`, not null |
+    /// | `rss_bytes` | `UInt64`, not null |
+    /// | `resident_set_size_peak` | `UInt64`, not on Linux and Android |
+    /// | `virtual_memory_size` | `UInt64` |
+    /// | `shared`, `text` | `UInt64`, Linux and Android only |
+    /// | `virtual_reserved` | `UInt64`, Windows only |
+    /// | `phys_footprint`, `compressed` | `UInt64`, MacOS and iOS only, not with `minimal-macos` |
+    ///
+    /// The field columns are null for the samples appended with `push`, which only have the RSS.
+    #[cfg(feature = "arrow")]
+    pub fn to_record_batch(&self) -> arrow_array::RecordBatch {
+        use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt64Array};
+        use arrow_schema::{DataType, Field, Schema};
+        use std::sync::Arc;
+
+        // `resident_set_size` comes first, it is `rss_bytes` already.
+        let names: Vec<&str> = ProcessMemoryInfo::default()
+            .fields()
+            .into_iter()
+            .map(|(name, _)| name)
+            .skip(1)
+            .collect();
+        let mut fields = vec![
+            Field::new("elapsed_secs", DataType::Float64, false),
+            Field::new("rss_bytes", DataType::UInt64, false),
+        ];
+        fields.extend(
+            names
+                .iter()
+                .map(|name| Field::new(*name, DataType::UInt64, true)),
+        );
+        let schema = Schema::new(fields);
+        let elapsed: Float64Array = self
+            .samples
+            .iter()
+            .map(|sample| sample.elapsed.as_secs_f64())
+            .collect::<Vec<_>>()
+            .into();
+        let rss: UInt64Array = self
+            .samples
+            .iter()
+            .map(|sample| sample.rss)
+            .collect::<Vec<_>>()
+            .into();
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(elapsed), Arc::new(rss)];
+        for i in 1..=names.len() {
+            let column: UInt64Array = self
+                .fields
+                .iter()
+                .map(|fields| fields.as_ref().map(|fields| fields[i]))
+                .collect::<Vec<_>>()
+                .into();
+            columns.push(Arc::new(column));
+        }
+        // the columns match the schema and have the same length.
+        RecordBatch::try_new(Arc::new(schema), columns).expect("valid timeline batch")
+    }
+
+    /// Write `to_record_batch` into an uncompressed Parquet file at `path` (`arrow` feature),
+    /// for DataFusion, DuckDB and the like.
+    #[cfg(feature = "arrow")]
+    pub fn write_parquet(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let batch = self.to_record_batch();
+        let file = std::fs::File::create(path)?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None)
+            .map_err(std::io::Error::other)?;
+        writer.write(&batch).map_err(std::io::Error::other)?;
+        writer.close().map_err(std::io::Error::other)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn timeline(rss: &[u64]) -> MemoryTimeline {
+        let mut timeline = MemoryTimeline::new();
+        for (i, rss) in rss.iter().enumerate() {
+            timeline.push(TimelineSample {
+                elapsed: Duration::from_millis(i as u64 * 100),
+                rss: *rss,
+            });
+        }
+        timeline
+    }
+
+    #[test]
+    fn test_decimate_keeps_peak() {
+        let mut rss: Vec<u64> = (0..1_000).map(|i| 1_000 + i % 7).collect();
+        rss[613] = 1_000_000;
+        let timeline = timeline(&rss);
+
+        let decimated = timeline.decimate(100);
+        assert!(decimated.len() <= 100);
+        assert!(decimated.len() < timeline.len());
+        let peak = decimated.samples().iter().max_by_key(|s| s.rss).unwrap();
+        assert_eq!(peak.rss, 1_000_000);
+        assert_eq!(peak.elapsed, Duration::from_millis(61_300));
+
+        assert_eq!(timeline.decimate(2_000).len(), 1_000);
+        assert!(timeline.decimate(0).is_empty());
+    }
+
+    #[test]
+    fn test_decimate_keeps_fields() {
+        let mut timeline = MemoryTimeline::new();
+        for (i, rss) in [10u64, 30, 20, 5].iter().copied().enumerate() {
+            let info = ProcessMemoryInfo {
+                resident_set_size: rss,
+                virtual_memory_size: rss * 10,
+                ..Default::default()
+            };
+            timeline.push_info(Duration::from_secs(i as u64), &info);
+        }
+        let decimated = timeline.decimate(2);
+        let rss: Vec<u64> = decimated.samples().iter().map(|s| s.rss).collect();
+        assert_eq!(rss, [30, 20]);
+        let vsz: Vec<u64> = decimated
+            .fields
+            .iter()
+            .map(|fields| fields.as_ref().unwrap()[1])
+            .collect();
+        assert_eq!(vsz, [300, 200]);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = crate::clock::MockClock::new();
+        let mut timeline = MemoryTimeline::with_clock(clock.clone());
+        timeline.record().unwrap();
+        clock.advance(Duration::from_millis(1500));
+        timeline.record().unwrap();
+        let elapsed: Vec<Duration> = timeline.samples().iter().map(|s| s.elapsed).collect();
+        assert_eq!(elapsed, [Duration::ZERO, Duration::from_millis(1500)]);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let csv = timeline(&[10, 20]).to_csv();
+        assert_eq!(csv, "elapsed_secs,rss_bytes\n0.000000,10\n0.100000,20\n");
+
+        let mut timeline = MemoryTimeline::new();
+        timeline.record().unwrap();
+        assert!(timeline.samples()[0].rss > 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_to_json() {
+        let json = timeline(&[10, 20]).to_json();
+        assert_eq!(
+            json,
+            r#"[{"elapsed_secs":0.0,"rss_bytes":10},{"elapsed_secs":0.1,"rss_bytes":20}]"#
+        );
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_to_record_batch() {
+        use arrow_array::{Array, UInt64Array};
+
+        let mut mixed = timeline(&[10, 20, 30]);
+        let info = ProcessMemoryInfo {
+            resident_set_size: 40,
+            virtual_memory_size: 400,
+            ..Default::default()
+        };
+        mixed.push_info(Duration::from_millis(300), &info);
+        let batch = mixed.to_record_batch();
+        let fields = info.fields();
+        assert_eq!(batch.num_columns(), 1 + fields.len());
+        assert_eq!(batch.num_rows(), 4);
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names[..2], ["elapsed_secs", "rss_bytes"]);
+        assert!(names.contains(&"virtual_memory_size"));
+        let column = |name: &str| {
+            let i = names.iter().position(|n| *n == name).unwrap();
+            batch
+                .column(i)
+                .as_any()
+                .downcast_ref::<UInt64Array>()
+                .unwrap()
+                .clone()
+        };
+        let rss = column("rss_bytes");
+        assert_eq!(rss.value(2), 30);
+        assert_eq!(rss.value(3), 40);
+        assert_eq!(rss.null_count(), 0);
+        // the samples pushed with their RSS only have no other field.
+        let vsz = column("virtual_memory_size");
+        assert_eq!(vsz.null_count(), 3);
+        assert_eq!(vsz.value(3), 400);
+
+        let path =
+            std::env::temp_dir().join(format!("perf-timeline-{}.parquet", std::process::id()));
+        timeline(&[10, 20, 30]).write_parquet(&path).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let rows: usize =
+            parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap()
+                .map(|batch| batch.unwrap().num_rows())
+                .sum();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rows, 3);
+    }
+}
@@ -0,0 +1,152 @@
+use super::ProcessMemoryInfo;
+
+/// `ProcessMemoryInfo` with the same fields on every platform, the platform specific ones are
+/// `None` where they don't exist.
+///
+/// `None` means the value is not measured, while `Some(0)` is a measured zero: alerting can
+/// skip the fields it doesn't get instead of reading them as zero.
+///
+/// It trades the compile time check for code without `#[cfg]`, keep `ProcessMemoryInfo` where
+/// the native fields are known to be there.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::{get_process_memory_info, UnifiedMemoryInfo};
+/// let info = UnifiedMemoryInfo::from(get_process_memory_info().unwrap());
+/// if let Some(footprint) = info.phys_footprint {
+///     println!("footprint {} bytes", footprint);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UnifiedMemoryInfo {
+    pub resident_set_size: u64,
+    pub virtual_memory_size: u64,
+    /// Not available on Linux and Android.
+    pub resident_set_size_peak: Option<u64>,
+    /// Linux and Android only.
+    pub shared: Option<u64>,
+    /// Linux and Android only.
+    pub text: Option<u64>,
+    /// Windows only, `None` unless queried with `MemoryQuery::virtual_reserved`.
+    pub virtual_reserved: Option<u64>,
+    /// MacOS and iOS only, not with the `minimal-macos` feature.
+    pub phys_footprint: Option<u64>,
+    /// MacOS and iOS only, not with the `minimal-macos` feature.
+    pub compressed: Option<u64>,
+}
+
+impl From<ProcessMemoryInfo> for UnifiedMemoryInfo {
+    fn from(info: ProcessMemoryInfo) -> Self {
+        #[allow(unused_mut)]
+        let mut unified = UnifiedMemoryInfo {
+            resident_set_size: info.resident_set_size,
+            virtual_memory_size: info.virtual_memory_size,
+            ..Default::default()
+        };
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        {
+            unified.resident_set_size_peak = Some(info.resident_set_size_peak);
+        }
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            unified.shared = Some(info.shared);
+            unified.text = Some(info.text);
+        }
+        // a process always reserves some address space, 0 is the value of a query which didn't
+        // compute it.
+        #[cfg(target_os = "windows")]
+        {
+            unified.virtual_reserved =
+                Some(info.virtual_reserved).filter(|&reserved| reserved != 0);
+        }
+        #[cfg(all(
+            any(target_os = "macos", target_os = "ios"),
+            not(feature = "minimal-macos")
+        ))]
+        {
+            unified.phys_footprint = Some(info.phys_footprint);
+            unified.compressed = Some(info.compressed);
+        }
+        unified
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mem::get_process_memory_info;
+
+    #[test]
+    fn test_from() {
+        let info = get_process_memory_info().unwrap();
+        let unified = UnifiedMemoryInfo::from(info.clone());
+        assert_eq!(unified.resident_set_size, info.resident_set_size);
+        assert_eq!(unified.virtual_memory_size, info.virtual_memory_size);
+        assert_eq!(
+            unified.resident_set_size_peak.is_some(),
+            cfg!(not(any(target_os = "android", target_os = "linux")))
+        );
+        let linux = cfg!(any(target_os = "android", target_os = "linux"));
+        assert_eq!(unified.shared.is_some(), linux);
+        assert_eq!(unified.text.is_some(), linux);
+        // only computed on request.
+        assert_eq!(unified.virtual_reserved, None);
+        let apple = cfg!(all(
+            any(target_os = "macos", target_os = "ios"),
+            not(feature = "minimal-macos")
+        ));
+        assert_eq!(unified.phys_footprint.is_some(), apple);
+        assert_eq!(unified.compressed.is_some(), apple);
+        // every field present natively is carried over.
+        assert_eq!(
+            info.fields().len(),
+            2 + [
+                unified.resident_set_size_peak,
+                unified.shared,
+                unified.text,
+                unified.phys_footprint,
+                unified.compressed
+            ]
+            .iter()
+            .filter(|field| field.is_some())
+            .count()
+                + usize::from(cfg!(target_os = "windows"))
+        );
+    }
+
+    #[test]
+    fn test_zero_is_some() {
+        let unified = UnifiedMemoryInfo::from(ProcessMemoryInfo::default());
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            assert_eq!(unified.shared, Some(0));
+            assert_eq!(unified.text, Some(0));
+            assert_eq!(unified.resident_set_size_peak, None);
+        }
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        {
+            assert_eq!(unified.resident_set_size_peak, Some(0));
+            assert_eq!(unified.shared, None);
+        }
+        #[cfg(all(
+            any(target_os = "macos", target_os = "ios"),
+            not(feature = "minimal-macos")
+        ))]
+        assert_eq!(unified.compressed, Some(0));
+        #[cfg(not(all(
+            any(target_os = "macos", target_os = "ios"),
+            not(feature = "minimal-macos")
+        )))]
+        assert_eq!(unified.compressed, None);
+        assert_eq!(unified.virtual_reserved, None);
+
+        #[cfg(target_os = "windows")]
+        {
+            let info = crate::mem::MemoryQuery::new()
+                .virtual_reserved(true)
+                .query()
+                .unwrap();
+            assert!(UnifiedMemoryInfo::from(info).virtual_reserved.unwrap() > 0);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::clock::{Clock, SystemClock};
+use std::time::Instant;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::io::Result;
+
+/// The system wide paging counters of `/proc/vmstat` since boot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VmActivity {
+    /// Bytes paged in from block devices, `pgpgin`.
+    pub paged_in: u64,
+    /// Bytes paged out to block devices, `pgpgout`.
+    pub paged_out: u64,
+    /// Page faults which had to read from storage, `pgmajfault`.
+    pub major_faults: u64,
+}
+
+/// Read `/proc/vmstat`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_vm_activity() -> Result<VmActivity> {
+    Ok(parse_vm_activity(&crate::procfs::read_to_string("vmstat")?))
+}
+
+/// The `name value` pairs of the content of `/proc/vmstat`, a value which isn't a number is 0.
+pub(super) fn vmstat_values(vmstat: &str) -> impl Iterator<Item = (&str, u64)> {
+    vmstat.lines().filter_map(|line| {
+        let mut fields = line.split_ascii_whitespace();
+        let (key, value) = (fields.next()?, fields.next()?);
+        Some((key, value.parse().unwrap_or(0)))
+    })
+}
+
+/// The time between the two latest samples of system counters, to compute their rates.
+#[derive(Clone, Copy)]
+pub(super) struct Elapsed(f64);
+
+impl Elapsed {
+    /// `None` when no time elapsed from `then` to `now`, the rates would be infinite.
+    pub(super) fn between(then: Instant, now: Instant) -> Option<Self> {
+        let secs = now.saturating_duration_since(then).as_secs_f64();
+        if secs == 0.0 {
+            None
+        } else {
+            Some(Elapsed(secs))
+        }
+    }
+
+    /// The per second rate of a counter going from `before` to `after`, 0 if it went back,
+    /// e.g. when it was reset.
+    pub(super) fn rate(self, before: u64, after: u64) -> f64 {
+        after.saturating_sub(before) as f64 / self.0
+    }
+
+    /// The per second change of a value which may shrink, negative if it did.
+    pub(super) fn change(self, before: u64, after: u64) -> f64 {
+        (after as f64 - before as f64) / self.0
+    }
+}
+
+/// The counters of the content of `/proc/vmstat`, 0 when missing.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn parse_vm_activity(vmstat: &str) -> VmActivity {
+    let mut activity = VmActivity::default();
+    for (key, value) in vmstat_values(vmstat) {
+        match key {
+            // in KiB, whatever the page size.
+            "pgpgin" => activity.paged_in = value.saturating_mul(1024),
+            "pgpgout" => activity.paged_out = value.saturating_mul(1024),
+            "pgmajfault" => activity.major_faults = value,
+            _ => {}
+        }
+    }
+    activity
+}
+
+/// The rates of `VmActivity` counters, per second.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VmActivityRate {
+    /// bytes per second.
+    pub paged_in: f64,
+    /// bytes per second.
+    pub paged_out: f64,
+    /// major page faults per second.
+    pub major_faults: f64,
+}
+
+/// Compute the paging rates between the two latest `VmActivity` samples, a proxy of the memory
+/// pressure for capacity planning.
+///
+/// ```
+/// # use workflow_perf_monitor::mem::VmActivityMonitor;
+/// let mut monitor = VmActivityMonitor::new();
+/// # #[cfg(target_os = "linux")]
+/// monitor.sample().unwrap();
+/// # #[cfg(target_os = "linux")]
+/// monitor.sample().unwrap();
+/// println!("{:.0} bytes/sec paged in", monitor.rate().paged_in);
+/// ```
+#[derive(Default)]
+pub struct VmActivityMonitor<C = SystemClock> {
+    last: Option<(Instant, VmActivity)>,
+    rate: VmActivityRate,
+    clock: C,
+}
+
+impl VmActivityMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C: Clock> VmActivityMonitor<C> {
+    /// As `new`, with `sample` reading the time from `clock`, e.g. a `MockClock`.
+    pub fn with_clock(clock: C) -> Self {
+        VmActivityMonitor {
+            last: None,
+            rate: VmActivityRate::default(),
+            clock,
+        }
+    }
+
+    /// Read `/proc/vmstat` and observe it now, according to the clock.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn sample(&mut self) -> Result<()> {
+        let activity = get_vm_activity()?;
+        let now = self.clock.now();
+        self.observe(now, activity);
+        Ok(())
+    }
+
+    /// Compute the paging rates from the previous counters to `activity`, read at `now`.
+    ///
+    /// A read at the same `now` as the previous one is ignored, counters which went back
+    /// count as no activity.
+    pub fn observe(&mut self, now: Instant, activity: VmActivity) {
+        if let Some((then, last)) = self.last {
+            let Some(elapsed) = Elapsed::between(then, now) else {
+                return;
+            };
+            self.rate = VmActivityRate {
+                paged_in: elapsed.rate(last.paged_in, activity.paged_in),
+                paged_out: elapsed.rate(last.paged_out, activity.paged_out),
+                major_faults: elapsed.rate(last.major_faults, activity.major_faults),
+            };
+        }
+        self.last = Some((now, activity));
+    }
+
+    /// The rates between the two latest samples, all 0 until there are two.
+    pub fn rate(&self) -> VmActivityRate {
+        self.rate
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn vmstat(pgpgin: u64, pgpgout: u64, pgmajfault: u64) -> String {
+        format!(
+            "nr_free_pages 1000\npgpgin {}\npgpgout {}\npswpin 0\npgfault 99999\npgmajfault {}\n",
+            pgpgin, pgpgout, pgmajfault
+        )
+    }
+
+    #[test]
+    fn test_parse_vm_activity() {
+        assert_eq!(
+            parse_vm_activity(&vmstat(10, 20, 3)),
+            VmActivity {
+                paged_in: 10 * 1024,
+                paged_out: 20 * 1024,
+                major_faults: 3,
+            }
+        );
+        assert_eq!(
+            parse_vm_activity(&vmstat(u64::MAX, 1 << 60, 0)).paged_in,
+            u64::MAX
+        );
+        assert_eq!(
+            parse_vm_activity("nr_free_pages 1000\n"),
+            VmActivity::default()
+        );
+    }
+
+    #[test]
+    fn test_rate() {
+        let start = Instant::now();
+        let mut monitor = VmActivityMonitor::new();
+        monitor.observe(start, parse_vm_activity(&vmstat(1000, 500, 10)));
+        assert_eq!(monitor.rate(), VmActivityRate::default());
+
+        // 2000 KiB in, 1000 KiB out and 50 major faults over 2 seconds.
+        monitor.observe(
+            start + Duration::from_secs(2),
+            parse_vm_activity(&vmstat(3000, 1500, 60)),
+        );
+        assert_eq!(
+            monitor.rate(),
+            VmActivityRate {
+                paged_in: 1000.0 * 1024.0,
+                paged_out: 500.0 * 1024.0,
+                major_faults: 25.0,
+            }
+        );
+
+        // no time elapsed, the rates stay.
+        monitor.observe(
+            start + Duration::from_secs(2),
+            parse_vm_activity(&vmstat(9000, 1500, 60)),
+        );
+        assert_eq!(monitor.rate().paged_in, 1000.0 * 1024.0);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_sample() {
+        let before = get_vm_activity().unwrap();
+        let mut monitor = VmActivityMonitor::new();
+        monitor.sample().unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        monitor.sample().unwrap();
+        let after = get_vm_activity().unwrap();
+        // the rates are over at least 10ms of what the counters grew by around the samples.
+        let rate = monitor.rate();
+        let grown = |before: u64, after: u64| (after - before) as f64;
+        assert!(rate.paged_in * 0.01 <= grown(before.paged_in, after.paged_in));
+        assert!(rate.paged_out * 0.01 <= grown(before.paged_out, after.paged_out));
+        assert!(rate.major_faults * 0.01 <= grown(before.major_faults, after.major_faults));
+    }
+}
@@ -0,0 +1,44 @@
+use wasmtime::{Memory, Store};
+
+use super::ByteSize;
+
+/// The size of the linear memory `memory` of a Wasmtime guest, `memory.data_size(store)`.
+///
+/// This is the memory the guest can address, a guest memory is usually reserved up front and
+/// only its pages touched are resident, so it overlaps with the RSS of the host rather than
+/// adding to it.
+///
+/// The `wasmtime` feature depends on `wasmtime = "48"`, i.e. `>=48.0.0, <49.0.0`, with its
+/// `runtime` and `cranelift` features only. A host on any 48.x shares that Wasmtime. A host on
+/// another major version makes cargo build Wasmtime 48 as a second copy, whose `Store` and
+/// `Memory` are other types than the host's, so the call doesn't compile.
+pub fn wasm_guest_memory<T>(store: &Store<T>, memory: &Memory) -> ByteSize {
+    ByteSize(memory.data_size(store) as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmtime::{Engine, Instance, Module};
+
+    /// `(module (memory (export "memory") 2))`
+    const MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x05, 0x03, 0x01, 0x00, 0x02, // one memory of 2 pages min
+        0x07, 0x0a, 0x01, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00, // export
+    ];
+
+    #[test]
+    fn test_wasm_guest_memory() {
+        let engine = Engine::default();
+        let module = Module::new(&engine, MODULE).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+
+        // wasm pages are 64KiB.
+        assert_eq!(wasm_guest_memory(&store, &memory), ByteSize(2 * 65536));
+        memory.grow(&mut store, 1).unwrap();
+        assert_eq!(wasm_guest_memory(&store, &memory).bytes(), 3 * 65536);
+    }
+}
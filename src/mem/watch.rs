@@ -0,0 +1,91 @@
+use std::{io::Result, time::Duration};
+
+use tokio::sync::watch;
+use tokio_stream::StreamExt;
+
+use super::{memory_stream, ProcessMemoryInfo};
+
+/// The latest memory info, sampled by a single background task and shared with any number of
+/// tasks through a `tokio::sync::watch` channel.
+///
+/// The subscribers `borrow` the last sample without a syscall, and `changed` wakes them on
+/// the next one. Cloning a subscriber is cheap, it subscribes another task. A failed read is
+/// skipped, the subscribers keep the previous sample. The sampling task stops once the
+/// `MemoryWatch` and all the subscribers are dropped.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use workflow_perf_monitor::mem::MemoryWatch;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let watch = MemoryWatch::spawn(Duration::from_secs(1)).await.unwrap();
+/// let rss = watch.subscribe();
+/// tokio::spawn(async move {
+///     println!("{} bytes", rss.borrow().resident_set_size);
+/// });
+/// # }
+/// ```
+pub struct MemoryWatch {
+    receiver: watch::Receiver<ProcessMemoryInfo>,
+}
+
+impl MemoryWatch {
+    /// Read the memory info, then spawn the task sampling it every `interval` on the current
+    /// tokio runtime. The reads happen on the blocking pool, and `interval` is at least 1ms, see
+    /// `memory_stream`.
+    ///
+    /// Fails if the first read does, the subscribers always have a sample.
+    pub async fn spawn(interval: Duration) -> Result<Self> {
+        let mut samples = memory_stream(interval);
+        let first = match samples.next().await {
+            Some(first) => first?,
+            None => unreachable!("memory_stream never ends"),
+        };
+        let (sender, receiver) = watch::channel(first);
+        tokio::spawn(async move {
+            while let Some(info) = samples.next().await {
+                let Ok(info) = info else {
+                    continue;
+                };
+                if sender.send(info).is_err() {
+                    // no receiver left.
+                    break;
+                }
+            }
+        });
+        Ok(MemoryWatch { receiver })
+    }
+
+    /// A new subscriber, the current sample is already marked as seen: `changed` waits for the
+    /// next one.
+    pub fn subscribe(&self) -> watch::Receiver<ProcessMemoryInfo> {
+        let mut receiver = self.receiver.clone();
+        receiver.mark_unchanged();
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_watch() {
+        let watch = MemoryWatch::spawn(Duration::from_millis(10)).await.unwrap();
+        let mut first = watch.subscribe();
+        let mut second = first.clone();
+        assert!(first.borrow().resident_set_size > 0);
+
+        let timeout = Duration::from_secs(5);
+        tokio::time::timeout(timeout, first.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::timeout(timeout, second.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(first.borrow_and_update().resident_set_size > 0);
+        assert!(second.borrow_and_update().resident_set_size > 0);
+    }
+}
@@ -0,0 +1,172 @@
+//! Sample everything at once and query the cached values, in the style of `sysinfo`.
+//!
+//! ```
+//! # use workflow_perf_monitor::monitor::Monitor;
+//! let mut monitor = Monitor::new().unwrap();
+//! // ... some work ...
+//! monitor.refresh().unwrap();
+//! println!(
+//!     "rss {} bytes, cpu {:.2}%, {} fds",
+//!     monitor.memory().resident_set_size,
+//!     monitor.cpu() * 100.0,
+//!     monitor.fd_count()
+//! );
+//! ```
+//!
+//! `capture_all_at` reads everything once, back-to-back, as a `ProcessSnapshot` with a single
+//! timestamp.
+use std::{
+    io::Result,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cpu::{total_cpu_time, ProcessStat},
+    fd::fd_count_cur,
+    io::IOStats,
+    mem::get_process_memory_info,
+    mem::ProcessMemoryInfo,
+};
+
+/// The memory, CPU, fd and io counters of current process read together, see `capture_all_at`.
+#[derive(Clone)]
+pub struct ProcessSnapshot {
+    /// the timestamp the snapshot is attributed to, the one passed to `capture_all_at`.
+    pub at: Instant,
+    /// the time from the start of the first read to the end of the last one.
+    pub skew: Duration,
+    /// the cpu time consumed since the process started, see `cpu::total_cpu_time`.
+    pub cpu_time: Duration,
+    pub memory: ProcessMemoryInfo,
+    /// `None` on iOS, which has no io counters.
+    pub io: Option<IOStats>,
+    pub fd_count: usize,
+}
+
+/// Read the CPU time, memory, io counters and fd count of current process back-to-back, and
+/// attribute them all to `now`.
+///
+/// The reads are ordered fastest-first, the fd count last as it lists every fd, so the values
+/// are as close together as possible. The time the reads span is returned as `skew`: tens of
+/// microseconds with small fd tables, growing with the number of fds. Any failing read fails
+/// the snapshot.
+pub fn capture_all_at(now: Instant) -> Result<ProcessSnapshot> {
+    let start = Instant::now();
+    let cpu_time = total_cpu_time()?;
+    let memory = get_process_memory_info()?;
+    #[cfg(not(target_os = "ios"))]
+    let io = Some(crate::io::get_process_io_stats().map_err(std::io::Error::other)?);
+    #[cfg(target_os = "ios")]
+    let io = None;
+    let fd_count = fd_count_cur()?;
+    Ok(ProcessSnapshot {
+        at: now,
+        skew: start.elapsed(),
+        cpu_time,
+        memory,
+        io,
+        fd_count,
+    })
+}
+
+/// The latest memory, CPU and fd values of current process.
+///
+/// The getters are free, they return the values read by the last `refresh`.
+pub struct Monitor {
+    stat: ProcessStat,
+    memory: ProcessMemoryInfo,
+    cpu: f64,
+    fd_count: usize,
+    refreshed_at: Instant,
+}
+
+impl Monitor {
+    /// Take a first sample, the cpu usage is 0 until the next `refresh`.
+    pub fn new() -> Result<Self> {
+        Ok(Monitor {
+            stat: ProcessStat::cur()?,
+            memory: get_process_memory_info()?,
+            cpu: 0.0,
+            fd_count: fd_count_cur()?,
+            refreshed_at: Instant::now(),
+        })
+    }
+
+    /// Sample everything again, the cpu usage is measured since the previous refresh.
+    ///
+    /// The cached values are left unchanged if any of them fails.
+    pub fn refresh(&mut self) -> Result<()> {
+        let memory = get_process_memory_info()?;
+        let fd_count = fd_count_cur()?;
+        self.cpu = self.stat.cpu()?;
+        self.memory = memory;
+        self.fd_count = fd_count;
+        self.refreshed_at = Instant::now();
+        Ok(())
+    }
+
+    pub fn memory(&self) -> &ProcessMemoryInfo {
+        &self.memory
+    }
+
+    /// The unnormalized cpu usage, see [`crate::cpu`].
+    pub fn cpu(&self) -> f64 {
+        self.cpu
+    }
+
+    pub fn fd_count(&self) -> usize {
+        self.fd_count
+    }
+
+    /// When the cached values were read.
+    pub fn refreshed_at(&self) -> Instant {
+        self.refreshed_at
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_refresh() {
+        let mut monitor = Monitor::new().unwrap();
+        let refreshed_at = monitor.refreshed_at();
+        let fd_count = monitor.fd_count();
+        assert_eq!(monitor.cpu(), 0.0);
+
+        // every item goes through black_box, the sum can't be folded in a closed form.
+        let x = std::hint::black_box(100_000u64);
+        for i in 0..100 {
+            std::hint::black_box((0..x + i).map(std::hint::black_box).sum::<u64>());
+        }
+        let files: Vec<_> = (0..10)
+            .map(|_| std::fs::File::open("Cargo.toml").unwrap())
+            .collect();
+        assert_eq!(monitor.cpu(), 0.0);
+        assert_eq!(monitor.fd_count(), fd_count);
+        assert_eq!(monitor.refreshed_at(), refreshed_at);
+
+        monitor.refresh().unwrap();
+        assert!(monitor.cpu() > 0.0);
+        assert!(monitor.refreshed_at() > refreshed_at);
+        assert!(monitor.memory().resident_set_size > 0);
+        drop(files);
+    }
+
+    #[test]
+    fn test_capture_all_at() {
+        let now = Instant::now();
+        let snapshot = capture_all_at(now).unwrap();
+        assert_eq!(snapshot.at, now);
+        assert!(snapshot.skew < Duration::from_secs(1));
+        assert!(snapshot.cpu_time > Duration::ZERO);
+        assert!(snapshot.memory.resident_set_size > 0);
+        assert!(snapshot.fd_count > 0);
+        #[cfg(not(target_os = "ios"))]
+        assert!(snapshot.io.is_some());
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(capture_all_at(later).unwrap().at, later);
+    }
+}
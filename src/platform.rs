@@ -0,0 +1,60 @@
+//! The platforms this crate supports, for build scripts to check a target early.
+
+/// The `target_os` values this crate supports.
+pub const SUPPORTED: &[&str] = &["linux", "android", "macos", "ios", "windows"];
+
+/// Whether `target`, a `target_os` value like `CARGO_CFG_TARGET_OS`, is in `SUPPORTED`.
+///
+/// ```
+/// # use workflow_perf_monitor::is_supported;
+/// const _: () = assert!(is_supported("linux"));
+/// assert!(!is_supported("freebsd"));
+/// ```
+pub const fn is_supported(target: &str) -> bool {
+    let mut i = 0;
+    while i < SUPPORTED.len() {
+        if str_eq(SUPPORTED[i], target) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Whether this build targets a platform of `SUPPORTED`.
+pub const CURRENT_SUPPORTED: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "windows"
+));
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_current_supported() {
+        assert!(CURRENT_SUPPORTED);
+        assert!(is_supported(std::env::consts::OS));
+        assert!(SUPPORTED.iter().all(|os| is_supported(os)));
+        assert!(!is_supported("linu"));
+        assert!(!is_supported(""));
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    io::{ErrorKind, Result},
+    path::PathBuf,
+};
+
+/// (pid, comm) of all processes in `/proc`.
+pub fn processes() -> Result<Vec<(u32, String)>> {
+    let mut processes = vec![];
+    for entry in std::fs::read_dir(crate::procfs::procfs_root())? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        match std::fs::read_to_string(entry.path().join("comm")) {
+            Ok(comm) => processes.push((pid, comm.trim_end_matches('\n').to_owned())),
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied) => {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(processes)
+}
+
+/// (pid, `/proc/{pid}/exe` target) of all processes whose executable we are allowed to resolve.
+pub fn executables() -> Result<Vec<(u32, PathBuf)>> {
+    let mut executables = vec![];
+    for entry in std::fs::read_dir(crate::procfs::procfs_root())? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        match std::fs::read_link(entry.path().join("exe")) {
+            Ok(exe) => executables.push((pid, exe)),
+            // kernel threads have no executable, the link of other users' processes can't be
+            // read without privileges.
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied) => {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(executables)
+}
+
+/// (comm, cmdline) of current process.
+pub fn identity() -> Result<(String, Vec<String>)> {
+    let comm = crate::procfs::read_to_string("self/comm")?;
+    let cmdline = crate::procfs::read("self/cmdline")?;
+    Ok((
+        comm.trim_end_matches('\n').to_owned(),
+        super::identity::parse_cmdline(&cmdline),
+    ))
+}
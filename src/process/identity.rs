@@ -0,0 +1,106 @@
+use std::io::Result;
+
+/// Who current process is, to tag its metrics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProcessIdentity {
+    pub pid: u32,
+    /// the name the OS reports, as for `find_processes_by_name`: `comm` on Linux, truncated to
+    /// 15 bytes, the image base name on Windows, `proc_name` on MacOS.
+    pub name: String,
+    /// the arguments, the program first. Invalid UTF-8 is replaced with `U+FFFD`.
+    pub cmdline: Vec<String>,
+}
+
+/// Get the pid, name and command line of current process.
+///
+/// The command line is the one the process was started with, not `std::env::args` which a
+/// runtime may have altered: `/proc/self/cmdline` on Linux and android, `KERN_PROCARGS2` on
+/// MacOS. On Windows and iOS it comes from std, which parses `GetCommandLineW` on Windows.
+/// A process rewriting its own argv, e.g. with `setproctitle`, shows the rewritten one on Linux.
+pub fn get_process_identity() -> Result<ProcessIdentity> {
+    let (name, cmdline) = super::platform::identity()?;
+    Ok(ProcessIdentity {
+        pid: std::process::id(),
+        name,
+        cmdline,
+    })
+}
+
+/// Split the NUL-terminated arguments of `/proc/<pid>/cmdline`.
+///
+/// The trailing NUL ends the last argument, it doesn't start an empty one. Empty arguments in
+/// between are kept.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+pub(crate) fn parse_cmdline(cmdline: &[u8]) -> Vec<String> {
+    let cmdline = cmdline.strip_suffix(b"\0").unwrap_or(cmdline);
+    if cmdline.is_empty() {
+        // kernel threads and zombies have no command line.
+        return vec![];
+    }
+    cmdline
+        .split(|b| *b == 0)
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect()
+}
+
+/// Parse the `KERN_PROCARGS2` buffer of MacOS: `argc` as a native `int`, the executable path,
+/// NUL padding, then `argc` NUL-terminated arguments and the environment.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub(crate) fn parse_procargs2(buf: &[u8]) -> Vec<String> {
+    const ARGC: usize = std::mem::size_of::<i32>();
+    let Some((argc, rest)) = buf.split_first_chunk::<ARGC>() else {
+        return vec![];
+    };
+    let argc = i32::from_ne_bytes(*argc).max(0) as usize;
+    // skip the executable path and its padding.
+    let exe_end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+    let args_start = rest[exe_end..]
+        .iter()
+        .position(|b| *b != 0)
+        .map_or(rest.len(), |pad| exe_end + pad);
+    rest[args_start..]
+        .split(|b| *b == 0)
+        .take(argc)
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_cmdline() {
+        assert_eq!(
+            parse_cmdline(b"/usr/bin/server\0--port\08080\0"),
+            ["/usr/bin/server", "--port", "8080"]
+        );
+        assert_eq!(parse_cmdline(b"a\0\0b\0"), ["a", "", "b"]);
+        assert_eq!(parse_cmdline(b"no-trailing-nul"), ["no-trailing-nul"]);
+        assert_eq!(parse_cmdline(b""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_procargs2() {
+        let mut buf = 2i32.to_ne_bytes().to_vec();
+        buf.extend_from_slice(b"/usr/bin/server\0\0\0\0server\0-v\0HOME=/root\0");
+        assert_eq!(parse_procargs2(&buf), ["server", "-v"]);
+        assert_eq!(parse_procargs2(b"\x01"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_get_process_identity() {
+        let identity = get_process_identity().unwrap();
+        assert_eq!(identity.pid, std::process::id());
+
+        let exe = std::env::current_exe().unwrap();
+        let exe_name = exe.file_name().unwrap().to_string_lossy();
+        // comm is truncated to 15 bytes.
+        let prefix: String = exe_name.chars().take(10).collect();
+        assert!(identity.name.starts_with(&prefix), "{:?}", identity.name);
+
+        assert!(!identity.cmdline.is_empty());
+        assert!(identity.cmdline[0].contains(&prefix));
+    }
+}
@@ -0,0 +1,29 @@
+// There is no public api to list the processes for ios.
+
+pub fn processes() -> std::io::Result<Vec<(u32, String)>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "listing processes is not supported on iOS",
+    ))
+}
+
+pub fn executables() -> std::io::Result<Vec<(u32, std::path::PathBuf)>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "listing processes is not supported on iOS",
+    ))
+}
+
+/// The `proc_*` family is not available, std knows the executable and the arguments of current
+/// process.
+pub fn identity() -> std::io::Result<(String, Vec<String>)> {
+    let exe = std::env::current_exe()?;
+    let name = exe
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let cmdline = std::env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    Ok((name, cmdline))
+}
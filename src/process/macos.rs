@@ -0,0 +1,98 @@
+use std::{
+    ffi::OsStr, io::Error, io::Result, os::raw::c_int, os::unix::ffi::OsStrExt, path::PathBuf,
+};
+
+fn process_ids() -> Result<Vec<c_int>> {
+    // A first call with a null buffer returns the number of pids.
+    let count = unsafe { libc::proc_listallpids(std::ptr::null_mut(), 0) };
+    if count <= 0 {
+        return Err(Error::last_os_error());
+    }
+    // Leave room for processes spawned in between.
+    let mut pids: Vec<c_int> = vec![0; count as usize + 64];
+    let count = unsafe {
+        libc::proc_listallpids(
+            pids.as_mut_ptr() as *mut _,
+            (pids.len() * std::mem::size_of::<c_int>()) as c_int,
+        )
+    };
+    if count <= 0 {
+        return Err(Error::last_os_error());
+    }
+    pids.truncate(count as usize);
+    Ok(pids)
+}
+
+/// (pid, proc_name) of all processes.
+pub fn processes() -> Result<Vec<(u32, String)>> {
+    let pids = process_ids()?;
+    Ok(pids
+        .into_iter()
+        .filter_map(|pid| process_name(pid).map(|name| (pid as u32, name)))
+        .collect())
+}
+
+fn process_name(pid: c_int) -> Option<String> {
+    let mut buf = [0u8; 4 * libc::MAXCOMLEN];
+    let len = unsafe { libc::proc_name(pid, buf.as_mut_ptr() as *mut _, buf.len() as u32) };
+    // 0 when the process exited, or isn't visible to us.
+    if len <= 0 {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&buf[..len as usize]).into_owned())
+}
+
+/// (pid, proc_pidpath) of all processes.
+pub fn executables() -> Result<Vec<(u32, PathBuf)>> {
+    let mut buf = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    Ok(process_ids()?
+        .into_iter()
+        .filter_map(|pid| {
+            let len =
+                unsafe { libc::proc_pidpath(pid, buf.as_mut_ptr() as *mut _, buf.len() as u32) };
+            if len <= 0 {
+                return None;
+            }
+            let path = PathBuf::from(OsStr::from_bytes(&buf[..len as usize]));
+            Some((pid as u32, path))
+        })
+        .collect())
+}
+
+/// (proc_name, `KERN_PROCARGS2` arguments) of current process.
+pub fn identity() -> Result<(String, Vec<String>)> {
+    let pid = std::process::id() as c_int;
+    let name = process_name(pid).ok_or_else(Error::last_os_error)?;
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+    let mut size: libc::size_t = 0;
+    // A first call with a null buffer returns the size.
+    let ret = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as u32,
+            std::ptr::null_mut(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::last_os_error());
+    }
+    let mut buf = vec![0u8; size];
+    let ret = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as u32,
+            buf.as_mut_ptr() as *mut _,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::last_os_error());
+    }
+    buf.truncate(size);
+    Ok((name, super::identity::parse_procargs2(&buf)))
+}
@@ -0,0 +1,178 @@
+//! Find processes of the system.
+//!
+//! ```
+//! use workflow_perf_monitor::process::find_processes_by_name;
+//!
+//! let pids = find_processes_by_name("nginx*").unwrap();
+//! ```
+//!
+//! `find_processes_by_executable` matches the full path of the executable instead.
+//!
+//! `get_process_identity` returns the pid, name and command line of current process, to tag its
+//! metrics.
+//!
+//! `get_process_priority` and `set_process_priority` read and change the nice value of current
+//! process.
+//!
+//! ## Bottom Layer Interface
+//!
+//! - Windows: [EnumProcesses] + [GetModuleBaseName]
+//! - Linux & android: [/proc/{pid}/comm][man5]
+//! - MacOS: `proc_listallpids` + `proc_name`
+//! - Executable path: [/proc/{pid}/exe][man5] on Linux and android, [QueryFullProcessImageName] on
+//!   Windows, `proc_pidpath` on MacOS.
+//! - iOS: unsupported, the `proc_*` family is not available.
+//!
+//! [EnumProcesses]: https://learn.microsoft.com/en-us/windows/win32/api/psapi/nf-psapi-enumprocesses
+//! [GetModuleBaseName]: https://learn.microsoft.com/en-us/windows/win32/api/psapi/nf-psapi-getmodulebasenamew
+//! [QueryFullProcessImageName]: https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-queryfullprocessimagenamew
+//! [man5]: https://man7.org/linux/man-pages/man5/proc.5.html
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod android_linux;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use android_linux as platform;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "windows")]
+use windows as platform;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+#[cfg(target_os = "ios")]
+mod ios;
+#[cfg(target_os = "ios")]
+use ios as platform;
+
+mod identity;
+pub use identity::{get_process_identity, ProcessIdentity};
+
+mod priority;
+pub use priority::{get_process_priority, set_process_priority};
+
+use std::{io::Result, path::Path};
+
+/// Check `name` against `pattern`.
+///
+/// A pattern containing `*` or `?` is a glob which must match the whole name,
+/// `*` matching any sequence and `?` any single character.
+/// Any other pattern is matched as a substring.
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Iterative wildcard matching, backtracking to the last `*` on a mismatch.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// return the pids of the processes whose name matches `pattern`, see [`name_matches`].
+///
+/// The name is the one the OS reports: `comm` on Linux, which the kernel truncates to 15 bytes,
+/// the image base name including `.exe` on Windows, `proc_name` on MacOS.
+/// Processes that can't be inspected, for lack of rights or because they exited during the scan,
+/// are skipped.
+pub fn find_processes_by_name(pattern: &str) -> Result<Vec<u32>> {
+    Ok(platform::processes()?
+        .into_iter()
+        .filter(|(_, name)| name_matches(pattern, name))
+        .map(|(pid, _)| pid)
+        .collect())
+}
+
+/// return the pids of the processes running the executable at `path`.
+///
+/// Both sides are canonicalized before comparing, so symlinks and relative paths to the
+/// executable match. On Linux a process whose executable was deleted or replaced since it started
+/// doesn't match, its link points to the old file.
+/// Processes that can't be inspected are skipped, as for [`find_processes_by_name`].
+pub fn find_processes_by_executable(path: &Path) -> Result<Vec<u32>> {
+    let target = std::fs::canonicalize(path)?;
+    Ok(platform::executables()?
+        .into_iter()
+        .filter(|(_, exe)| {
+            *exe == target || std::fs::canonicalize(exe).is_ok_and(|exe| exe == target)
+        })
+        .map(|(pid, _)| pid)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("nginx", "nginx"));
+        assert!(name_matches("nginx", "nginx: worker"));
+        assert!(!name_matches("nginx", "apache2"));
+        assert!(name_matches("ngin?", "nginx"));
+        assert!(!name_matches("ngin?", "nginx: worker"));
+        assert!(name_matches("ng*x", "nginx"));
+        assert!(name_matches("*worker*", "nginx: worker process"));
+        assert!(name_matches("*", ""));
+        assert!(!name_matches("a*b", "abc"));
+        assert!(name_matches("a*b*c", "aXbYbZc"));
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn test_find_self_by_name() {
+        let exe = std::env::current_exe().unwrap();
+        let name: String = exe
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .chars()
+            .take(10)
+            .collect();
+        let pid = std::process::id();
+
+        assert!(find_processes_by_name(&name).unwrap().contains(&pid));
+        assert!(find_processes_by_name(&format!("{}*", name))
+            .unwrap()
+            .contains(&pid));
+        assert!(!find_processes_by_name("no-such-process-name")
+            .unwrap()
+            .contains(&pid));
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn test_find_self_by_executable() {
+        let exe = std::env::current_exe().unwrap();
+        let pid = std::process::id();
+        assert!(find_processes_by_executable(&exe).unwrap().contains(&pid));
+
+        let missing = exe.with_file_name("no-such-executable");
+        assert!(find_processes_by_executable(&missing).is_err());
+    }
+}
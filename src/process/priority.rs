@@ -0,0 +1,175 @@
+use std::io::{Error, ErrorKind, Result};
+
+const NICE_MIN: i32 = -20;
+const NICE_MAX: i32 = 19;
+
+fn clamp_nice(nice: i32) -> i32 {
+    nice.clamp(NICE_MIN, NICE_MAX)
+}
+
+/// Get the nice value of current process, from `-20`, the highest priority, to `19`.
+///
+/// On Linux the nice value belongs to each thread: this is the one of the calling thread,
+/// which new threads inherit.
+///
+/// On Windows the priority class is mapped to a nice value:
+///
+/// | class | nice |
+/// | -- | -- |
+/// | `REALTIME_PRIORITY_CLASS`, `HIGH_PRIORITY_CLASS` | -20 |
+/// | `ABOVE_NORMAL_PRIORITY_CLASS` | -10 |
+/// | `NORMAL_PRIORITY_CLASS` | 0 |
+/// | `BELOW_NORMAL_PRIORITY_CLASS` | 10 |
+/// | `IDLE_PRIORITY_CLASS` | 19 |
+pub fn get_process_priority() -> Result<i32> {
+    platform_get_priority()
+}
+
+/// Set the nice value of current process, `nice` is clamped to `-20..=19`.
+///
+/// Lowering the nice value requires privileges, `CAP_SYS_NICE` on Linux or root on MacOS,
+/// `ErrorKind::PermissionDenied` is returned otherwise. On Linux only the calling thread is
+/// changed, see [`get_process_priority`].
+///
+/// On Windows the nice value is mapped to the priority class:
+///
+/// | nice | class |
+/// | -- | -- |
+/// | `-20..=-16` | `HIGH_PRIORITY_CLASS` |
+/// | `-15..=-6` | `ABOVE_NORMAL_PRIORITY_CLASS` |
+/// | `-5..=4` | `NORMAL_PRIORITY_CLASS` |
+/// | `5..=14` | `BELOW_NORMAL_PRIORITY_CLASS` |
+/// | `15..=19` | `IDLE_PRIORITY_CLASS` |
+///
+/// `REALTIME_PRIORITY_CLASS` is never set, it can starve the system.
+pub fn set_process_priority(nice: i32) -> Result<()> {
+    let nice = clamp_nice(nice);
+    platform_set_priority(nice).map_err(|e| {
+        if e.kind() == ErrorKind::PermissionDenied {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "setting the nice value to {} requires privileges: {}",
+                    nice, e
+                ),
+            )
+        } else {
+            e
+        }
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+use crate::utils::errno::errno;
+
+#[cfg(not(target_os = "windows"))]
+fn platform_get_priority() -> Result<i32> {
+    // -1 is a valid nice value, errors are told apart by errno only.
+    unsafe { *errno() = 0 };
+    let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+    if nice == -1 && unsafe { *errno() } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(nice)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn platform_set_priority(nice: i32) -> Result<()> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+use windows_sys::Win32::System::Threading::{
+    ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+    IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS, REALTIME_PRIORITY_CLASS,
+};
+
+#[cfg(target_os = "windows")]
+fn class_to_nice(class: u32) -> i32 {
+    match class {
+        REALTIME_PRIORITY_CLASS | HIGH_PRIORITY_CLASS => -20,
+        ABOVE_NORMAL_PRIORITY_CLASS => -10,
+        BELOW_NORMAL_PRIORITY_CLASS => 10,
+        IDLE_PRIORITY_CLASS => 19,
+        _ => 0,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn nice_to_class(nice: i32) -> u32 {
+    match nice {
+        i32::MIN..=-16 => HIGH_PRIORITY_CLASS,
+        -15..=-6 => ABOVE_NORMAL_PRIORITY_CLASS,
+        -5..=4 => NORMAL_PRIORITY_CLASS,
+        5..=14 => BELOW_NORMAL_PRIORITY_CLASS,
+        _ => IDLE_PRIORITY_CLASS,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn platform_get_priority() -> Result<i32> {
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetPriorityClass};
+    let class = unsafe { GetPriorityClass(GetCurrentProcess()) };
+    if class == 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(class_to_nice(class))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_set_priority(nice: i32) -> Result<()> {
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, SetPriorityClass};
+    if unsafe { SetPriorityClass(GetCurrentProcess(), nice_to_class(nice)) } == 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clamp_nice() {
+        assert_eq!(clamp_nice(-100), -20);
+        assert_eq!(clamp_nice(-20), -20);
+        assert_eq!(clamp_nice(5), 5);
+        assert_eq!(clamp_nice(19), 19);
+        assert_eq!(clamp_nice(100), 19);
+    }
+
+    #[test]
+    fn test_get_process_priority() {
+        let nice = get_process_priority().unwrap();
+        assert!((NICE_MIN..=NICE_MAX).contains(&nice));
+        // setting the current value never requires privileges.
+        set_process_priority(nice).unwrap();
+        assert_eq!(get_process_priority().unwrap(), nice);
+    }
+
+    // the nice value is per thread on Linux, a thread can lower its priority on its own.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_set_process_priority() {
+        std::thread::spawn(|| {
+            set_process_priority(100).unwrap();
+            assert_eq!(get_process_priority().unwrap(), NICE_MAX);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_priority_class_mapping() {
+        for nice in NICE_MIN..=NICE_MAX {
+            let class = nice_to_class(nice);
+            assert_eq!(nice_to_class(class_to_nice(class)), class);
+        }
+        assert_eq!(nice_to_class(0), NORMAL_PRIORITY_CLASS);
+        assert_eq!(class_to_nice(REALTIME_PRIORITY_CLASS), -20);
+    }
+}
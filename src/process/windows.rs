@@ -0,0 +1,94 @@
+use std::io::{Error, Result};
+use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+use windows_sys::Win32::Foundation::FALSE;
+use windows_sys::Win32::System::ProcessStatus::{EnumProcesses, GetModuleBaseNameW};
+use windows_sys::Win32::System::Threading::{
+    OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    PROCESS_VM_READ,
+};
+
+use crate::utils::ptr_upgrade::HandleUpgrade;
+use crate::utils::windows_handle::Handle;
+
+fn process_ids() -> Result<Vec<u32>> {
+    let mut pids: Vec<u32> = vec![0; 1024];
+    loop {
+        let cb = (pids.len() * std::mem::size_of::<u32>()) as u32;
+        let mut needed = 0;
+        let ret = unsafe { EnumProcesses(pids.as_mut_ptr(), cb, &mut needed) };
+        if ret == 0 {
+            return Err(Error::last_os_error());
+        }
+        // A full buffer means there may be more processes, retry with a larger one.
+        if needed < cb {
+            pids.truncate(needed as usize / std::mem::size_of::<u32>());
+            return Ok(pids);
+        }
+        pids.resize(pids.len() * 2, 0);
+    }
+}
+
+fn process_name(pid: u32) -> Option<String> {
+    let handle = unsafe {
+        OpenProcess(
+            PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_VM_READ,
+            FALSE,
+            pid,
+        )
+    }
+    .upgrade()
+    .map(|x| unsafe { Handle::new(x) })?;
+    let mut buf = [0u16; 260];
+    let len =
+        unsafe { GetModuleBaseNameW(handle.as_handle(), 0, buf.as_mut_ptr(), buf.len() as u32) };
+    if len == 0 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&buf[..len as usize]))
+}
+
+/// (pid, image base name) of all processes we are allowed to open.
+pub fn processes() -> Result<Vec<(u32, String)>> {
+    Ok(process_ids()?
+        .into_iter()
+        .filter_map(|pid| process_name(pid).map(|name| (pid, name)))
+        .collect())
+}
+
+fn process_image(pid: u32) -> Option<PathBuf> {
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) }
+        .upgrade()
+        .map(|x| unsafe { Handle::new(x) })?;
+    let mut buf = [0u16; 1024];
+    let mut len = buf.len() as u32;
+    let ret = unsafe {
+        QueryFullProcessImageNameW(
+            handle.as_handle(),
+            PROCESS_NAME_WIN32,
+            buf.as_mut_ptr(),
+            &mut len,
+        )
+    };
+    if ret == 0 {
+        return None;
+    }
+    Some(OsString::from_wide(&buf[..len as usize]).into())
+}
+
+/// (pid, full image path) of all processes we are allowed to open.
+pub fn executables() -> Result<Vec<(u32, PathBuf)>> {
+    Ok(process_ids()?
+        .into_iter()
+        .filter_map(|pid| process_image(pid).map(|path| (pid, path)))
+        .collect())
+}
+
+/// (image base name, command line) of current process.
+pub fn identity() -> Result<(String, Vec<String>)> {
+    let name = process_name(std::process::id()).ok_or_else(Error::last_os_error)?;
+    // std splits `GetCommandLineW` with the rules of the MSVC runtime.
+    let cmdline = std::env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    Ok((name, cmdline))
+}
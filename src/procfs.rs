@@ -0,0 +1,193 @@
+//! The root of procfs the Linux readers use, `/proc` by default.
+//!
+//! Like the Prometheus node exporter, the `HOST_PROC` environment variable moves it, for
+//! instance to the `/proc` of the host bind mounted in a container. The host wide files,
+//! like `meminfo` or the other processes, are then the ones of the host, while `self` still
+//! resolves to current process in the pid namespace of the mount.
+//!
+//! ```
+//! # use workflow_perf_monitor::procfs::{procfs_root, set_procfs_root};
+//! set_procfs_root("/host/proc");
+//! assert_eq!(procfs_root(), std::path::Path::new("/host/proc"));
+//! ```
+//!
+//! A read of procfs may block for good on a frozen host, e.g. on the `mmap_lock` of a process
+//! stuck in a hung NFS fault, and it can't be interrupted. `set_read_timeout` bounds how long
+//! the samplers wait for the files they read.
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, OnceLock, RwLock,
+    },
+    time::Duration,
+};
+
+static ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+/// The timeout of `set_read_timeout` in nanoseconds, 0 for none.
+static TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+fn default_root() -> &'static PathBuf {
+    static DEFAULT: OnceLock<PathBuf> = OnceLock::new();
+    DEFAULT.get_or_init(|| match std::env::var_os("HOST_PROC") {
+        Some(root) if !root.is_empty() => PathBuf::from(root),
+        _ => PathBuf::from("/proc"),
+    })
+}
+
+/// The root of procfs: the one of `set_procfs_root`, or else `HOST_PROC` when it was set at
+/// the first read, or else `/proc`.
+pub fn procfs_root() -> PathBuf {
+    match &*ROOT.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(root) => root.clone(),
+        None => default_root().clone(),
+    }
+}
+
+/// Read procfs under `root` from now on, whatever `HOST_PROC` is.
+pub fn set_procfs_root(root: impl Into<PathBuf>) {
+    *ROOT.write().unwrap_or_else(|e| e.into_inner()) = Some(root.into());
+}
+
+/// `path` under the root of procfs, e.g. `self/statm`.
+pub(crate) fn path(path: &str) -> PathBuf {
+    match &*ROOT.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(root) => root.join(path),
+        None => default_root().join(path),
+    }
+}
+
+/// Give up on reading a procfs file after `timeout`, with `ErrorKind::TimedOut`, `None` to
+/// wait as long as it takes, the default.
+///
+/// With a timeout every read runs on a new thread the caller waits for, which costs a thread
+/// spawn, tens of microseconds, on top of the read. A read which times out leaves its thread
+/// blocked until the kernel completes it, possibly never: each timeout may leak a thread, and
+/// its stack. This covers the files the samplers read whole, like `statm`, `status` or
+/// `meminfo`, not the directory listings nor `StatmReader`, which must not spawn threads.
+pub fn set_read_timeout(timeout: Option<Duration>) {
+    let nanos = timeout.map_or(0, |timeout| {
+        u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX).max(1)
+    });
+    TIMEOUT.store(nanos, Ordering::Relaxed);
+}
+
+/// The timeout of `set_read_timeout`.
+pub fn read_timeout() -> Option<Duration> {
+    match TIMEOUT.load(Ordering::Relaxed) {
+        0 => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+/// Read `path` under the root of procfs, within the timeout of `set_read_timeout`.
+pub(crate) fn read_to_string(path: &str) -> Result<String> {
+    let path = self::path(path);
+    read_with_timeout(read_timeout(), move || std::fs::read_to_string(path))
+}
+
+/// Same as `read_to_string` for the files which may not be UTF-8, like `cmdline`.
+pub(crate) fn read(path: &str) -> Result<Vec<u8>> {
+    let path = self::path(path);
+    read_with_timeout(read_timeout(), move || std::fs::read(path))
+}
+
+fn read_with_timeout<T, F>(timeout: Option<Duration>, read: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        return read();
+    };
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("perf-procfs-read".into())
+        .spawn(move || {
+            // the caller may have given up already.
+            let _ = tx.send(read());
+        })?;
+    rx.recv_timeout(timeout).unwrap_or_else(|e| match e {
+        mpsc::RecvTimeoutError::Timeout => Err(Error::new(
+            ErrorKind::TimedOut,
+            format!("procfs read timed out after {:?}", timeout),
+        )),
+        mpsc::RecvTimeoutError::Disconnected => Err(Error::other("procfs reader thread panicked")),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CHILD: &str = "PERF_MONITOR_HOST_PROC_CHILD";
+
+    // The root is global and read once, so the reads are checked in a child process: the
+    // other tests keep reading the real procfs.
+    #[test]
+    fn test_host_proc() {
+        if std::env::var_os(CHILD).is_some() {
+            let info = crate::mem::get_system_memory_info().unwrap();
+            assert_eq!(info.total, 1024 << 10);
+            let info = crate::mem::get_process_memory_info().unwrap();
+            assert_eq!(
+                info.resident_set_size,
+                20 * crate::mem::memory_granularity()
+            );
+            return;
+        }
+
+        let root = std::env::temp_dir().join(format!("perf-host-proc-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("self")).unwrap();
+        std::fs::write(
+            root.join("meminfo"),
+            "MemTotal: 1024 kB\nMemFree: 512 kB\nMemAvailable: 768 kB\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("self/statm"), "100 20 10 5 0 50 0\n").unwrap();
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "procfs::test::test_host_proc",
+                "--test-threads=1",
+            ])
+            .env(CHILD, "1")
+            .env("HOST_PROC", &root)
+            .output()
+            .unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 passed"));
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let slow = || {
+            std::thread::sleep(Duration::from_secs(2));
+            Ok(String::from("late"))
+        };
+        let start = std::time::Instant::now();
+        let err = read_with_timeout(Some(Duration::from_millis(20)), slow).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let fast = read_with_timeout(Some(Duration::from_secs(5)), || Ok(1)).unwrap();
+        assert_eq!(fast, 1);
+        let err = read_with_timeout::<(), _>(None, || Err(Error::from(ErrorKind::NotFound)));
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::NotFound);
+        assert!(read_to_string("self/statm").unwrap().ends_with('\n'));
+    }
+
+    #[test]
+    fn test_path() {
+        assert!(path("self/statm").ends_with("self/statm"));
+        assert!(path("self/statm").starts_with(procfs_root()));
+    }
+}
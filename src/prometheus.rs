@@ -0,0 +1,483 @@
+//! Serve the metrics of current process in the Prometheus text format.
+//!
+//! A background thread samples on an interval and caches the exposition, so a scrape only
+//! copies a string: its latency doesn't depend on the sampling, and scraping often doesn't
+//! sample more.
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use workflow_perf_monitor::prometheus::PrometheusExporter;
+//! let exporter = PrometheusExporter::builder()
+//!     .interval(Duration::from_secs(5))
+//!     .with_cpu(true)
+//!     .with_io(true)
+//!     .build();
+//! // in the `/metrics` handler:
+//! let body = exporter.render();
+//! ```
+//!
+//! `fields` narrows the memory metrics down to some fields of
+//! [`ProcessMemoryInfo`](crate::mem::ProcessMemoryInfo):
+//!
+//! ```
+//! # use workflow_perf_monitor::prometheus::PrometheusExporter;
+//! let exporter = PrometheusExporter::builder()
+//!     .fields(&["resident_set_size"])
+//!     .unwrap()
+//!     .build();
+//! assert!(!exporter.render().contains("process_virtual_memory_bytes"));
+//! ```
+//!
+//! `with_rss_histogram` exports the distribution of the RSS samples as a histogram instead of
+//! the last one as a gauge, see [`RssHistogram`].
+use std::{
+    fmt::Write,
+    io::Result,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    mem::ProcessMemoryInfo,
+    runner::{PollHandle, PollRunner},
+};
+
+/// The bucket bounds of `RssHistogram::default`, powers of two from 1MiB to 64GiB.
+pub const DEFAULT_RSS_BUCKETS: [u64; 17] = [
+    1 << 20,
+    1 << 21,
+    1 << 22,
+    1 << 23,
+    1 << 24,
+    1 << 25,
+    1 << 26,
+    1 << 27,
+    1 << 28,
+    1 << 29,
+    1 << 30,
+    1 << 31,
+    1 << 32,
+    1 << 33,
+    1 << 34,
+    1 << 35,
+    1 << 36,
+];
+
+/// The memory fields exported unless `fields` is called.
+const DEFAULT_FIELDS: [&str; 2] = ["resident_set_size", "virtual_memory_size"];
+
+/// Configures a [`PrometheusExporter`], the memory metrics are always exported, see `fields`.
+pub struct PrometheusExporterBuilder {
+    interval: Duration,
+    cpu: bool,
+    io: bool,
+    fields: Vec<&'static str>,
+    histogram: Option<RssHistogram>,
+}
+
+impl PrometheusExporterBuilder {
+    /// How often to sample, 15 seconds by default, the default scrape interval of Prometheus.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Export `process_cpu_seconds_total`.
+    pub fn with_cpu(mut self, cpu: bool) -> Self {
+        self.cpu = cpu;
+        self
+    }
+
+    /// Export `process_io_read_bytes_total` and `process_io_write_bytes_total`, not on iOS.
+    pub fn with_io(mut self, io: bool) -> Self {
+        self.io = io;
+        self
+    }
+
+    /// Accumulate the RSS samples into a histogram, exported as
+    /// `process_resident_memory_bytes` in place of the gauge, with the `DEFAULT_RSS_BUCKETS`
+    /// unless `rss_buckets` is called.
+    ///
+    /// The histogram counts every sample since the exporter was built, one per `interval`.
+    pub fn with_rss_histogram(mut self, histogram: bool) -> Self {
+        self.histogram = match (histogram, self.histogram.take()) {
+            (false, _) => None,
+            (true, histogram) => Some(histogram.unwrap_or_default()),
+        };
+        self
+    }
+
+    /// Export the RSS histogram with these upper bounds, in bytes, see `RssHistogram::new`.
+    pub fn rss_buckets(mut self, bounds: &[u64]) -> Self {
+        self.histogram = Some(RssHistogram::new(bounds));
+        self
+    }
+
+    /// Export only these fields of [`ProcessMemoryInfo`], by their names,
+    /// `resident_set_size` and `virtual_memory_size` by default.
+    ///
+    /// `resident_set_size` and `virtual_memory_size` are exported as
+    /// `process_resident_memory_bytes` and `process_virtual_memory_bytes`, the other fields as
+    /// `process_memory_<field>_bytes`. `InvalidInput` if a name is not a field on this
+    /// platform.
+    pub fn fields(mut self, names: &[&str]) -> Result<Self> {
+        self.fields = ProcessMemoryInfo::field_names(names)?;
+        Ok(self)
+    }
+
+    /// Take a first sample and start sampling in the background.
+    pub fn build(self) -> PrometheusExporter {
+        let PrometheusExporterBuilder {
+            interval,
+            cpu,
+            io,
+            fields,
+            mut histogram,
+        } = self;
+        let exposition = Arc::new(Mutex::new(render(&fields, cpu, io, histogram.as_mut())));
+
+        let cache = exposition.clone();
+        let mut runner = PollRunner::new(interval);
+        // the first tick of the runner happens right away, the sample above stands for it.
+        let mut sampled = true;
+        runner.add(move || {
+            if std::mem::take(&mut sampled) {
+                return;
+            }
+            let rendered = render(&fields, cpu, io, histogram.as_mut());
+            *cache.lock().unwrap_or_else(|e| e.into_inner()) = rendered;
+        });
+        PrometheusExporter {
+            exposition,
+            _runner: runner.start(),
+        }
+    }
+}
+
+/// The sampling thread of a Prometheus endpoint, it stops when dropped.
+#[must_use = "the sampling thread stops when the exporter is dropped"]
+pub struct PrometheusExporter {
+    exposition: Arc<Mutex<String>>,
+    _runner: PollHandle,
+}
+
+impl PrometheusExporter {
+    pub fn builder() -> PrometheusExporterBuilder {
+        PrometheusExporterBuilder {
+            interval: Duration::from_secs(15),
+            cpu: false,
+            io: false,
+            fields: DEFAULT_FIELDS.to_vec(),
+            histogram: None,
+        }
+    }
+
+    /// The exposition of the last sample, without any syscall.
+    pub fn render(&self) -> String {
+        self.exposition
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// The distribution of RSS samples in cumulative buckets, rendered as a Prometheus histogram.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RssHistogram {
+    bounds: Vec<u64>,
+    /// the samples of each bucket alone, the last one is `+Inf`.
+    counts: Vec<u64>,
+    sum: u128,
+    count: u64,
+}
+
+impl Default for RssHistogram {
+    fn default() -> Self {
+        Self::new(&DEFAULT_RSS_BUCKETS)
+    }
+}
+
+impl RssHistogram {
+    /// Buckets with the upper bounds `bounds` in bytes, inclusive, sorted and deduplicated.
+    /// The `+Inf` bucket is always added.
+    pub fn new(bounds: &[u64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        RssHistogram {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, rss: u64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < rss);
+        self.counts[bucket] += 1;
+        self.sum += rss as u128;
+        self.count += 1;
+    }
+
+    /// The number of samples at most each bound, `+Inf` last.
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            })
+            .collect()
+    }
+
+    pub fn sum(&self) -> u128 {
+        self.sum
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Append the histogram as `name`, with its `_bucket`, `_sum` and `_count` series.
+    pub fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let bounds = self.bounds.iter().map(|bound| bound.to_string());
+        for (le, count) in bounds
+            .chain(std::iter::once("+Inf".to_string()))
+            .zip(self.cumulative_counts())
+        {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, count);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Sample and format, a metric whose read fails is left out. The RSS is added to `histogram`
+/// and exported through it rather than as a gauge, if there is one.
+fn render(fields: &[&str], cpu: bool, io: bool, histogram: Option<&mut RssHistogram>) -> String {
+    let mut out = String::new();
+    if let Ok(info) = crate::mem::get_process_memory_info() {
+        for (field, value) in info.fields() {
+            if !fields.contains(&field) || (field == "resident_set_size" && histogram.is_some()) {
+                continue;
+            }
+            match field {
+                "resident_set_size" => metric(
+                    &mut out,
+                    "process_resident_memory_bytes",
+                    "gauge",
+                    "Resident memory size in bytes.",
+                    value,
+                ),
+                "virtual_memory_size" => metric(
+                    &mut out,
+                    "process_virtual_memory_bytes",
+                    "gauge",
+                    "Virtual memory size in bytes.",
+                    value,
+                ),
+                _ => metric(
+                    &mut out,
+                    &format!("process_memory_{}_bytes", field),
+                    "gauge",
+                    &format!("The {} memory field in bytes.", field),
+                    value,
+                ),
+            }
+        }
+        if let Some(histogram) = histogram {
+            histogram.observe(info.resident_set_size);
+            histogram.render(
+                &mut out,
+                "process_resident_memory_bytes",
+                "Resident memory size in bytes.",
+            );
+        }
+    }
+    if cpu {
+        if let Ok(cpu_time) = crate::cpu::cpu_time() {
+            metric(
+                &mut out,
+                "process_cpu_seconds_total",
+                "counter",
+                "Total user and system CPU time spent in seconds.",
+                cpu_time.as_secs_f64(),
+            );
+        }
+    }
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "windows"
+    ))]
+    if io {
+        if let Ok(stats) = crate::io::get_process_io_stats() {
+            metric(
+                &mut out,
+                "process_io_read_bytes_total",
+                "counter",
+                "Total bytes read from storage.",
+                stats.read_bytes,
+            );
+            metric(
+                &mut out,
+                "process_io_write_bytes_total",
+                "counter",
+                "Total bytes written to storage.",
+                stats.write_bytes,
+            );
+        }
+    }
+    #[cfg(target_os = "ios")]
+    let _ = io;
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_render() {
+        let exporter = PrometheusExporter::builder()
+            .interval(Duration::from_millis(10))
+            .with_cpu(true)
+            .with_io(true)
+            .build();
+        let first = exporter.render();
+        assert!(first.contains("# TYPE process_resident_memory_bytes gauge\n"));
+        assert!(first.contains("\nprocess_cpu_seconds_total "));
+        #[cfg(target_os = "linux")]
+        assert!(first.contains("\nprocess_io_read_bytes_total "));
+
+        // the cpu time grows as we spin, a later sample must show it.
+        let mut x = 1_000_000u64;
+        std::hint::black_box(&mut x);
+        let start = Instant::now();
+        loop {
+            std::hint::black_box((0..x).sum::<u64>());
+            if exporter.render() != first {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10));
+        }
+    }
+
+    #[test]
+    fn test_memory_only() {
+        let exposition = PrometheusExporter::builder().build().render();
+        assert!(exposition.contains("\nprocess_virtual_memory_bytes "));
+        assert!(!exposition.contains("cpu"));
+        assert!(!exposition.contains("io_"));
+    }
+
+    #[test]
+    fn test_fields() {
+        let exposition = PrometheusExporter::builder()
+            .fields(&["resident_set_size"])
+            .unwrap()
+            .build()
+            .render();
+        assert!(exposition.contains("\nprocess_resident_memory_bytes "));
+        assert!(!exposition.contains("process_virtual_memory_bytes"));
+
+        #[cfg(target_os = "linux")]
+        {
+            let exposition = PrometheusExporter::builder()
+                .fields(&["shared"])
+                .unwrap()
+                .build()
+                .render();
+            assert!(exposition.contains("# TYPE process_memory_shared_bytes gauge\n"));
+            assert!(!exposition.contains("process_resident_memory_bytes"));
+        }
+
+        let err = PrometheusExporter::builder()
+            .fields(&["resident_set_size", "rss"])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Unknown memory field rss");
+    }
+
+    #[test]
+    fn test_rss_histogram() {
+        const MIB: u64 = 1 << 20;
+        let mut histogram = RssHistogram::new(&[4 * MIB, MIB, 2 * MIB, MIB]);
+        for rss in [MIB / 2, MIB, 3 * MIB, 3 * MIB, 100 * MIB] {
+            histogram.observe(rss);
+        }
+        // the bounds are inclusive.
+        assert_eq!(histogram.cumulative_counts(), [2, 2, 4, 5]);
+        assert_eq!(histogram.sum(), (MIB / 2 + 107 * MIB) as u128);
+        assert_eq!(histogram.count(), 5);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "rss", "RSS.");
+        assert_eq!(
+            out,
+            format!(
+                "# HELP rss RSS.\n\
+                 # TYPE rss histogram\n\
+                 rss_bucket{{le=\"1048576\"}} 2\n\
+                 rss_bucket{{le=\"2097152\"}} 2\n\
+                 rss_bucket{{le=\"4194304\"}} 4\n\
+                 rss_bucket{{le=\"+Inf\"}} 5\n\
+                 rss_sum {}\n\
+                 rss_count 5\n",
+                MIB / 2 + 107 * MIB
+            )
+        );
+
+        let default = RssHistogram::default();
+        assert_eq!(default.cumulative_counts().len(), 18);
+    }
+
+    #[test]
+    fn test_histogram_exporter() {
+        let exposition = PrometheusExporter::builder()
+            .with_rss_histogram(true)
+            .build()
+            .render();
+        assert!(exposition.contains("# TYPE process_resident_memory_bytes histogram\n"));
+        assert!(
+            exposition.contains("\nprocess_resident_memory_bytes_bucket{le=\"68719476736\"} 1\n")
+        );
+        assert!(exposition.contains("\nprocess_resident_memory_bytes_count 1\n"));
+        assert!(!exposition.contains("\nprocess_resident_memory_bytes "));
+        assert!(exposition.contains("\nprocess_virtual_memory_bytes "));
+
+        let exposition = PrometheusExporter::builder()
+            .rss_buckets(&[1])
+            .build()
+            .render();
+        assert!(exposition.contains("\nprocess_resident_memory_bytes_bucket{le=\"1\"} 0\n"));
+        assert!(exposition.contains("\nprocess_resident_memory_bytes_bucket{le=\"+Inf\"} 1\n"));
+    }
+
+    #[test]
+    fn test_histogram_first_sample_counted_once() {
+        let exporter = PrometheusExporter::builder()
+            .interval(Duration::from_secs(3600))
+            .with_rss_histogram(true)
+            .build();
+        assert!(exporter
+            .render()
+            .contains("\nprocess_resident_memory_bytes_count 1\n"));
+        // past the first tick of the runner.
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(exporter
+            .render()
+            .contains("\nprocess_resident_memory_bytes_count 1\n"));
+    }
+}
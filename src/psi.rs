@@ -0,0 +1,166 @@
+//! Pressure stall information of the system, from `/proc/pressure` on Linux 4.20 and later.
+//!
+//! The percentages of time tasks were stalled waiting for a resource are a better pressure
+//! signal than usage counters: a full page cache is fine, tasks waiting on reclaim are not.
+//!
+//! ```
+//! # use workflow_perf_monitor::psi::{get_psi, PsiResource};
+//! # #[cfg(target_os = "linux")]
+//! match get_psi(PsiResource::Memory) {
+//!     Ok(psi) => println!("stalled on memory {:.2}% of the last 10s", psi.some_avg10),
+//!     Err(e) if e.kind() == std::io::ErrorKind::Unsupported => println!("no PSI"),
+//!     Err(e) => panic!("{}", e),
+//! }
+//! ```
+use std::io::{Error, ErrorKind, Result};
+
+/// The resources of `/proc/pressure`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PsiResource {
+    Cpu,
+    Io,
+    Memory,
+}
+
+impl PsiResource {
+    /// The name of the file in `/proc/pressure`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PsiResource::Cpu => "cpu",
+            PsiResource::Io => "io",
+            PsiResource::Memory => "memory",
+        }
+    }
+}
+
+/// The stall percentages averaged over the last 10, 60 and 300 seconds, in `0.0..=100.0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PsiStats {
+    /// some tasks were stalled on the resource.
+    pub some_avg10: f64,
+    pub some_avg60: f64,
+    pub some_avg300: f64,
+    /// all non-idle tasks were stalled at once, the time was lost for the whole system. 0 for
+    /// the cpu on kernels older than 5.13, which only report `some`.
+    pub full_avg10: f64,
+    pub full_avg60: f64,
+    pub full_avg300: f64,
+}
+
+/// Parse the content of a `/proc/pressure` file.
+///
+/// ```text
+/// some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// ```
+///
+/// The `some` line is required, a missing `full` line leaves its fields to 0.
+pub fn parse_psi(psi: &str) -> Result<PsiStats> {
+    let invalid = |line: &str| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid pressure line {:?}", line),
+        )
+    };
+    let mut stats = PsiStats::default();
+    let mut some = false;
+    for line in psi.lines() {
+        let mut fields = line.split_ascii_whitespace();
+        let avgs = match fields.next() {
+            Some("some") => {
+                some = true;
+                [
+                    &mut stats.some_avg10,
+                    &mut stats.some_avg60,
+                    &mut stats.some_avg300,
+                ]
+            }
+            Some("full") => [
+                &mut stats.full_avg10,
+                &mut stats.full_avg60,
+                &mut stats.full_avg300,
+            ],
+            _ => continue,
+        };
+        for (key, avg) in ["avg10", "avg60", "avg300"].iter().zip(avgs) {
+            *avg = fields
+                .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid(line))?;
+        }
+    }
+    if !some {
+        return Err(invalid(psi));
+    }
+    Ok(stats)
+}
+
+/// Get the pressure stall information of `resource` for the whole system.
+///
+/// Fails with `ErrorKind::Unsupported` on kernels without PSI, older than 4.20 or built or
+/// booted without it (`psi=0`).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn get_psi(resource: PsiResource) -> Result<PsiStats> {
+    let path = format!("pressure/{}", resource.name());
+    match crate::procfs::read_to_string(&path) {
+        Ok(psi) => parse_psi(&psi),
+        Err(e) if e.kind() == ErrorKind::NotFound || e.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+            Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("pressure stall information is not available: {}", e),
+            ))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_psi() {
+        let psi = "\
+some avg10=1.53 avg60=0.87 avg300=0.22 total=12345678
+full avg10=0.50 avg60=0.25 avg300=0.05 total=2345678
+";
+        assert_eq!(
+            parse_psi(psi).unwrap(),
+            PsiStats {
+                some_avg10: 1.53,
+                some_avg60: 0.87,
+                some_avg300: 0.22,
+                full_avg10: 0.50,
+                full_avg60: 0.25,
+                full_avg300: 0.05,
+            }
+        );
+
+        // cpu before 5.13.
+        let stats = parse_psi("some avg10=2.00 avg60=1.00 avg300=0.50 total=42\n").unwrap();
+        assert_eq!(stats.some_avg10, 2.0);
+        assert_eq!(stats.full_avg300, 0.0);
+
+        for invalid in [
+            "",
+            "full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n",
+            "some avg10=x\n",
+        ] {
+            assert_eq!(
+                parse_psi(invalid).unwrap_err().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_get_psi() {
+        for resource in [PsiResource::Cpu, PsiResource::Io, PsiResource::Memory] {
+            match get_psi(resource) {
+                Ok(stats) => assert!((0.0..=100.0).contains(&stats.some_avg10)),
+                Err(e) => assert_eq!(e.kind(), ErrorKind::Unsupported),
+            }
+        }
+    }
+}
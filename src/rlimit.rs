@@ -0,0 +1,88 @@
+//! Resource limits of current process, from `getrlimit`. Not available on Windows.
+//!
+//! ```
+//! # use workflow_perf_monitor::rlimit::{get_rlimit, Resource};
+//! let limit = get_rlimit(Resource::NoFile).unwrap();
+//! println!("at most {:?} open files", limit.soft);
+//! ```
+use std::io::{Error, Result};
+
+/// The resources of `getrlimit`, see <https://man7.org/linux/man-pages/man2/getrlimit.2.html>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// `RLIMIT_AS`, the size of the virtual address space in bytes.
+    AddressSpace,
+    /// `RLIMIT_DATA`, the size of the data segment in bytes, heap included.
+    Data,
+    /// `RLIMIT_STACK`, the size of the main thread stack in bytes.
+    Stack,
+    /// `RLIMIT_MEMLOCK`, the memory which may be locked in RAM in bytes, as `mlock` does.
+    MemLock,
+    /// `RLIMIT_NOFILE`, one more than the highest file descriptor number which may be opened.
+    NoFile,
+}
+
+/// A soft and hard limit, `None` is `RLIM_INFINITY`.
+///
+/// The soft limit is the one enforced, the hard limit is the ceiling an unprivileged process
+/// may raise it to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceLimit {
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+// rlim_t is not u64 on every target.
+#[allow(clippy::unnecessary_cast)]
+fn limit(value: libc::rlim_t) -> Option<u64> {
+    if value == libc::RLIM_INFINITY {
+        None
+    } else {
+        Some(value as u64)
+    }
+}
+
+/// Get the soft and hard limits of `resource`.
+pub fn get_rlimit(resource: Resource) -> Result<ResourceLimit> {
+    let resource = match resource {
+        Resource::AddressSpace => libc::RLIMIT_AS,
+        Resource::Data => libc::RLIMIT_DATA,
+        Resource::Stack => libc::RLIMIT_STACK,
+        Resource::MemLock => libc::RLIMIT_MEMLOCK,
+        Resource::NoFile => libc::RLIMIT_NOFILE,
+    };
+    let mut rlimit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(resource, &mut rlimit) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(ResourceLimit {
+        soft: limit(rlimit.rlim_cur),
+        hard: limit(rlimit.rlim_max),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_rlimit() {
+        for resource in [
+            Resource::AddressSpace,
+            Resource::Data,
+            Resource::Stack,
+            Resource::MemLock,
+            Resource::NoFile,
+        ] {
+            let limit = get_rlimit(resource).unwrap();
+            if let (Some(soft), Some(hard)) = (limit.soft, limit.hard) {
+                assert!(soft <= hard);
+            }
+        }
+        let nofile = get_rlimit(Resource::NoFile).unwrap();
+        assert!(nofile.soft.unwrap_or(u64::MAX) > 2);
+    }
+}
@@ -0,0 +1,221 @@
+//! Run several samplers on one shared schedule.
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use workflow_perf_monitor::runner::PollRunner;
+//! use workflow_perf_monitor::cpu::ProcessStat;
+//! use workflow_perf_monitor::mem::get_process_memory_info;
+//!
+//! let mut runner = PollRunner::new(Duration::from_secs(1));
+//! runner.add(|| {
+//!     let _ = get_process_memory_info();
+//! });
+//! let mut stat = ProcessStat::cur().unwrap();
+//! runner.add(move || {
+//!     let _ = stat.cpu();
+//! });
+//! let handle = runner.start();
+//! handle.stop().unwrap();
+//! ```
+use std::{
+    convert::TryFrom,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+type Task = Box<dyn FnMut() + Send>;
+
+/// Closures called one after another on every tick, on a single background thread.
+///
+/// Ticks are scheduled at fixed times from the start, a tick which can't be honored because
+/// the previous one ran late is skipped rather than run in a burst.
+pub struct PollRunner {
+    interval: Duration,
+    tasks: Vec<Task>,
+}
+
+/// The shortest interval of a `PollRunner`, a shorter one is raised to it.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+impl PollRunner {
+    /// `interval` is at least `MIN_INTERVAL`.
+    pub fn new(interval: Duration) -> Self {
+        PollRunner {
+            interval: interval.max(MIN_INTERVAL),
+            tasks: vec![],
+        }
+    }
+
+    /// Add a closure to call on every tick.
+    pub fn add(&mut self, task: impl FnMut() + Send + 'static) -> &mut Self {
+        self.tasks.push(Box::new(task));
+        self
+    }
+
+    /// Start the background thread, the first tick happens right away.
+    pub fn start(self) -> PollHandle {
+        let PollRunner {
+            interval,
+            mut tasks,
+        } = self;
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let panics = Arc::new(AtomicU64::new(0));
+
+        let panic_count = panics.clone();
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            loop {
+                for task in tasks.iter_mut() {
+                    // A panicking closure must not take the others down.
+                    if catch_unwind(AssertUnwindSafe(task)).is_err() {
+                        panic_count.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+
+                let timeout = until_next_tick(interval, start.elapsed());
+                match stop_rx.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+
+        PollHandle {
+            stop: Some(stop_tx),
+            handle: Some(handle),
+            panics,
+        }
+    }
+}
+
+/// The time from `elapsed` to the first tick after it, the ticks being every `interval` from 0.
+fn until_next_tick(interval: Duration, elapsed: Duration) -> Duration {
+    let interval_nanos = interval.as_nanos();
+    let next = (elapsed.as_nanos() / interval_nanos + 1) * interval_nanos;
+    let next = Duration::new(
+        u64::try_from(next / 1_000_000_000).unwrap_or(u64::MAX),
+        (next % 1_000_000_000) as u32,
+    );
+    next.saturating_sub(elapsed)
+}
+
+/// The running `PollRunner`, it stops when dropped.
+#[must_use = "the polling thread stops when the handle is dropped"]
+pub struct PollHandle {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+    panics: Arc<AtomicU64>,
+}
+
+impl PollHandle {
+    /// How many calls panicked so far.
+    pub fn panics(&self) -> u64 {
+        self.panics.load(Ordering::Relaxed)
+    }
+
+    /// Stop the polling thread and wait for it, it is not interrupted in the middle of a tick.
+    pub fn stop(mut self) -> thread::Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> thread::Result<()> {
+        self.stop.take();
+        match self.handle.take() {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PollHandle {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wait_for(cond: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !cond() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_two_closures_tick() {
+        let first = Arc::new(AtomicU64::new(0));
+        let second = Arc::new(AtomicU64::new(0));
+
+        let mut runner = PollRunner::new(Duration::from_millis(5));
+        let counter = first.clone();
+        runner.add(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let counter = second.clone();
+        runner.add(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let handle = runner.start();
+
+        wait_for(|| first.load(Ordering::SeqCst) >= 3 && second.load(Ordering::SeqCst) >= 3);
+        assert!(handle.stop().is_ok());
+        let ticks = first.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(first.load(Ordering::SeqCst), ticks);
+    }
+
+    #[test]
+    fn test_until_next_tick() {
+        let ms = Duration::from_millis;
+        assert_eq!(until_next_tick(ms(10), ms(0)), ms(10));
+        assert_eq!(until_next_tick(ms(10), ms(25)), ms(5));
+        // a tick missed while running late is skipped.
+        assert_eq!(until_next_tick(ms(10), ms(30)), ms(10));
+        let late = Duration::from_secs(100 * 24 * 3600);
+        assert_eq!(
+            until_next_tick(Duration::from_nanos(1), late),
+            Duration::from_nanos(1)
+        );
+    }
+
+    #[test]
+    fn test_zero_interval() {
+        let ticks = Arc::new(AtomicU64::new(0));
+        let mut runner = PollRunner::new(Duration::ZERO);
+        assert_eq!(runner.interval, MIN_INTERVAL);
+        let counter = ticks.clone();
+        runner.add(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let handle = runner.start();
+        wait_for(|| ticks.load(Ordering::SeqCst) >= 3);
+        assert!(handle.stop().is_ok());
+    }
+
+    #[test]
+    fn test_panic_isolation() {
+        let ticks = Arc::new(AtomicU64::new(0));
+
+        let mut runner = PollRunner::new(Duration::from_millis(5));
+        runner.add(|| panic!("a broken sampler"));
+        let counter = ticks.clone();
+        runner.add(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let handle = runner.start();
+
+        wait_for(|| ticks.load(Ordering::SeqCst) >= 3);
+        assert!(handle.panics() >= 2);
+        assert!(handle.stop().is_ok());
+    }
+}
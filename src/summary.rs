@@ -0,0 +1,167 @@
+//! Everything this crate can read about current process at once, as a readable report.
+//!
+//! ```
+//! println!("{}", workflow_perf_monitor::summary().unwrap());
+//! ```
+use std::{fmt, io::Result, time::Duration};
+
+use crate::mem::{format_bytes, get_process_memory_info, ProcessMemoryInfo};
+
+/// A one-shot report of current process, see `summary`.
+///
+/// The values which couldn't be read, or aren't available on the platform, are `None` and
+/// shown as `n/a`. The limits are `Some(None)` when there is none, shown as `unlimited`.
+#[derive(Clone)]
+pub struct Summary {
+    pub memory: ProcessMemoryInfo,
+    /// see `mem::get_effective_memory_limit`.
+    pub memory_limit: Option<Option<u64>>,
+    /// the cpu time consumed since the process started.
+    pub cpu_time: Option<Duration>,
+    pub processors: Option<usize>,
+    /// only read on Linux and android.
+    pub threads: Option<u64>,
+    pub fd_count: Option<usize>,
+    /// the soft `RLIMIT_NOFILE`, `None` on Windows.
+    pub fd_limit: Option<Option<u64>>,
+}
+
+/// Read the memory, CPU, thread and fd usage of current process and their limits.
+///
+/// Only the memory info is required, the other reads which fail are left out as `None`.
+pub fn summary() -> Result<Summary> {
+    Ok(Summary {
+        memory: get_process_memory_info()?,
+        memory_limit: crate::mem::get_effective_memory_limit().ok(),
+        cpu_time: crate::cpu::total_cpu_time().ok(),
+        processors: crate::cpu::processor_numbers().ok(),
+        threads: threads(),
+        fd_count: crate::fd::fd_count_cur().ok(),
+        fd_limit: fd_limit(),
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn threads() -> Option<u64> {
+    let status = crate::procfs::read_to_string("self/status").ok()?;
+    crate::mem::ProcStatusView::new(&status).threads()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn threads() -> Option<u64> {
+    None
+}
+
+#[cfg(not(target_os = "windows"))]
+fn fd_limit() -> Option<Option<u64>> {
+    use crate::rlimit::{get_rlimit, Resource};
+    get_rlimit(Resource::NoFile).ok().map(|limit| limit.soft)
+}
+
+#[cfg(target_os = "windows")]
+fn fd_limit() -> Option<Option<u64>> {
+    None
+}
+
+/// Shorter labels for the memory fields, `resident_set_size` is `rss`.
+fn label(field: &str) -> String {
+    match field {
+        "resident_set_size" => "rss".to_string(),
+        "resident_set_size_peak" => "rss peak".to_string(),
+        "virtual_memory_size" => "vsz".to_string(),
+        field => field.replace('_', " "),
+    }
+}
+
+fn or_na<T>(value: Option<T>, format: impl FnOnce(T) -> String) -> String {
+    value.map_or_else(|| "n/a".to_string(), format)
+}
+
+fn limit(limit: Option<Option<u64>>, format: impl FnOnce(u64) -> String) -> String {
+    or_na(limit, |limit| {
+        limit.map_or_else(|| "unlimited".to_string(), format)
+    })
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "memory")?;
+        for (field, value) in self.memory.fields() {
+            writeln!(f, "  {:<16}{}", label(field), format_bytes(value))?;
+        }
+        writeln!(
+            f,
+            "  {:<16}{}",
+            "limit",
+            limit(self.memory_limit, format_bytes)
+        )?;
+        writeln!(f, "cpu")?;
+        writeln!(
+            f,
+            "  {:<16}{}",
+            "time",
+            or_na(self.cpu_time, |time| format!("{:.3}s", time.as_secs_f64()))
+        )?;
+        writeln!(
+            f,
+            "  {:<16}{}",
+            "processors",
+            or_na(self.processors, |n| n.to_string())
+        )?;
+        writeln!(
+            f,
+            "  {:<16}{}",
+            "threads",
+            or_na(self.threads, |n| n.to_string())
+        )?;
+        writeln!(f, "fd")?;
+        writeln!(
+            f,
+            "  {:<16}{}",
+            "open",
+            or_na(self.fd_count, |n| n.to_string())
+        )?;
+        write!(
+            f,
+            "  {:<16}{}",
+            "limit",
+            limit(self.fd_limit, |n| n.to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let summary = summary().unwrap();
+        let report = summary.to_string();
+        assert!(!report.is_empty());
+        let rss = format!(
+            "  rss             {}\n",
+            format_bytes(summary.memory.resident_set_size)
+        );
+        assert!(report.contains(&rss), "{}", report);
+        assert!(report.ends_with(&limit(summary.fd_limit, |n| n.to_string())));
+    }
+
+    #[test]
+    fn test_missing_values() {
+        let summary = Summary {
+            memory: ProcessMemoryInfo::default(),
+            memory_limit: Some(None),
+            cpu_time: None,
+            processors: Some(4),
+            threads: None,
+            fd_count: None,
+            fd_limit: None,
+        };
+        let report = summary.to_string();
+        assert!(report.contains("  limit           unlimited\n"));
+        assert!(report.contains("  time            n/a\n"));
+        assert!(report.contains("  processors      4\n"));
+        assert!(report.ends_with("  limit           n/a"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Pinning the calling thread to a CPU core, the index of a logical CPU.
+//!
+//! MacOS and iOS have no thread pinning, affinity tags only hint the scheduler and Apple
+//! silicon ignores them, so `pin_current_thread` does nothing there, nor on the other
+//! platforms but Linux, Android and Windows.
+use std::io::Result;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+use std::io::{Error, ErrorKind};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn pin_current_thread(core: usize) -> Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("No CPU core {}", core),
+        ));
+    }
+    let mut set = unsafe { std::mem::zeroed::<libc::cpu_set_t>() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    // 0 is the calling thread.
+    let ret = unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) };
+    if ret != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn pin_current_thread(core: usize) -> Result<()> {
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadAffinityMask};
+    // a thread runs in its processor group, of at most 64 cores.
+    if core >= usize::BITS as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("No CPU core {} in the processor group", core),
+        ));
+    }
+    let previous = unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << core) };
+    if previous == 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "windows")))]
+pub fn pin_current_thread(_core: usize) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+pub(crate) mod test {
+    use super::*;
+
+    pub(crate) fn current_affinity() -> Vec<usize> {
+        let mut set = unsafe { std::mem::zeroed::<libc::cpu_set_t>() };
+        let ret = unsafe { libc::sched_getaffinity(0, std::mem::size_of_val(&set), &mut set) };
+        assert_eq!(ret, 0);
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+            .collect()
+    }
+
+    #[test]
+    fn test_pin_current_thread() {
+        let core = *current_affinity().last().unwrap();
+        std::thread::spawn(move || {
+            pin_current_thread(core).unwrap();
+            assert_eq!(current_affinity(), [core]);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            pin_current_thread(usize::MAX).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+}
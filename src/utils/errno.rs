@@ -0,0 +1,10 @@
+/// The thread local `errno` of libc.
+pub fn errno() -> *mut libc::c_int {
+    #[cfg(target_os = "linux")]
+    let errno = unsafe { libc::__errno_location() };
+    #[cfg(target_os = "android")]
+    let errno = unsafe { libc::__errno() };
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    let errno = unsafe { libc::__error() };
+    errno
+}
@@ -0,0 +1,152 @@
+//! Readable errors for mach `kern_return_t` codes.
+//!
+//! The codes are copied from `mach/kern_return.h` so the mapping can be tested on every platform.
+use std::io::{Error, ErrorKind};
+
+pub const KERN_SUCCESS: i32 = 0;
+pub const KERN_INVALID_ADDRESS: i32 = 1;
+pub const KERN_PROTECTION_FAILURE: i32 = 2;
+pub const KERN_NO_SPACE: i32 = 3;
+pub const KERN_INVALID_ARGUMENT: i32 = 4;
+pub const KERN_FAILURE: i32 = 5;
+pub const KERN_RESOURCE_SHORTAGE: i32 = 6;
+pub const KERN_NO_ACCESS: i32 = 8;
+pub const KERN_ABORTED: i32 = 14;
+pub const KERN_INVALID_NAME: i32 = 15;
+pub const KERN_INVALID_TASK: i32 = 16;
+pub const KERN_INVALID_RIGHT: i32 = 17;
+pub const KERN_INVALID_VALUE: i32 = 18;
+pub const KERN_INVALID_CAPABILITY: i32 = 20;
+pub const KERN_TERMINATED: i32 = 37;
+pub const KERN_NOT_SUPPORTED: i32 = 46;
+pub const KERN_OPERATION_TIMED_OUT: i32 = 49;
+
+/// A short description of a `kern_return_t`.
+pub fn kern_return_message(code: i32) -> &'static str {
+    match code {
+        KERN_SUCCESS => "success",
+        KERN_INVALID_ADDRESS => "invalid address",
+        KERN_PROTECTION_FAILURE => "protection failure",
+        KERN_NO_SPACE => "no space",
+        KERN_INVALID_ARGUMENT => "invalid argument",
+        KERN_FAILURE => "failure",
+        KERN_RESOURCE_SHORTAGE => "resource shortage",
+        KERN_NO_ACCESS => "access denied",
+        KERN_ABORTED => "aborted",
+        KERN_INVALID_NAME => "invalid port name",
+        KERN_INVALID_TASK => "invalid task",
+        KERN_INVALID_RIGHT => "invalid port right",
+        KERN_INVALID_VALUE => "invalid value",
+        KERN_INVALID_CAPABILITY => "invalid capability",
+        KERN_TERMINATED => "target terminated",
+        KERN_NOT_SUPPORTED => "not supported",
+        KERN_OPERATION_TIMED_OUT => "operation timed out",
+        _ => "unknown error",
+    }
+}
+
+fn kern_return_kind(code: i32) -> ErrorKind {
+    match code {
+        KERN_PROTECTION_FAILURE | KERN_NO_ACCESS => ErrorKind::PermissionDenied,
+        KERN_INVALID_ADDRESS | KERN_INVALID_ARGUMENT | KERN_INVALID_VALUE => {
+            ErrorKind::InvalidInput
+        }
+        KERN_INVALID_NAME | KERN_INVALID_TASK | KERN_TERMINATED => ErrorKind::NotFound,
+        KERN_OPERATION_TIMED_OUT => ErrorKind::TimedOut,
+        KERN_ABORTED => ErrorKind::Interrupted,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// A failed mach call, the payload of the errors of `kern_return_error`, for `PerfError` to
+/// tell them from the other io errors.
+#[derive(Debug)]
+pub struct KernReturnError {
+    call: String,
+    code: i32,
+}
+
+impl KernReturnError {
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+}
+
+impl std::fmt::Display for KernReturnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} failed: {} (DARWIN_KERN_RET_CODE:{})",
+            self.call,
+            kern_return_message(self.code),
+            self.code
+        )
+    }
+}
+
+impl std::error::Error for KernReturnError {}
+
+/// Build an error for the failed mach call named `call`.
+///
+/// The raw code stays in the message as `DARWIN_KERN_RET_CODE:{code}`.
+pub fn kern_return_error(call: &str, code: i32) -> Error {
+    Error::new(
+        kern_return_kind(code),
+        KernReturnError {
+            call: call.to_string(),
+            code,
+        },
+    )
+}
+
+/// `task_for_pid` reports a missing entitlement, SIP protection or a missing pid all as `KERN_FAILURE`.
+/// The first two are by far the most common, so it's surfaced as `PermissionDenied`.
+pub fn task_for_pid_error(pid: u32, code: i32) -> Error {
+    if code != KERN_FAILURE {
+        return kern_return_error("task_for_pid", code);
+    }
+    Error::new(
+        ErrorKind::PermissionDenied,
+        format!(
+            "task_for_pid({}) was denied: access to the task port of another process requires \
+             the com.apple.security.get-task-allow / task_for_pid entitlements or running as root, \
+             and is refused for SIP protected processes (DARWIN_KERN_RET_CODE:{})",
+            pid, code
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_task_for_pid_failure() {
+        let err = task_for_pid_error(1, KERN_FAILURE);
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let msg = err.to_string();
+        assert!(msg.contains("entitlements"));
+        assert!(msg.contains("root"));
+        assert!(msg.contains("DARWIN_KERN_RET_CODE:5"));
+
+        let err = task_for_pid_error(1, KERN_INVALID_ARGUMENT);
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "task_for_pid failed: invalid argument (DARWIN_KERN_RET_CODE:4)"
+        );
+    }
+
+    #[test]
+    fn test_kern_return_error() {
+        let err = kern_return_error("task_info", KERN_INVALID_TASK);
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "task_info failed: invalid task (DARWIN_KERN_RET_CODE:16)"
+        );
+        assert_eq!(kern_return_message(12345), "unknown error");
+        let inner = err.get_ref().unwrap().downcast_ref::<KernReturnError>();
+        assert_eq!(inner.map(KernReturnError::code), Some(KERN_INVALID_TASK));
+    }
+}
@@ -1,3 +1,9 @@
+pub mod affinity;
+#[cfg(not(target_os = "windows"))]
+pub mod errno;
+#[cfg_attr(not(any(target_os = "macos", target_os = "ios")), allow(dead_code))]
+pub mod kern_return;
+#[cfg(windows)]
 pub mod ptr_upgrade;
 #[cfg(windows)]
 pub mod windows_handle;
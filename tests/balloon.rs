@@ -0,0 +1,27 @@
+//! The per-pid memory functions against a child of a known size.
+//!
+//! Run with `cargo test --features test-util`.
+#![cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+
+use workflow_perf_monitor::mem::{get_process_memory_info_for_pid, spawn_balloon};
+
+#[test]
+fn test_balloon_rss() {
+    const SIZE: u64 = 64 << 20;
+    let mut child = spawn_balloon(SIZE).unwrap();
+    let info = get_process_memory_info_for_pid(child.id());
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let rss = info.unwrap().resident_set_size;
+    // the balloon, plus the runtime and libraries of the binary.
+    assert!(rss >= SIZE, "{}", rss);
+    assert!(rss <= SIZE + (16 << 20), "{}", rss);
+}
+
+#[test]
+fn test_balloon_exits_with_stdin() {
+    let mut child = spawn_balloon(1 << 20).unwrap();
+    drop(child.stdin.take());
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,49 @@
+//! `reset_peak_rss` in a process of its own: it resets the peak of the calling process, and
+//! the spike it measures would make the RSS of the tests running in parallel in the lib test
+//! binary jump.
+#![cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+
+use workflow_perf_monitor::mem::{get_process_memory_info, reset_peak_rss};
+
+const SIZE: usize = 256 << 20;
+
+fn spike() -> Vec<u8> {
+    let mut spike = vec![0u8; SIZE];
+    for page in spike.chunks_mut(4096) {
+        page[0] = 1;
+    }
+    std::hint::black_box(spike)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn test_reset_peak_rss() {
+    fn peak() -> u64 {
+        let path = workflow_perf_monitor::procfs::procfs_root().join("self/status");
+        std::fs::read_to_string(path)
+            .unwrap()
+            .parse::<workflow_perf_monitor::mem::ProcStatus>()
+            .unwrap()
+            .vm_hwm
+            .unwrap()
+    }
+
+    drop(spike());
+    let before = peak();
+    reset_peak_rss().unwrap();
+    let after = peak();
+    assert!(after + SIZE as u64 / 2 < before, "{} {}", before, after);
+    assert!(after >= get_process_memory_info().unwrap().resident_set_size / 2);
+}
+
+#[cfg(target_os = "windows")]
+#[test]
+fn test_reset_peak_rss() {
+    // the peak can't be reset, the working set is trimmed instead.
+    let spike = spike();
+    let before = get_process_memory_info().unwrap().resident_set_size;
+    reset_peak_rss().unwrap();
+    let after = get_process_memory_info().unwrap().resident_set_size;
+    assert!(after + SIZE as u64 / 2 < before, "{} {}", before, after);
+    drop(spike);
+}
@@ -0,0 +1,28 @@
+//! `release_free_memory` in a process of its own: the RSS it measures would move with the
+//! allocations of the tests running in parallel in the lib test binary.
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+use workflow_perf_monitor::mem::{get_process_memory_info, release_free_memory};
+
+#[test]
+fn test_release_free_memory() {
+    const CHUNKS: usize = 64 * 1024;
+    const CHUNK_SIZE: usize = 1024;
+    // Small chunks are served from the arena of this thread rather than mmap'ed, and freeing
+    // them doesn't unmap anything.
+    let chunks: Vec<Vec<u8>> = (0..CHUNKS).map(|_| vec![1u8; CHUNK_SIZE]).collect();
+    // Keep the top of the heap busy so `free` can't shrink it by itself.
+    let pin = vec![1u8; CHUNK_SIZE];
+    drop(chunks);
+
+    let before = get_process_memory_info().unwrap().resident_set_size;
+    release_free_memory().unwrap();
+    let after = get_process_memory_info().unwrap().resident_set_size;
+    assert!(
+        after + (CHUNKS * CHUNK_SIZE / 2) as u64 <= before,
+        "before: {}, after: {}",
+        before,
+        after
+    );
+    drop(pin);
+}